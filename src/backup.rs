@@ -0,0 +1,415 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{replace_file_contents, Environment, Error};
+
+/// A copy of a user mimeapps.list, taken before it was overwritten
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    id: String,
+    source: PathBuf,
+    path: PathBuf,
+    created: SystemTime,
+}
+
+impl Snapshot {
+    /// The snapshot's id, the UTC time it was taken, e.g., "20261017-153045.123"
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The path of the file the snapshot was taken of
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// The path of the copy
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn created(&self) -> SystemTime {
+        self.created
+    }
+
+    pub fn contents(&self) -> Result<String, Error> {
+        std::fs::read_to_string(&self.path).map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+/// How many snapshots are kept. For each file, at most `max_snapshots` are kept, and those older
+/// than `max_age` are removed; the newest snapshot of each file is always kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub max_snapshots: usize,
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_snapshots: 20,
+            max_age: Some(Duration::from_secs(90 * 24 * 60 * 60)),
+        }
+    }
+}
+
+/// Keeps snapshots of the user's mimeapps.list files in a directory, by default `$XDG_STATE_HOME/mimeassoc/backups`,
+/// so that changes such as resetting every assignment to the system defaults can be taken back after saving.
+/// Each snapshot is stored as `<id>.list`, alongside `<id>.source` which records the path it was taken of.
+#[derive(Clone, Debug)]
+pub struct BackupStore {
+    directory: PathBuf,
+    retention: RetentionPolicy,
+}
+
+impl BackupStore {
+    pub fn new<P>(directory: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            directory: directory.into(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+
+    /// The backup store of the environment's user, in `Environment::backups_dir`
+    pub fn for_environment(environment: &Environment) -> Self {
+        Self::new(environment.backups_dir())
+    }
+
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn retention(&self) -> RetentionPolicy {
+        self.retention
+    }
+
+    /// Take a snapshot of the file at `path`. Returns None, taking no snapshot, if the file doesn't
+    /// exist, or is unchanged since its newest snapshot.
+    pub fn snapshot<P>(&self, path: P) -> Result<Option<Snapshot>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let source = path.as_ref();
+        let contents = match std::fs::read_to_string(source) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::io(source, e)),
+        };
+
+        if let Some(newest) = self.snapshots_of(source)?.first() {
+            if newest.contents()? == contents {
+                return Ok(None);
+            }
+        }
+
+        std::fs::create_dir_all(&self.directory).map_err(|e| Error::io(&self.directory, e))?;
+
+        // snapshots taken within the same millisecond are told apart by a suffix
+        let timestamp = format_timestamp(SystemTime::now());
+        let mut id = timestamp.clone();
+        let mut suffix = 1;
+        let (path, created) = loop {
+            let path = self.directory.join(format!("{}.list", id));
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    // like those listed by `snapshots`, the creation time is the copy's modification time
+                    let created = file
+                        .write_all(contents.as_bytes())
+                        .and_then(|_| file.metadata())
+                        .and_then(|metadata| metadata.modified())
+                        .map_err(|e| Error::io(&path, e))?;
+                    break (path, created);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    id = format!("{}-{}", timestamp, suffix);
+                    suffix += 1;
+                }
+                Err(e) => return Err(Error::io(&path, e)),
+            }
+        };
+
+        let source_path = self.directory.join(format!("{}.source", id));
+        std::fs::write(&source_path, source.to_string_lossy().as_bytes())
+            .map_err(|e| Error::io(&source_path, e))?;
+
+        log::info!("BackupStore::snapshot - {:?} to {:?}", source, path);
+        Ok(Some(Snapshot {
+            id,
+            source: source.to_path_buf(),
+            path,
+            created,
+        }))
+    }
+
+    /// Returns every snapshot, newest first
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>, Error> {
+        let contents = match std::fs::read_dir(&self.directory) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(Error::io(&self.directory, e)),
+        };
+
+        let mut snapshots = vec![];
+        for path in contents.flatten().map(|entry| entry.path()) {
+            if !crate::has_extension(&path, "list") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let source_path = path.with_extension("source");
+            let Ok(source) = std::fs::read_to_string(&source_path) else {
+                log::warn!(
+                    "BackupStore::snapshots - skipping {:?}, which has no source",
+                    path
+                );
+                continue;
+            };
+            let created = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|e| Error::io(&path, e))?;
+
+            snapshots.push(Snapshot {
+                id: id.to_string(),
+                source: PathBuf::from(source.trim_end()),
+                path,
+                created,
+            });
+        }
+
+        // ids are timestamps, so they sort chronologically
+        snapshots.sort_by(|a, b| snapshot_order(&b.id).cmp(&snapshot_order(&a.id)));
+        Ok(snapshots)
+    }
+
+    /// Returns the snapshots of the file at `path`, newest first
+    pub fn snapshots_of<P>(&self, path: P) -> Result<Vec<Snapshot>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        Ok(self
+            .snapshots()?
+            .into_iter()
+            .filter(|snapshot| snapshot.source == path)
+            .collect())
+    }
+
+    pub fn find_snapshot(&self, id: &str) -> Result<Option<Snapshot>, Error> {
+        Ok(self
+            .snapshots()?
+            .into_iter()
+            .find(|snapshot| snapshot.id == id))
+    }
+
+    /// Replace the file `snapshot` was taken of with the snapshot's contents. The file is snapshotted
+    /// first, so the restore can itself be undone; that snapshot is returned, if one was taken.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<Option<Snapshot>, Error> {
+        let contents = snapshot.contents()?;
+        let previous = self.snapshot(&snapshot.source)?;
+        replace_file_contents(&snapshot.source, &contents)?;
+        self.prune()?;
+        Ok(previous)
+    }
+
+    /// Remove the snapshots the retention policy no longer keeps. Returns the removed snapshots.
+    pub fn prune(&self) -> Result<Vec<Snapshot>, Error> {
+        let now = SystemTime::now();
+        let mut kept_per_source: Vec<(PathBuf, usize)> = vec![];
+        let mut removed = vec![];
+
+        for snapshot in self.snapshots()? {
+            let kept = match kept_per_source
+                .iter_mut()
+                .find(|(source, _)| *source == snapshot.source)
+            {
+                Some((_, kept)) => kept,
+                None => {
+                    kept_per_source.push((snapshot.source.clone(), 0));
+                    &mut kept_per_source.last_mut().unwrap().1
+                }
+            };
+
+            let is_expired = self.retention.max_age.is_some_and(|max_age| {
+                now.duration_since(snapshot.created)
+                    .is_ok_and(|age| age > max_age)
+            });
+            let is_newest = *kept == 0;
+            if is_newest || (*kept < self.retention.max_snapshots && !is_expired) {
+                *kept += 1;
+                continue;
+            }
+
+            for path in [&snapshot.path, &snapshot.path.with_extension("source")] {
+                std::fs::remove_file(path).map_err(|e| Error::io(path, e))?;
+            }
+            removed.push(snapshot);
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Orders snapshot ids chronologically: by timestamp, then by the suffix of ids taken in the same millisecond
+fn snapshot_order(id: &str) -> (&str, usize) {
+    match id
+        .split_once('.')
+        .and_then(|(_, rest)| rest.split_once('-'))
+    {
+        Some((_, suffix)) => (
+            &id[..id.len() - suffix.len() - 1],
+            suffix.parse().unwrap_or(0),
+        ),
+        None => (id, 0),
+    }
+}
+
+/// Format `time` as UTC, e.g., "20261017-153045.123"
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}.{:03}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) date of the proleptic Gregorian calendar.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> anyhow::Result<PathBuf> {
+        let dir =
+            std::env::temp_dir().join(format!("mimeassoc-backup-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn formats_timestamps_as_utc() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "19700101-000000.000");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_millis(1_709_210_096_789)),
+            "20240229-123456.789"
+        );
+    }
+
+    #[test]
+    fn snapshots_and_restores_files() -> anyhow::Result<()> {
+        let dir = scratch_dir("restore")?;
+        let mimeapps_list_path = dir.join("mimeapps.list");
+        let backups = BackupStore::new(dir.join("backups"));
+
+        // nothing to snapshot yet
+        assert_eq!(backups.snapshot(&mimeapps_list_path)?, None);
+        assert!(backups.snapshots()?.is_empty());
+
+        let original = "[Default Applications]\ntext/html=firefox.desktop\n";
+        std::fs::write(&mimeapps_list_path, original)?;
+        let snapshot = backups.snapshot(&mimeapps_list_path)?.unwrap();
+        assert_eq!(snapshot.source(), mimeapps_list_path);
+        assert_eq!(snapshot.contents()?, original);
+
+        // unchanged files aren't snapshotted again
+        assert_eq!(backups.snapshot(&mimeapps_list_path)?, None);
+
+        let reset = "[Default Applications]\n";
+        std::fs::write(&mimeapps_list_path, reset)?;
+        let previous = backups.restore(&snapshot)?.unwrap();
+        assert_eq!(std::fs::read_to_string(&mimeapps_list_path)?, original);
+        assert_eq!(previous.contents()?, reset);
+
+        let snapshots = backups.snapshots()?;
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].id(), previous.id());
+        assert_eq!(backups.find_snapshot(snapshot.id())?, Some(snapshot));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn prunes_snapshots_per_retention_policy() -> anyhow::Result<()> {
+        let dir = scratch_dir("prune")?;
+        let mimeapps_list_path = dir.join("mimeapps.list");
+        let other_path = dir.join("gnome-mimeapps.list");
+        let backups = BackupStore::new(dir.join("backups")).with_retention(RetentionPolicy {
+            max_snapshots: 2,
+            max_age: None,
+        });
+
+        std::fs::write(&other_path, "[Default Applications]\n")?;
+        backups.snapshot(&other_path)?;
+        for i in 0..4 {
+            std::fs::write(
+                &mimeapps_list_path,
+                format!("[Default Applications]\ntext/plain=editor{}.desktop\n", i),
+            )?;
+            backups.snapshot(&mimeapps_list_path)?;
+        }
+
+        let removed = backups.prune()?;
+        assert_eq!(removed.len(), 2);
+        let kept = backups.snapshots_of(&mimeapps_list_path)?;
+        assert_eq!(kept.len(), 2);
+        assert!(kept[0].contents()?.contains("editor3"));
+        assert!(kept[1].contents()?.contains("editor2"));
+        assert_eq!(backups.snapshots_of(&other_path)?.len(), 1);
+
+        // the newest snapshot of a file is kept, however old
+        let backups = backups.with_retention(RetentionPolicy {
+            max_snapshots: 10,
+            max_age: Some(Duration::ZERO),
+        });
+        std::thread::sleep(Duration::from_millis(10));
+        backups.prune()?;
+        assert_eq!(backups.snapshots_of(&mimeapps_list_path)?, kept[..1]);
+        assert_eq!(backups.snapshots_of(&other_path)?.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::{fmt::Display, path::PathBuf};
+
+use mimeassoc::MimeType;
+
+/// The failures of a command. Library failures are wrapped, so that each can be given a message and exit code.
+#[derive(Debug)]
+pub enum CommandError {
+    Library(mimeassoc::Error),
+    MissingArgument(&'static str),
+    UnknownRole(String),
+    UnknownApplication(String),
+    UnknownSnapshot(String),
+    NoHandler {
+        target: String,
+        mime_type: MimeType,
+    },
+    Launch {
+        program: String,
+        source: std::io::Error,
+    },
+    /// The user's assignments don't match a desired state: `changes` are needed, and `unresolved` entries can't be satisfied
+    Drift {
+        changes: usize,
+        unresolved: usize,
+    },
+    /// `lint` found this many errors and warnings in the user's mimeapps.list files
+    LintProblems(usize),
+    /// `validate-desktop` found this many errors in the desktop entry at `path`
+    InvalidDesktopEntry {
+        path: PathBuf,
+        errors: usize,
+    },
+}
+
+impl CommandError {
+    /// The process exit code for this error:
+    /// - 1: reading or parsing configuration failed
+    /// - 2: an argument is invalid, e.g., a malformed mime type, or an unknown application, role or snapshot
+    /// - 3: the application doesn't support the mime type, role or URI
+    /// - 4: there is no writable mimeapps.list to save changes to
+    /// - 5: the application couldn't be launched
+    /// - 6: another program changed the mimeapps.list while we were saving, conflicting with our changes
+    /// - 7: the assignments don't match the desired state
+    /// - 8: the user's mimeapps.list files have problems
+    /// - 9: the desktop entry has errors
+    pub fn exit_code(&self) -> i32 {
+        use mimeassoc::Error;
+        match self {
+            Self::Library(e) => match e {
+                Error::InvalidMimeType(_)
+                | Error::InvalidScheme(_)
+                | Error::InvalidDesktopEntryId(_)
+                | Error::NoDesktopEntries(_) => 2,
+                Error::InvalidApplication(_)
+                | Error::UnsupportedMimeType { .. }
+                | Error::UnsupportedRole { .. }
+                | Error::UnsupportedUris { .. } => 3,
+                Error::NoWritableScope | Error::ReadOnlyScope(_) => 4,
+                Error::MissingExec(_)
+                | Error::InvalidExec { .. }
+                | Error::ExecutableNotFound(_)
+                | Error::NotExecutable(_) => 5,
+                Error::SaveConflict { .. } => 6,
+                _ => 1,
+            },
+            Self::MissingArgument(_)
+            | Self::UnknownRole(_)
+            | Self::UnknownApplication(_)
+            | Self::UnknownSnapshot(_) => 2,
+            Self::NoHandler { .. } => 3,
+            Self::Launch { .. } => 5,
+            Self::Drift { .. } => 7,
+            Self::LintProblems(_) => 8,
+            Self::InvalidDesktopEntry { .. } => 9,
+        }
+    }
+}
+
+impl From<mimeassoc::Error> for CommandError {
+    fn from(e: mimeassoc::Error) -> Self {
+        Self::Library(e)
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use mimeassoc::Error;
+        match self {
+            Self::Library(Error::InvalidMimeType(id)) => {
+                write!(f, "\"{}\" is not a valid mime type identifier", id)
+            }
+            Self::Library(Error::UnsupportedMimeType {
+                desktop_entry,
+                mime_type,
+            }) => write!(
+                f,
+                "\"{}\" is not able to open mime type \"{}\"",
+                desktop_entry, mime_type
+            ),
+            Self::Library(Error::NoWritableScope) => write!(
+                f,
+                "No writable mimeapps.list was found; unable to save changes"
+            ),
+            Self::Library(Error::SaveConflict { path, mime_types }) => write!(
+                f,
+                "{} was changed by another program while saving, and those changes conflict with ours for {}; nothing was saved",
+                path.display(),
+                mime_types
+                    .iter()
+                    .map(MimeType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Library(e) => write!(f, "{}", e),
+            Self::MissingArgument(argument) => write!(f, "No {} provided", argument),
+            Self::UnknownRole(role) => write!(f, "\"{}\" is not a known role", role),
+            Self::UnknownApplication(id) => write!(
+                f,
+                "\"{}\" does not appear to be an installed application",
+                id
+            ),
+            Self::UnknownSnapshot(id) => write!(
+                f,
+                "\"{}\" is not a known snapshot; see `backup list`",
+                id
+            ),
+            Self::NoHandler { target, mime_type } => write!(
+                f,
+                "No installed application is able to open \"{}\" ({})",
+                target, mime_type
+            ),
+            Self::Launch { program, source } => {
+                write!(f, "Unable to launch \"{}\": {}", program, source)
+            }
+            Self::Drift {
+                changes,
+                unresolved,
+            } => write!(
+                f,
+                "Assignments don't match the desired state: {} changes needed, {} entries can't be satisfied",
+                changes, unresolved
+            ),
+            Self::LintProblems(count) => write!(
+                f,
+                "{} problems were found in the user's mimeapps.list files",
+                count
+            ),
+            Self::InvalidDesktopEntry { path, errors } => {
+                write!(f, "{} errors were found in {}", errors, path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Library(e) => Some(e),
+            Self::Launch { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
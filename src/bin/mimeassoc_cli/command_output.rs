@@ -3,6 +3,8 @@ use std::path::PathBuf;
 
 use mimeassoc::*;
 
+use super::command_error::CommandError;
+
 /// Container for output generated by cli commands
 pub enum CommandOutput {
     MimeTypes(Vec<MimeTypesCommandOutput>),
@@ -12,6 +14,62 @@ pub enum CommandOutput {
     Set(SetDefaultHandlerCommandOutput),
     Reset(ResetDefaultHandlerCommandOutput),
     Configuration(ConfigurationCommandOutput),
+    Schemes(Vec<SchemeCommandOutput>),
+    Scheme(SchemeCommandOutput),
+    Roles(Vec<RoleCommandOutput>),
+    Role(RoleCommandOutput),
+    Open(OpenCommandOutput),
+    Explain(ExplainCommandOutput),
+    Export(ExportCommandOutput),
+    Import(ImportCommandOutput),
+    Plan(PlanCommandOutput),
+    Backups(Vec<BackupCommandOutput>),
+    BackupDiff(BackupDiffCommandOutput),
+    BackupRestore(BackupRestoreCommandOutput),
+    Lint(LintCommandOutput),
+    ValidateDesktop(DesktopEntryValidation),
+}
+
+impl CommandOutput {
+    /// The failure to report once the output is displayed, e.g., the drift found by `plan`, so scripts see both
+    pub fn failure(&self) -> Option<CommandError> {
+        match self {
+            Self::Plan(output) => {
+                let changes = if output.applied {
+                    0
+                } else {
+                    output.changes.len()
+                };
+                let unresolved = output.unresolved.len();
+                (changes > 0 || unresolved > 0).then_some(CommandError::Drift {
+                    changes,
+                    unresolved,
+                })
+            }
+            Self::Lint(output) => {
+                let problems = output
+                    .remaining
+                    .iter()
+                    .filter(|diagnostic| {
+                        diagnostic.is_fixable() && diagnostic.severity <= LintSeverity::Warning
+                    })
+                    .count();
+                (problems > 0).then_some(CommandError::LintProblems(problems))
+            }
+            Self::ValidateDesktop(validation) => {
+                let errors = validation
+                    .diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity == LintSeverity::Error)
+                    .count();
+                (errors > 0).then(|| CommandError::InvalidDesktopEntry {
+                    path: validation.path.clone(),
+                    errors,
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -23,6 +81,7 @@ pub struct MimeTypesCommandOutput {
 #[derive(Serialize)]
 pub struct MimeTypeCommandHandlerInfo {
     pub desktop_entry: DesktopEntryId,
+    pub name: Option<String>,
     pub is_default_handler: bool,
 }
 
@@ -42,6 +101,7 @@ pub struct MimeInfo {
 #[derive(Serialize)]
 pub struct ApplicationCommandOutput {
     pub desktop_entry: Option<DesktopEntryId>,
+    pub name: Option<String>,
     pub mime_info: Vec<MimeInfo>,
 }
 
@@ -62,6 +122,125 @@ pub struct ConfigurationCommandOutput {
     pub desktop_entry_scope_paths: Vec<PathBuf>,
 }
 
+#[derive(Serialize)]
+pub struct SchemeCommandOutput {
+    pub scheme: String,
+    pub mime_type: MimeType,
+    pub handler_info: Vec<MimeTypeCommandHandlerInfo>,
+}
+
+#[derive(Serialize)]
+pub struct RoleCommandHandlerInfo {
+    pub mime_type: MimeType,
+    pub desktop_entry: Option<DesktopEntryId>,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RoleCommandOutput {
+    pub role: String,
+    pub name: String,
+    pub owner: Option<DesktopEntryId>,
+    pub is_partially_owned: bool,
+    pub unowned_mime_types: Vec<MimeType>,
+    pub handler_info: Vec<RoleCommandHandlerInfo>,
+}
+
+#[derive(Serialize)]
+pub struct OpenCommandOutput {
+    pub target: String,
+    pub mime_type: MimeType,
+    pub desktop_entry: DesktopEntryId,
+    pub command_lines: Vec<ExecCommandLine>,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct ExplainCommandOutput {
+    pub target: String,
+    pub trace: ResolutionTrace,
+}
+
+#[derive(Serialize)]
+pub struct ExportCommandOutput {
+    pub path: Option<PathBuf>,
+    pub profile: Profile,
+    /// The profile as written, in the requested format
+    #[serde(skip)]
+    pub contents: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportCommandAssignment {
+    pub mime_type: MimeType,
+    pub desktop_entry: DesktopEntryId,
+}
+
+#[derive(Serialize)]
+pub struct ImportCommandOutput {
+    pub path: PathBuf,
+    pub default_applications: Vec<ImportCommandAssignment>,
+    pub added_associations: Vec<ImportCommandAssignment>,
+    pub skipped: Vec<SkippedProfileEntry>,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct PlanCommandOutput {
+    pub path: PathBuf,
+    pub changes: Vec<PlannedChange>,
+    pub unresolved: Vec<SkippedProfileEntry>,
+    /// True if the changes were made, e.g., by `apply`
+    pub applied: bool,
+}
+
+#[derive(Serialize)]
+pub struct BackupCommandOutput {
+    pub id: String,
+    pub source: PathBuf,
+    pub path: PathBuf,
+    /// Seconds since the unix epoch
+    pub created: u64,
+}
+
+impl From<&Snapshot> for BackupCommandOutput {
+    fn from(snapshot: &Snapshot) -> Self {
+        Self {
+            id: snapshot.id().to_string(),
+            source: snapshot.source().to_path_buf(),
+            path: snapshot.path().to_path_buf(),
+            created: snapshot
+                .created()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BackupDiffCommandOutput {
+    pub snapshot: BackupCommandOutput,
+    /// The changes restoring the snapshot would make to its source
+    pub changes: Vec<LineChange>,
+}
+
+#[derive(Serialize)]
+pub struct BackupRestoreCommandOutput {
+    pub restored: BackupCommandOutput,
+    /// The snapshot taken of the source before it was replaced, if it existed and wasn't already snapshotted
+    pub previous: Option<BackupCommandOutput>,
+    pub changes: Vec<LineChange>,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct LintCommandOutput {
+    /// The problems fixed by `lint --fix`
+    pub fixed: Vec<LintDiagnostic>,
+    pub remaining: Vec<LintDiagnostic>,
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
 /// Trait for handler types for processing CommandOutput
@@ -91,6 +270,24 @@ impl CommandOutputConsumer for DefaultCommandOutputConsumer {
             CommandOutput::Configuration(output) => {
                 Self::display_configuration_command_output(output)
             }
+            CommandOutput::Schemes(output) => Self::display_schemes_command_output(output),
+            CommandOutput::Scheme(output) => Self::display_scheme_command_output(output),
+            CommandOutput::Roles(output) => Self::display_roles_command_output(output),
+            CommandOutput::Role(output) => Self::display_role_command_output(output),
+            CommandOutput::Open(output) => Self::display_open_command_output(output),
+            CommandOutput::Explain(output) => Self::display_explain_command_output(output),
+            CommandOutput::Export(output) => Self::display_export_command_output(output),
+            CommandOutput::Import(output) => Self::display_import_command_output(output),
+            CommandOutput::Plan(output) => Self::display_plan_command_output(output),
+            CommandOutput::Backups(output) => Self::display_backups_command_output(output),
+            CommandOutput::BackupDiff(output) => Self::display_backup_diff_command_output(output),
+            CommandOutput::BackupRestore(output) => {
+                Self::display_backup_restore_command_output(output)
+            }
+            CommandOutput::Lint(output) => Self::display_lint_command_output(output),
+            CommandOutput::ValidateDesktop(output) => {
+                Self::display_validate_desktop_command_output(output)
+            }
         }
         Ok(())
     }
@@ -138,17 +335,77 @@ impl DefaultCommandOutputConsumer {
             }
             println!();
 
-            for handler in entry.handler_info.iter() {
-                if handler.is_default_handler {
-                    println!("\t*{}", handler.desktop_entry);
-                } else {
-                    println!("\t {}", handler.desktop_entry);
-                }
+            Self::display_handler_info(&entry.handler_info);
+            println!();
+        }
+    }
+
+    fn display_handler_info(handler_info: &[MimeTypeCommandHandlerInfo]) {
+        for handler in handler_info.iter() {
+            let marker = if handler.is_default_handler { '*' } else { ' ' };
+            match &handler.name {
+                Some(name) => println!("\t{}{} ({})", marker, handler.desktop_entry, name),
+                None => println!("\t{}{}", marker, handler.desktop_entry),
             }
+        }
+    }
+
+    fn display_schemes_command_output(output: &[SchemeCommandOutput]) {
+        for entry in output.iter() {
+            Self::display_scheme_command_output(entry);
+            println!();
+        }
+    }
+
+    fn display_scheme_command_output(output: &SchemeCommandOutput) {
+        println!("{}: [{}]", output.scheme, output.mime_type);
+        Self::display_handler_info(&output.handler_info);
+    }
+
+    fn display_roles_command_output(output: &[RoleCommandOutput]) {
+        for entry in output.iter() {
+            Self::display_role_command_output(entry);
             println!();
         }
     }
 
+    fn display_role_command_output(output: &RoleCommandOutput) {
+        print!("{} ({}): ", output.role, output.name);
+        match &output.owner {
+            Some(owner) if output.is_partially_owned => println!(
+                "partially owned by {}, missing {}",
+                owner,
+                output
+                    .unowned_mime_types
+                    .iter()
+                    .map(|m| m.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Some(owner) => println!("owned by {}", owner),
+            None => println!("unowned"),
+        }
+
+        for handler in output.handler_info.iter() {
+            let marker = if handler.desktop_entry.is_some() && handler.desktop_entry == output.owner
+            {
+                '*'
+            } else {
+                ' '
+            };
+            match (&handler.desktop_entry, &handler.name) {
+                (Some(desktop_entry), Some(name)) => println!(
+                    "\t{}{}: {} ({})",
+                    marker, handler.mime_type, desktop_entry, name
+                ),
+                (Some(desktop_entry), None) => {
+                    println!("\t{}{}: {}", marker, handler.mime_type, desktop_entry)
+                }
+                (None, _) => println!("\t{}{}: none", marker, handler.mime_type),
+            }
+        }
+    }
+
     fn display_applications_command_output(output: &[ApplicationCommandOutput]) {
         for info in output.iter() {
             Self::display_application_command_output(info);
@@ -158,7 +415,10 @@ impl DefaultCommandOutputConsumer {
 
     fn display_application_command_output(output: &ApplicationCommandOutput) {
         if let Some(desktop_entry) = &output.desktop_entry {
-            println!("{}", desktop_entry);
+            match &output.name {
+                Some(name) => println!("{} ({})", desktop_entry, name),
+                None => println!("{}", desktop_entry),
+            }
             for mime_info in output.mime_info.iter() {
                 if mime_info.is_default_handler {
                     println!("\t*{}", mime_info.mime_type);
@@ -212,6 +472,354 @@ impl DefaultCommandOutputConsumer {
             }
         }
     }
+
+    fn display_open_command_output(output: &OpenCommandOutput) {
+        if output.dry_run {
+            println!(
+                "Would open \"{}\" ({}) with {}:",
+                output.target, output.mime_type, output.desktop_entry
+            );
+        } else {
+            println!(
+                "Opened \"{}\" ({}) with {}:",
+                output.target, output.mime_type, output.desktop_entry
+            );
+        }
+
+        for command_line in output.command_lines.iter() {
+            let argv = command_line
+                .argv()
+                .iter()
+                .map(|arg| {
+                    if arg.is_empty() || arg.contains(char::is_whitespace) {
+                        format!("\"{}\"", arg)
+                    } else {
+                        arg.to_string()
+                    }
+                })
+                .collect::<Vec<_>>();
+            println!("\t{}", argv.join(" "));
+        }
+    }
+
+    fn display_explain_command_output(output: &ExplainCommandOutput) {
+        let trace = &output.trace;
+        if output.target == trace.mime_type.id() {
+            println!("{}:", trace.mime_type);
+        } else {
+            println!("\"{}\" ({}):", output.target, trace.mime_type);
+        }
+
+        let describe = |applications: &[TracedApplication]| {
+            applications
+                .iter()
+                .map(|application| {
+                    let outcome = match application.outcome {
+                        TracedOutcome::Chosen => "chosen",
+                        TracedOutcome::NotInstalled => "not installed",
+                        TracedOutcome::NotLaunchable => "not launchable",
+                        TracedOutcome::Removed => "removed association",
+                        TracedOutcome::Overridden => "overridden",
+                    };
+                    format!("{} ({})", application.desktop_entry, outcome)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let origin = |origin: CandidateOrigin| match origin {
+            CandidateOrigin::Requested => "",
+            CandidateOrigin::Alias => " (canonical type of alias)",
+            CandidateOrigin::Ancestor => " (ancestor)",
+            CandidateOrigin::Wildcard => " (wildcard)",
+        };
+
+        let mut current_mime_type = None;
+        for step in trace.steps.iter() {
+            match step {
+                TraceStep::Scope(step) => {
+                    // wildcard entries are listed under the mime type they were consulted for
+                    let is_wildcard = step.origin == CandidateOrigin::Wildcard;
+                    if !is_wildcard && current_mime_type != Some(&step.mime_type) {
+                        current_mime_type = Some(&step.mime_type);
+                        println!("  {}{}", step.mime_type, origin(step.origin));
+                    }
+                    let mut location = match step.line {
+                        Some(line) => format!("{}:{}", step.path.display(), line),
+                        None => step.path.display().to_string(),
+                    };
+                    if is_wildcard {
+                        location = format!("{} [{}]", location, step.mime_type);
+                    }
+                    let user = if step.is_user_customizable {
+                        " (user)"
+                    } else {
+                        ""
+                    };
+                    if step.applications.is_empty() {
+                        println!("\t{}{}: no entry", location, user);
+                    } else {
+                        println!("\t{}{}: {}", location, user, describe(&step.applications));
+                    }
+                }
+                TraceStep::Fallback(step) => {
+                    if step.applications.is_empty() {
+                        println!(
+                            "  No application supports {}{}",
+                            step.mime_type,
+                            origin(step.origin)
+                        );
+                    } else {
+                        println!(
+                            "  Applications supporting {}{}: {}",
+                            step.mime_type,
+                            origin(step.origin),
+                            describe(&step.applications)
+                        );
+                    }
+                }
+            }
+        }
+
+        match &trace.result {
+            Some(desktop_entry) => println!("Resolved to {}", desktop_entry),
+            None => println!("No application can open {}", trace.mime_type),
+        }
+    }
+
+    fn display_export_command_output(output: &ExportCommandOutput) {
+        match &output.path {
+            Some(path) => println!(
+                "Exported {} default applications and {} added associations to {}",
+                output.profile.default_applications.len(),
+                output.profile.added_associations.len(),
+                path.display()
+            ),
+            None => print!("{}", output.contents),
+        }
+    }
+
+    fn display_import_command_output(output: &ImportCommandOutput) {
+        let verb = if output.dry_run {
+            "Would assign"
+        } else {
+            "Assigned"
+        };
+        for assignment in output.default_applications.iter() {
+            println!(
+                "{} {} to {}",
+                verb, assignment.desktop_entry, assignment.mime_type
+            );
+        }
+
+        let verb = if output.dry_run {
+            "Would associate"
+        } else {
+            "Associated"
+        };
+        for assignment in output.added_associations.iter() {
+            println!(
+                "{} {} with {}",
+                verb, assignment.desktop_entry, assignment.mime_type
+            );
+        }
+
+        if output.default_applications.is_empty() && output.added_associations.is_empty() {
+            println!("Nothing was imported from {}", output.path.display());
+        }
+
+        if !output.skipped.is_empty() {
+            println!("\nSkipped:");
+            Self::display_skipped_profile_entries(&output.skipped);
+        }
+    }
+
+    fn display_skipped_profile_entries(entries: &[SkippedProfileEntry]) {
+        for entry in entries.iter() {
+            let reason = match entry.reason {
+                SkipReason::InvalidMimeType => "not a valid mime type",
+                SkipReason::NotInstalled => "not installed",
+                SkipReason::Unsupported => "doesn't support the mime type",
+                SkipReason::NotLaunchable => "doesn't appear to be launchable",
+            };
+            if entry.identifiers.is_empty() {
+                println!("\t{} ({})", entry.mime_type, reason);
+            } else {
+                println!(
+                    "\t{}: {} ({})",
+                    entry.mime_type,
+                    entry.identifiers.join(", "),
+                    reason
+                );
+            }
+        }
+    }
+
+    fn display_plan_command_output(output: &PlanCommandOutput) {
+        if output.changes.is_empty() && output.unresolved.is_empty() {
+            println!(
+                "Assignments match {}; nothing to change",
+                output.path.display()
+            );
+        } else if output.changes.is_empty() {
+            println!("No changes can be made to match {}", output.path.display());
+        } else if output.applied {
+            println!("Changed:");
+        } else {
+            println!("Changes needed to match {}:", output.path.display());
+        }
+
+        for change in output.changes.iter() {
+            match change {
+                PlannedChange::SetDefaultApplication {
+                    mime_type,
+                    from,
+                    to,
+                } => match from {
+                    Some(from) => println!("\tset {}: {} -> {}", mime_type, from, to),
+                    None => println!("\tset {}: {}", mime_type, to),
+                },
+                PlannedChange::ResetDefaultApplication { mime_type, from } => match from {
+                    Some(from) => println!("\treset {}: {}", mime_type, from),
+                    None => println!("\treset {}", mime_type),
+                },
+                PlannedChange::AddAssociation {
+                    mime_type,
+                    desktop_entry,
+                } => println!("\tassociate {}: {}", mime_type, desktop_entry),
+            }
+        }
+
+        if !output.unresolved.is_empty() {
+            println!("\nUnable to satisfy:");
+            Self::display_skipped_profile_entries(&output.unresolved);
+        }
+    }
+
+    fn display_lint_diagnostics(diagnostics: &[LintDiagnostic]) {
+        let mut current_path = None;
+        for diagnostic in diagnostics.iter() {
+            if current_path != Some(&diagnostic.path) {
+                current_path = Some(&diagnostic.path);
+                let user = if diagnostic.is_user_customizable {
+                    " (user)"
+                } else {
+                    ""
+                };
+                println!("{}{}:", diagnostic.path.display(), user);
+            }
+            let severity = match diagnostic.severity {
+                LintSeverity::Error => "error",
+                LintSeverity::Warning => "warning",
+                LintSeverity::Info => "info",
+            };
+            match &diagnostic.fix {
+                Some(fix) => println!(
+                    "\t{}: {}: {} (fix: {})",
+                    diagnostic.line, severity, diagnostic.problem, fix
+                ),
+                None => println!(
+                    "\t{}: {}: {}",
+                    diagnostic.line, severity, diagnostic.problem
+                ),
+            }
+        }
+    }
+
+    fn display_lint_command_output(output: &LintCommandOutput) {
+        if output.fixed.is_empty() && output.remaining.is_empty() {
+            println!("No problems found");
+            return;
+        }
+
+        if !output.fixed.is_empty() {
+            println!("Fixed:");
+            Self::display_lint_diagnostics(&output.fixed);
+            if !output.remaining.is_empty() {
+                println!("\nNot fixed:");
+            }
+        }
+        Self::display_lint_diagnostics(&output.remaining);
+
+        let fixable = output
+            .remaining
+            .iter()
+            .filter(|diagnostic| diagnostic.is_fixable())
+            .count();
+        if fixable > 0 {
+            println!("\n{} problems can be fixed with `lint --fix`", fixable);
+        }
+    }
+
+    fn display_validate_desktop_command_output(output: &DesktopEntryValidation) {
+        if output.diagnostics.is_empty() {
+            println!("{}: no problems found", output.path.display());
+            return;
+        }
+
+        println!("{}:", output.path.display());
+        for diagnostic in output.diagnostics.iter() {
+            let severity = match diagnostic.severity {
+                LintSeverity::Error => "error",
+                LintSeverity::Warning => "warning",
+                LintSeverity::Info => "info",
+            };
+            match diagnostic.line {
+                Some(line) => println!("\t{}: {}: {}", line, severity, diagnostic.problem),
+                None => println!("\t{}: {}", severity, diagnostic.problem),
+            }
+        }
+    }
+
+    fn display_backups_command_output(output: &[BackupCommandOutput]) {
+        if output.is_empty() {
+            println!("No snapshots have been taken.");
+        }
+        for snapshot in output.iter() {
+            println!("{}\t{}", snapshot.id, snapshot.source.display());
+        }
+    }
+
+    fn display_line_changes(changes: &[LineChange]) {
+        if !changes.iter().any(LineChange::is_change) {
+            println!("\t(no changes)");
+        }
+        for change in changes.iter().filter(|change| change.is_change()) {
+            println!("\t{}", change);
+        }
+    }
+
+    fn display_backup_diff_command_output(output: &BackupDiffCommandOutput) {
+        println!(
+            "Restoring {} would change {}:",
+            output.snapshot.id,
+            output.snapshot.source.display()
+        );
+        Self::display_line_changes(&output.changes);
+    }
+
+    fn display_backup_restore_command_output(output: &BackupRestoreCommandOutput) {
+        if output.dry_run {
+            println!(
+                "Would restore {} to {}:",
+                output.restored.id,
+                output.restored.source.display()
+            );
+        } else {
+            println!(
+                "Restored {} to {}:",
+                output.restored.id,
+                output.restored.source.display()
+            );
+        }
+        Self::display_line_changes(&output.changes);
+
+        if let Some(previous) = &output.previous {
+            println!(
+                "The previous contents were saved as {}; restore it to undo",
+                previous.id
+            );
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -229,6 +837,20 @@ impl CommandOutputConsumer for JsonCommandOutputConsumer {
             CommandOutput::Set(output) => serde_json::to_string_pretty(output),
             CommandOutput::Reset(output) => serde_json::to_string_pretty(output),
             CommandOutput::Configuration(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Schemes(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Scheme(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Roles(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Role(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Open(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Explain(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Export(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Import(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Plan(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Backups(output) => serde_json::to_string_pretty(output),
+            CommandOutput::BackupDiff(output) => serde_json::to_string_pretty(output),
+            CommandOutput::BackupRestore(output) => serde_json::to_string_pretty(output),
+            CommandOutput::Lint(output) => serde_json::to_string_pretty(output),
+            CommandOutput::ValidateDesktop(output) => serde_json::to_string_pretty(output),
         }?;
 
         println!("{}", json_string);
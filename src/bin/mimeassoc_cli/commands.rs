@@ -1,8 +1,9 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{Args, Subcommand};
 use mimeassoc::*;
 
+use super::command_error::*;
 use super::command_output::*;
 
 #[derive(Args)]
@@ -32,6 +33,118 @@ pub struct ResetCommandArgs {
     mime_types: Vec<String>,
 }
 
+#[derive(Args)]
+pub struct SchemeCommandArgs {
+    /// If set, make no changes, just display what would be assigned
+    #[arg(short, long)]
+    dry_run: bool,
+    /// The URL scheme, e.g., "mailto" or "https"
+    scheme: String,
+    /// If specified, assign this application as the handler for the scheme
+    desktop_entry: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RolesCommandArgs {
+    /// If set, only display roles whose owner handles just some of the role's mime types it supports
+    #[arg(short, long)]
+    partial: bool,
+}
+
+#[derive(Args)]
+pub struct RoleCommandArgs {
+    /// If set, make no changes, just display what would be assigned
+    #[arg(short, long)]
+    dry_run: bool,
+    /// The role, e.g., "browser", "mail" or "photos"
+    role: String,
+    /// If specified, assign this application as the handler for every mime type of the role it supports
+    desktop_entry: Option<String>,
+}
+
+#[derive(Args)]
+pub struct OpenCommandArgs {
+    /// If set, launch nothing, just display the command line which would be run
+    #[arg(short, long)]
+    dry_run: bool,
+    /// Open with this application instead of the default handler
+    #[arg(short, long)]
+    with: Option<String>,
+    /// The file path or URI to open
+    target: String,
+}
+
+#[derive(Args)]
+pub struct ExplainCommandArgs {
+    /// A mime type, e.g., "image/png", or a file path or URI whose mime type is detected
+    target: String,
+}
+
+#[derive(Args)]
+pub struct ExportCommandArgs {
+    /// If set, identify applications by desktop entry id, falling back to their name, rather than by id alone
+    #[arg(short, long)]
+    names: bool,
+    /// If set, write TOML rather than JSON. Implied when the path ends in `.toml`
+    #[arg(short, long)]
+    toml: bool,
+    /// The file to write the profile to; if not specified, it's displayed
+    path: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ImportCommandArgs {
+    /// If set, make no changes, just display what would be assigned and skipped
+    #[arg(short, long)]
+    dry_run: bool,
+    /// The profile to import, in JSON, or TOML if the path ends in `.toml`
+    path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct DesiredStateCommandArgs {
+    /// The desired state, in JSON, or TOML if the path ends in `.toml`
+    path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct LintCommandArgs {
+    /// If set, apply the suggested fixes to the user's mimeapps.list files
+    #[arg(short, long)]
+    fix: bool,
+}
+
+#[derive(Args)]
+pub struct ValidateDesktopCommandArgs {
+    /// The path of a `.desktop` file, or an application's id or name, e.g., "org.gnome.Evince.desktop" or "evince"
+    target: String,
+}
+
+#[derive(Args)]
+pub struct BackupCommandArgs {
+    #[command(subcommand)]
+    command: BackupCommands,
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommands {
+    /// Display the snapshots taken of the user's mimeapps.list files, newest first
+    List,
+    /// Display the changes restoring a snapshot would make to the file it was taken of
+    Show {
+        /// The snapshot's id, as displayed by `backup list`
+        id: String,
+    },
+    /// Replace a mimeapps.list with a snapshot of it. The current file is snapshotted first, so the restore can be undone
+    Restore {
+        /// If set, make no changes, just display what would be restored
+        #[arg(short, long)]
+        dry_run: bool,
+        /// The snapshot's id, as displayed by `backup list`
+        id: String,
+    },
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Subcommand)]
@@ -51,6 +164,32 @@ pub enum Commands {
     Reset(ResetCommandArgs),
     /// Display the configuration state of `mimeassoc`. Shows the mime association sources, and where desktop entry files were loaded from, in chain order.
     Configuration,
+    /// Display all URL schemes, e.g., "mailto" or "https", which installed applications can handle, and the assigned handler of each
+    Schemes,
+    /// Display the applications which can handle a URL scheme, e.g., "mailto", with an asterisk indicating the assigned handler. If an application is specified, assigns it as the handler for the scheme
+    Scheme(SchemeCommandArgs),
+    /// Display all roles, e.g., "browser" or "mail", and the application owning each. Roles are groups of related mime types, such as `x-scheme-handler/https` and `text/html`, which are usually handled by one application
+    Roles(RolesCommandArgs),
+    /// Display the application handling each mime type of a role, e.g., "browser". If an application is specified, assigns it as the handler for every mime type of the role it supports
+    Role(RoleCommandArgs),
+    /// Open a file or URI with the default handler for its mime type or URI scheme, or with the application specified with `--with`
+    Open(OpenCommandArgs),
+    /// Trace how the application which opens a mime type, file or URI is chosen: each mimeapps.list consulted, the line deciding, and why other applications lost
+    Explain(ExplainCommandArgs),
+    /// Export the user's default applications and added associations as a portable JSON or TOML profile
+    Export(ExportCommandArgs),
+    /// Import a profile written by `export`, skipping and reporting entries whose applications aren't installed
+    Import(ImportCommandArgs),
+    /// Display the changes needed to make the user's assignments match a desired state file. Exits with status 7 if they don't match
+    Plan(DesiredStateCommandArgs),
+    /// Make the user's assignments match a desired state file. Exits with status 7 if some of it can't be satisfied, e.g., an application isn't installed
+    Apply(DesiredStateCommandArgs),
+    /// List, inspect and restore the snapshots taken of the user's mimeapps.list files before each save
+    Backup(BackupCommandArgs),
+    /// Check the mimeapps.list files for problems, e.g., lines which can't be parsed, or applications which aren't installed, and suggest fixes. Exits with status 8 if the user's files have errors or warnings
+    Lint(LintCommandArgs),
+    /// Check a desktop entry against the Desktop Entry spec, like `desktop-file-validate`, e.g., to find why an application isn't offered for its mime types. Exits with status 9 if it has errors
+    ValidateDesktop(ValidateDesktopCommandArgs),
 }
 
 impl Commands {
@@ -59,18 +198,23 @@ impl Commands {
         mime_associations_store: &mut MimeTypeAssociationStore,
         desktop_entry_store: &DesktopEntryStore,
         mime_info_store: &MimeTypeInfoStore,
-    ) -> CommandOutput {
+        role_store: &RoleStore,
+    ) -> Result<CommandOutput, CommandError> {
         match self {
-            Commands::MimeTypes => Self::get_mime_types(mime_associations_store, mime_info_store),
+            Commands::MimeTypes => Ok(Self::get_mime_types(
+                mime_associations_store,
+                mime_info_store,
+            )),
             Commands::MimeType(args) => Self::get_mime_type(
                 mime_associations_store,
                 desktop_entry_store,
                 mime_info_store,
                 args.id.as_deref(),
             ),
-            Commands::Applications => {
-                Self::get_applications(mime_associations_store, desktop_entry_store)
-            }
+            Commands::Applications => Ok(Self::get_applications(
+                mime_associations_store,
+                desktop_entry_store,
+            )),
             Commands::Application(args) => Self::get_application(
                 mime_associations_store,
                 desktop_entry_store,
@@ -96,9 +240,97 @@ impl Commands {
                     .collect::<Vec<_>>(),
                 args.dry_run,
             ),
-            Commands::Configuration => {
-                Self::get_configuration(mime_associations_store, desktop_entry_store)
+            Commands::Configuration => Ok(Self::get_configuration(
+                mime_associations_store,
+                desktop_entry_store,
+            )),
+            Commands::Schemes => Ok(Self::get_schemes(
+                mime_associations_store,
+                desktop_entry_store,
+                mime_info_store,
+            )),
+            Commands::Scheme(args) => Self::scheme(
+                mime_associations_store,
+                desktop_entry_store,
+                mime_info_store,
+                args.scheme.as_str(),
+                args.desktop_entry.as_deref(),
+                args.dry_run,
+            ),
+            Commands::Roles(args) => Ok(Self::get_roles(
+                mime_associations_store,
+                desktop_entry_store,
+                role_store,
+                args.partial,
+            )),
+            Commands::Role(args) => Self::role(
+                mime_associations_store,
+                desktop_entry_store,
+                role_store,
+                args.role.as_str(),
+                args.desktop_entry.as_deref(),
+                args.dry_run,
+            ),
+            Commands::Open(args) => Self::open(
+                mime_associations_store,
+                desktop_entry_store,
+                mime_info_store,
+                args.target.as_str(),
+                args.with.as_deref(),
+                args.dry_run,
+            ),
+            Commands::Explain(args) => Ok(Self::explain(
+                mime_associations_store,
+                desktop_entry_store,
+                mime_info_store,
+                args.target.as_str(),
+            )),
+            Commands::Export(args) => Self::export_profile(
+                mime_associations_store,
+                desktop_entry_store,
+                args.path.as_deref(),
+                args.names,
+                args.toml,
+            ),
+            Commands::Import(args) => Self::import_profile(
+                mime_associations_store,
+                desktop_entry_store,
+                &args.path,
+                args.dry_run,
+            ),
+            Commands::Plan(args) => Self::plan(
+                mime_associations_store,
+                desktop_entry_store,
+                &args.path,
+                false,
+            ),
+            Commands::Apply(args) => Self::plan(
+                mime_associations_store,
+                desktop_entry_store,
+                &args.path,
+                true,
+            ),
+            Commands::Backup(args) => {
+                let backups = mime_associations_store.backups();
+                match &args.command {
+                    BackupCommands::List => Self::get_backups(&backups),
+                    BackupCommands::Show { id } => Self::show_backup(&backups, id),
+                    BackupCommands::Restore { id, dry_run } => {
+                        Self::restore_backup(&backups, id, *dry_run)
+                    }
+                }
             }
+            Commands::Lint(args) => Self::lint(
+                mime_associations_store,
+                desktop_entry_store,
+                mime_info_store,
+                args.fix,
+            ),
+            Commands::ValidateDesktop(args) => Self::validate_desktop_entry(
+                mime_associations_store.environment(),
+                desktop_entry_store,
+                &args.target,
+            ),
         }
     }
 
@@ -125,14 +357,12 @@ impl Commands {
         desktop_entry_store: &DesktopEntryStore,
         mime_info_store: &MimeTypeInfoStore,
         id: Option<&str>,
-    ) -> CommandOutput {
+    ) -> Result<CommandOutput, CommandError> {
         let Some(id) = id else {
-            panic!("No mime type provded.");
+            return Err(CommandError::MissingArgument("mime type"));
         };
 
-        let Ok(mime_type) = MimeType::parse(id) else {
-            panic!("\"{}\" is not a valid mime type identifier", id);
-        };
+        let mime_type = MimeType::parse(id)?;
 
         let mut output = vec![];
         for mt_match in mime_associations_store.find_matching_mimetypes(&mime_type) {
@@ -146,7 +376,7 @@ impl Commands {
 
         output.sort_by(|a, b| a.mime_type.cmp(&b.mime_type));
 
-        CommandOutput::MimeType(output)
+        Ok(CommandOutput::MimeType(output))
     }
 
     fn get_single_mime_type(
@@ -155,8 +385,17 @@ impl Commands {
         mime_info_store: &MimeTypeInfoStore,
         mime_type: &MimeType,
     ) -> MimeTypeCommandOutput {
-        let desktop_entries = desktop_entry_store.find_desktop_entries_for_mimetype(mime_type);
-        let default_handler = mime_associations_store.default_application_for(mime_type);
+        let desktop_entries = mime_associations_store
+            .desktop_entries_for(mime_type, desktop_entry_store)
+            .into_iter()
+            .filter(|desktop_entry| desktop_entry_store.is_launchable(desktop_entry));
+        let default_handler = mime_associations_store
+            .installed_default_application_for(mime_type, desktop_entry_store)
+            .map(|desktop_entry| desktop_entry.id())
+            .or_else(|| {
+                mime_associations_store
+                    .inherited_default_application_for(mime_type, mime_info_store)
+            });
 
         let mut output = MimeTypeCommandOutput {
             mime_type: mime_type.clone(),
@@ -167,20 +406,200 @@ impl Commands {
         for entry in desktop_entries {
             output.handler_info.push(MimeTypeCommandHandlerInfo {
                 desktop_entry: entry.id().clone(),
+                name: entry.name().map(str::to_string),
                 is_default_handler: Some(entry.id()) == default_handler,
             });
         }
 
+        // a handler inherited from a parent type may not list this mime type itself
+        if let Some(default_handler) = default_handler {
+            if !output
+                .handler_info
+                .iter()
+                .any(|h| &h.desktop_entry == default_handler)
+            {
+                output.handler_info.push(MimeTypeCommandHandlerInfo {
+                    desktop_entry: default_handler.clone(),
+                    name: desktop_entry_store
+                        .find_desktop_entry_with_id(default_handler)
+                        .and_then(|desktop_entry| desktop_entry.name())
+                        .map(str::to_string),
+                    is_default_handler: true,
+                });
+            }
+        }
+
         output
     }
 
+    fn get_schemes(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> CommandOutput {
+        let schemes = mime_associations_store.schemes(desktop_entry_store);
+
+        CommandOutput::Schemes(
+            schemes
+                .iter()
+                .filter_map(|scheme| MimeType::for_scheme(scheme).ok())
+                .map(|mime_type| {
+                    Self::get_single_scheme(
+                        mime_associations_store,
+                        desktop_entry_store,
+                        mime_info_store,
+                        &mime_type,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn scheme(
+        mime_associations_store: &mut MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+        scheme: &str,
+        desktop_entry: Option<&str>,
+        dry_run: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let mime_type = MimeType::for_scheme(scheme)?;
+
+        if let Some(desktop_entry) = desktop_entry {
+            return Self::set_default_handler(
+                mime_associations_store,
+                desktop_entry_store,
+                desktop_entry,
+                &[mime_type.id()],
+                dry_run,
+            );
+        }
+
+        Ok(CommandOutput::Scheme(Self::get_single_scheme(
+            mime_associations_store,
+            desktop_entry_store,
+            mime_info_store,
+            &mime_type,
+        )))
+    }
+
+    fn get_single_scheme(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+        mime_type: &MimeType,
+    ) -> SchemeCommandOutput {
+        let output = Self::get_single_mime_type(
+            mime_associations_store,
+            desktop_entry_store,
+            mime_info_store,
+            mime_type,
+        );
+
+        SchemeCommandOutput {
+            scheme: mime_type.scheme().unwrap_or_default().to_string(),
+            mime_type: output.mime_type,
+            handler_info: output.handler_info,
+        }
+    }
+
+    fn get_roles(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        role_store: &RoleStore,
+        partial: bool,
+    ) -> CommandOutput {
+        CommandOutput::Roles(
+            role_store
+                .roles()
+                .iter()
+                .map(|role| {
+                    Self::get_single_role(mime_associations_store, desktop_entry_store, role)
+                })
+                .filter(|output| !partial || output.is_partially_owned)
+                .collect(),
+        )
+    }
+
+    fn role(
+        mime_associations_store: &mut MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        role_store: &RoleStore,
+        role: &str,
+        desktop_entry: Option<&str>,
+        dry_run: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let Some(role) = role_store.find_role(role) else {
+            return Err(CommandError::UnknownRole(role.to_string()));
+        };
+
+        let Some(desktop_entry_id) = desktop_entry else {
+            return Ok(CommandOutput::Role(Self::get_single_role(
+                mime_associations_store,
+                desktop_entry_store,
+                role,
+            )));
+        };
+
+        let Some(desktop_entry) = lookup_desktop_entry(desktop_entry_store, desktop_entry_id)
+        else {
+            return Err(CommandError::UnknownApplication(
+                desktop_entry_id.to_string(),
+            ));
+        };
+
+        let mime_types =
+            mime_associations_store.set_default_handler_for_role(role, desktop_entry)?;
+
+        // persist the changes...
+        if !dry_run {
+            mime_associations_store.save()?;
+        }
+
+        Ok(CommandOutput::Set(SetDefaultHandlerCommandOutput {
+            desktop_entry: desktop_entry.id().clone(),
+            mime_types,
+        }))
+    }
+
+    fn get_single_role(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        role: &Role,
+    ) -> RoleCommandOutput {
+        let handlers = mime_associations_store.role_handlers(role, desktop_entry_store);
+        let name_of = |id: &DesktopEntryId| {
+            desktop_entry_store
+                .find_desktop_entry_with_id(id)
+                .and_then(|desktop_entry| desktop_entry.name())
+                .map(str::to_string)
+        };
+
+        RoleCommandOutput {
+            role: role.id().to_string(),
+            name: role.name().to_string(),
+            owner: handlers.owner().cloned(),
+            is_partially_owned: handlers.is_partially_owned(),
+            unowned_mime_types: handlers.unowned_mime_types().to_vec(),
+            handler_info: handlers
+                .handlers()
+                .iter()
+                .map(|(mime_type, desktop_entry)| RoleCommandHandlerInfo {
+                    mime_type: mime_type.clone(),
+                    desktop_entry: desktop_entry.clone(),
+                    name: desktop_entry.as_ref().and_then(name_of),
+                })
+                .collect(),
+        }
+    }
+
     fn get_applications(
         mime_associations_store: &MimeTypeAssociationStore,
         desktop_entry_store: &DesktopEntryStore,
     ) -> CommandOutput {
         CommandOutput::Applications(
             desktop_entry_store
-                .desktop_entries()
+                .visible_desktop_entries()
                 .iter()
                 .map(|desktop_entry| {
                     let mut mime_types = desktop_entry.mime_types().clone();
@@ -190,7 +609,8 @@ impl Commands {
                         .iter()
                         .map(|mime_type| {
                             let is_handler = mime_associations_store
-                                .default_application_for(mime_type)
+                                .installed_default_application_for(mime_type, desktop_entry_store)
+                                .map(DesktopEntry::id)
                                 == Some(desktop_entry.id());
                             MimeInfo {
                                 mime_type: mime_type.clone(),
@@ -201,6 +621,7 @@ impl Commands {
 
                     ApplicationCommandOutput {
                         desktop_entry: Some(desktop_entry.id().clone()),
+                        name: desktop_entry.name().map(str::to_string),
                         mime_info,
                     }
                 })
@@ -212,16 +633,17 @@ impl Commands {
         mime_associations_store: &MimeTypeAssociationStore,
         desktop_entry_store: &DesktopEntryStore,
         id: Option<&str>,
-    ) -> CommandOutput {
+    ) -> Result<CommandOutput, CommandError> {
         let Some(id) = id else {
-            panic!("No desktop entry id provded.");
+            return Err(CommandError::MissingArgument("desktop entry id"));
         };
 
         let Some(desktop_entry) = lookup_desktop_entry(desktop_entry_store, id) else {
-            return CommandOutput::Application(ApplicationCommandOutput {
+            return Ok(CommandOutput::Application(ApplicationCommandOutput {
                 desktop_entry: None,
+                name: None,
                 mime_info: vec![],
-            });
+            }));
         };
 
         let mut mime_types = desktop_entry.mime_types().clone();
@@ -230,7 +652,9 @@ impl Commands {
         let mime_info = mime_types
             .iter()
             .map(|mime_type| {
-                let is_handler = mime_associations_store.default_application_for(mime_type)
+                let is_handler = mime_associations_store
+                    .installed_default_application_for(mime_type, desktop_entry_store)
+                    .map(DesktopEntry::id)
                     == Some(desktop_entry.id());
                 MimeInfo {
                     mime_type: mime_type.clone(),
@@ -239,10 +663,11 @@ impl Commands {
             })
             .collect::<Vec<_>>();
 
-        CommandOutput::Application(ApplicationCommandOutput {
+        Ok(CommandOutput::Application(ApplicationCommandOutput {
             desktop_entry: Some(desktop_entry.id().clone()),
+            name: desktop_entry.name().map(str::to_string),
             mime_info,
-        })
+        }))
     }
 
     fn set_default_handler(
@@ -251,24 +676,19 @@ impl Commands {
         desktop_entry_id: &str,
         mime_types: &[&str],
         dry_run: bool,
-    ) -> CommandOutput {
+    ) -> Result<CommandOutput, CommandError> {
         // map input mime type strings to mime types
         let mut resolved_mime_types: Vec<MimeType> = vec![];
         for mime_type in mime_types {
-            if let Ok(mime_type) = MimeType::parse(mime_type) {
-                resolved_mime_types.push(mime_type);
-            } else {
-                panic!("\"{}\" is not a valid mime type identifier", mime_type);
-            }
+            resolved_mime_types.push(MimeType::parse(mime_type)?);
         }
 
         // find the desktop entry
         let Some(desktop_entry) = lookup_desktop_entry(desktop_entry_store, desktop_entry_id)
         else {
-            panic!(
-                "\"{}\" does not appear to be an installed application",
-                desktop_entry_id
-            );
+            return Err(CommandError::UnknownApplication(
+                desktop_entry_id.to_string(),
+            ));
         };
 
         // if no mime types are supported, we will use all the mime types this app claims to handle
@@ -278,12 +698,12 @@ impl Commands {
 
         // verify the desktop entry can handle the provided mime types
         for mime_type in resolved_mime_types.iter() {
-            if !desktop_entry.can_open_mime_type(mime_type) {
-                panic!(
-                    "\"{}\" is not able to open mime type \"{}\"",
-                    desktop_entry.id(),
-                    mime_type
-                );
+            if !desktop_entry.can_open_mime_type_matching(mime_type) {
+                return Err(Error::UnsupportedMimeType {
+                    desktop_entry: desktop_entry.id().clone(),
+                    mime_type: mime_type.clone(),
+                }
+                .into());
             }
         }
 
@@ -293,42 +713,27 @@ impl Commands {
         };
 
         for mime_type in resolved_mime_types.iter() {
-            match mime_associations_store
-                .set_default_handler_for_mime_type(mime_type, desktop_entry)
-            {
-                Ok(_) => output.mime_types.push(mime_type.clone()),
-                Err(e) => panic!(
-                    "Failed to assign {} to {}, error: {:?}",
-                    mime_type,
-                    desktop_entry.id(),
-                    e
-                ),
-            }
+            mime_associations_store.set_default_handler_for_mime_type(mime_type, desktop_entry)?;
+            output.mime_types.push(mime_type.clone());
         }
 
         // persist the changes...
         if !dry_run {
-            if let Err(e) = mime_associations_store.save() {
-                panic!("Unable to save changes: {:?}", e);
-            }
+            mime_associations_store.save()?;
         }
 
-        CommandOutput::Set(output)
+        Ok(CommandOutput::Set(output))
     }
 
     fn reset_mime_types(
         mime_associations_store: &mut MimeTypeAssociationStore,
         mime_types: &[&str],
         dry_run: bool,
-    ) -> CommandOutput {
+    ) -> Result<CommandOutput, CommandError> {
         // map input mime type strings to mime types
         let mut resolved_mime_types: Vec<MimeType> = vec![];
         for mime_type in mime_types {
-            if let Ok(mime_type) = MimeType::parse(mime_type) {
-                resolved_mime_types.push(mime_type);
-            } else {
-                panic!("\"{}\" is not a valid mime type identifier", mime_type);
-            }
+            resolved_mime_types.push(MimeType::parse(mime_type)?);
         }
 
         let mut output = ResetDefaultHandlerCommandOutput {
@@ -343,12 +748,10 @@ impl Commands {
 
         // persist the changes...
         if !dry_run {
-            if let Err(e) = mime_associations_store.save() {
-                panic!("Unable to save changes: {:?}", e);
-            }
+            mime_associations_store.save()?;
         }
 
-        CommandOutput::Reset(output)
+        Ok(CommandOutput::Reset(output))
     }
 
     fn get_configuration(
@@ -371,4 +774,293 @@ impl Commands {
             desktop_entry_scope_paths,
         })
     }
+
+    fn open(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+        target: &str,
+        with: Option<&str>,
+        dry_run: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let mime_type = mime_info_store.mime_type_for_path_or_uri(target);
+
+        let desktop_entry = if let Some(with) = with {
+            let Some(desktop_entry) = lookup_desktop_entry(desktop_entry_store, with) else {
+                return Err(CommandError::UnknownApplication(with.to_string()));
+            };
+            desktop_entry
+        } else {
+            let Some(desktop_entry) = mime_associations_store.resolve_application_for(
+                &mime_type,
+                desktop_entry_store,
+                mime_info_store,
+            ) else {
+                return Err(CommandError::NoHandler {
+                    target: target.to_string(),
+                    mime_type,
+                });
+            };
+            desktop_entry
+        };
+
+        // the handler may not share our working directory, so pass existing local paths absolutely
+        let target = std::fs::canonicalize(target)
+            .ok()
+            .and_then(|path| path.to_str().map(str::to_string))
+            .unwrap_or_else(|| target.to_string());
+
+        let command_lines = desktop_entry.command_line(&[&target])?;
+
+        if !dry_run {
+            for command_line in command_lines.iter() {
+                command_line
+                    .command()
+                    .spawn()
+                    .map_err(|source| CommandError::Launch {
+                        program: command_line.program().to_string(),
+                        source,
+                    })?;
+            }
+        }
+
+        Ok(CommandOutput::Open(OpenCommandOutput {
+            target,
+            mime_type,
+            desktop_entry: desktop_entry.id().clone(),
+            command_lines,
+            dry_run,
+        }))
+    }
+
+    fn get_backups(backups: &BackupStore) -> Result<CommandOutput, CommandError> {
+        Ok(CommandOutput::Backups(
+            backups
+                .snapshots()?
+                .iter()
+                .map(BackupCommandOutput::from)
+                .collect(),
+        ))
+    }
+
+    fn find_backup(backups: &BackupStore, id: &str) -> Result<Snapshot, CommandError> {
+        backups
+            .find_snapshot(id)?
+            .ok_or_else(|| CommandError::UnknownSnapshot(id.to_string()))
+    }
+
+    /// Diff the file a snapshot was taken of against the snapshot, i.e., the changes restoring it would make
+    fn diff_backup(snapshot: &Snapshot) -> Result<Vec<LineChange>, CommandError> {
+        let current = match std::fs::read_to_string(snapshot.source()) {
+            Ok(current) => current,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(source) => {
+                return Err(mimeassoc::Error::Io {
+                    path: snapshot.source().to_path_buf(),
+                    source,
+                }
+                .into())
+            }
+        };
+        Ok(line_diff(&current, &snapshot.contents()?))
+    }
+
+    fn show_backup(backups: &BackupStore, id: &str) -> Result<CommandOutput, CommandError> {
+        let snapshot = Self::find_backup(backups, id)?;
+        Ok(CommandOutput::BackupDiff(BackupDiffCommandOutput {
+            changes: Self::diff_backup(&snapshot)?,
+            snapshot: BackupCommandOutput::from(&snapshot),
+        }))
+    }
+
+    fn restore_backup(
+        backups: &BackupStore,
+        id: &str,
+        dry_run: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let snapshot = Self::find_backup(backups, id)?;
+        let changes = Self::diff_backup(&snapshot)?;
+
+        let previous = if dry_run {
+            None
+        } else {
+            backups.restore(&snapshot)?
+        };
+
+        Ok(CommandOutput::BackupRestore(BackupRestoreCommandOutput {
+            restored: BackupCommandOutput::from(&snapshot),
+            previous: previous.as_ref().map(BackupCommandOutput::from),
+            changes,
+            dry_run,
+        }))
+    }
+
+    fn export_profile(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        path: Option<&Path>,
+        names: bool,
+        toml: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let identifiers = if names {
+            ProfileIdentifiers::Names
+        } else {
+            ProfileIdentifiers::Ids
+        };
+        let format = if toml {
+            ProfileFormat::Toml
+        } else {
+            path.and_then(ProfileFormat::from_path)
+                .unwrap_or(ProfileFormat::Json)
+        };
+
+        let profile = mime_associations_store.export_profile(desktop_entry_store, identifiers);
+        let contents = profile.to_string(format)?;
+
+        if let Some(path) = path {
+            std::fs::write(path, &contents).map_err(|source| mimeassoc::Error::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        }
+
+        Ok(CommandOutput::Export(ExportCommandOutput {
+            path: path.map(Path::to_path_buf),
+            profile,
+            contents,
+        }))
+    }
+
+    fn import_profile(
+        mime_associations_store: &mut MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        path: &Path,
+        dry_run: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let profile = Profile::load(path)?;
+        let result = mime_associations_store.import_profile(&profile, desktop_entry_store)?;
+
+        // persist the changes...
+        if !dry_run {
+            mime_associations_store.save()?;
+        }
+
+        let assignments = |assignments: Vec<(MimeType, DesktopEntryId)>| {
+            assignments
+                .into_iter()
+                .map(|(mime_type, desktop_entry)| ImportCommandAssignment {
+                    mime_type,
+                    desktop_entry,
+                })
+                .collect()
+        };
+
+        Ok(CommandOutput::Import(ImportCommandOutput {
+            path: path.to_path_buf(),
+            default_applications: assignments(result.default_applications),
+            added_associations: assignments(result.added_associations),
+            skipped: result.skipped,
+            dry_run,
+        }))
+    }
+
+    fn plan(
+        mime_associations_store: &mut MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        path: &Path,
+        apply: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let desired_state = DesiredState::load(path)?;
+        let plan = mime_associations_store.plan(&desired_state, desktop_entry_store);
+
+        // persist the changes...
+        if apply && !plan.changes.is_empty() {
+            mime_associations_store.apply_plan(&plan, desktop_entry_store)?;
+            mime_associations_store.save()?;
+        }
+
+        Ok(CommandOutput::Plan(PlanCommandOutput {
+            path: path.to_path_buf(),
+            changes: plan.changes,
+            unresolved: plan.unresolved,
+            applied: apply,
+        }))
+    }
+
+    fn lint(
+        mime_associations_store: &mut MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+        fix: bool,
+    ) -> Result<CommandOutput, CommandError> {
+        let diagnostics = mime_associations_store.lint(desktop_entry_store, mime_info_store);
+        if !fix {
+            return Ok(CommandOutput::Lint(LintCommandOutput {
+                fixed: vec![],
+                remaining: diagnostics,
+            }));
+        }
+
+        let fixed = mime_associations_store
+            .fix_lint(&diagnostics)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        if !fixed.is_empty() {
+            mime_associations_store.save()?;
+        }
+        let remaining = diagnostics
+            .into_iter()
+            .filter(|diagnostic| !fixed.contains(diagnostic))
+            .collect();
+
+        Ok(CommandOutput::Lint(LintCommandOutput { fixed, remaining }))
+    }
+
+    fn validate_desktop_entry(
+        environment: &Environment,
+        desktop_entry_store: &DesktopEntryStore,
+        target: &str,
+    ) -> Result<CommandOutput, CommandError> {
+        let path = if Path::new(target).is_file() {
+            PathBuf::from(target)
+        } else if let Some(desktop_entry) = lookup_desktop_entry(desktop_entry_store, target) {
+            desktop_entry.path().to_path_buf()
+        } else {
+            // entries which fail to load aren't in the store, but their files can still be found by id
+            DesktopEntryId::parse(target)
+                .or_else(|_| DesktopEntryId::parse(&format!("{}.desktop", target)))
+                .ok()
+                .and_then(|id| desktop_entry_store.find_desktop_entry_file(&id))
+                .ok_or_else(|| CommandError::UnknownApplication(target.to_string()))?
+        };
+
+        Ok(CommandOutput::ValidateDesktop(
+            DesktopEntryValidation::validate_for_environment(path, environment)?,
+        ))
+    }
+
+    fn explain(
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+        target: &str,
+    ) -> CommandOutput {
+        // an existing file named like a mime type, e.g., "text/plain", is explained as a file
+        let mime_type = match MimeType::parse(target) {
+            Ok(mime_type) if !Path::new(target).exists() => mime_type,
+            _ => mime_info_store.mime_type_for_path_or_uri(target),
+        };
+
+        let trace = mime_associations_store.trace_resolution(
+            &mime_type,
+            desktop_entry_store,
+            mime_info_store,
+        );
+
+        CommandOutput::Explain(ExplainCommandOutput {
+            target: target.to_string(),
+            trace,
+        })
+    }
 }
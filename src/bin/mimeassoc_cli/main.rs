@@ -1,9 +1,13 @@
+mod command_error;
 mod command_output;
 mod commands;
 
+use std::path::PathBuf;
+
 use clap::Parser;
 use log::{Level, LevelFilter, Metadata, Record};
 
+use command_error::*;
 use command_output::*;
 use commands::*;
 use mimeassoc::*;
@@ -18,27 +22,53 @@ pub struct Cli {
     #[arg(short, long)]
     json: bool,
 
+    /// Read and write configuration beneath this directory, e.g., a container image or chroot, rather than `/`.
+    /// The host's `$XDG_*` variables are ignored in favor of the spec's defaults
+    #[arg(long, global = true, value_name = "PATH")]
+    root: Option<PathBuf>,
+
+    /// Use this directory as the user's home, rather than `$HOME`. With `--root`, it's relative to the root.
+    /// As with `--root`, the host's `$XDG_*` variables are ignored in favor of the spec's defaults
+    #[arg(long, global = true, value_name = "PATH")]
+    home: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 impl Cli {
+    /// The environment to discover configuration in, the process environment unless overridden by `--home` or `--root`
+    pub fn environment(&self) -> Result<Environment, mimeassoc::Error> {
+        Ok(match (&self.root, &self.home) {
+            (Some(root), Some(home)) => Environment::for_root(root, home),
+            (Some(root), None) => Environment::for_root(root, Environment::from_process()?.home()),
+            (None, Some(home)) => Environment::for_home(home),
+            (None, None) => Environment::from_process()?,
+        })
+    }
+
     pub fn process(
         &self,
         mime_associations_store: &mut MimeTypeAssociationStore,
         desktop_entry_store: &DesktopEntryStore,
         mime_info_store: &MimeTypeInfoStore,
-    ) {
+        role_store: &RoleStore,
+    ) -> Result<(), CommandError> {
         if let Some(command) = &self.command {
             let command_output = command.process(
                 mime_associations_store,
                 desktop_entry_store,
                 mime_info_store,
-            );
+                role_store,
+            )?;
             if let Err(e) = self.command_output_consumer().process(&command_output) {
                 panic!("Error processing command output: {}", e);
             }
+            if let Some(failure) = command_output.failure() {
+                return Err(failure);
+            }
         }
+        Ok(())
     }
 
     fn command_output_consumer(&self) -> Box<dyn CommandOutputConsumer> {
@@ -92,49 +122,50 @@ fn main() {
         .map(|()| log::set_max_level(log_level_filter))
         .expect("Expect to set up logger");
 
-    let desktop_entry_dirs = match desktop_entry_dirs() {
-        Ok(desktop_entry_dirs) => desktop_entry_dirs,
-        Err(e) => panic!("Unable to load desktop_entry_dirs: {:?}", e),
-    };
-
-    let mimeapps_lists = match mimeapps_lists_paths() {
-        Ok(mimeapps_lists) => mimeapps_lists,
-        Err(e) => panic!("Unable to load mimeapps_lists_paths: {:?}", e),
-    };
-
-    let mimetype_info_paths = match mimeinfo_paths() {
-        Ok(paths) => paths,
-        Err(e) => panic!("Unable to load mimeinfo_paths: {:?}", e),
-    };
-
-    let mut mime_associations_store = match MimeTypeAssociationStore::load(&mimeapps_lists) {
-        Ok(mimeassoc) => mimeassoc,
-        Err(e) => panic!("Unable to load MimeAssociationStore: {:?}", e),
-    };
-
-    let desktop_entry_store = match DesktopEntryStore::load(&desktop_entry_dirs) {
-        Ok(desktop_entries) => desktop_entries,
-        Err(e) => panic!("Unable to load DesktopEntryStore: {:?}", e),
-    };
-
-    let mime_info_store = match MimeTypeInfoStore::load(&mimetype_info_paths) {
-        Ok(s) => s,
-        Err(e) => panic!("Unable to load MimeTypeInfoStore: {:?}", e),
-    };
+    let cli = Cli::parse();
+    let environment = cli
+        .environment()
+        .unwrap_or_else(|e| exit_with_error(e.into()));
+
+    let desktop_entry_dirs = environment.desktop_entry_dirs();
+    let mimeapps_lists = environment
+        .mimeapps_lists_paths()
+        .unwrap_or_else(|e| exit_with_error(e.into()));
+    let mimetype_info_paths = environment
+        .mimeinfo_paths()
+        .unwrap_or_else(|e| exit_with_error(e.into()));
+
+    let mut mime_associations_store =
+        MimeTypeAssociationStore::load_for_environment(&mimeapps_lists, &environment)
+            .unwrap_or_else(|e| exit_with_error(e.into()));
+    let desktop_entry_store = DesktopEntryStore::load_for_desktops(
+        &desktop_entry_dirs,
+        environment.current_desktops().to_vec(),
+    )
+    .unwrap_or_else(|e| exit_with_error(e.into()));
+    let mime_info_store =
+        MimeTypeInfoStore::load(&mimetype_info_paths).unwrap_or_else(|e| exit_with_error(e.into()));
+    let role_store = RoleStore::load(environment.user_roles_path())
+        .unwrap_or_else(|e| exit_with_error(e.into()));
 
     for mime_type in desktop_entry_store.mime_types() {
         if let Err(e) = mime_associations_store.add_added_associations(&mime_type, &[]) {
-            panic!(
-                "Unable to add mimetypes from desktop entry store to mime association store {:?}",
-                e
-            );
+            exit_with_error(e.into());
         }
     }
 
-    let cli = Cli::parse();
-    cli.process(
+    if let Err(e) = cli.process(
         &mut mime_associations_store,
         &desktop_entry_store,
         &mime_info_store,
-    );
+        &role_store,
+    ) {
+        exit_with_error(e);
+    }
+}
+
+/// Report `error` on stderr and exit with its exit code. See `CommandError::exit_code`.
+fn exit_with_error(error: CommandError) -> ! {
+    eprintln!("error: {}", error);
+    std::process::exit(error.exit_code());
 }
@@ -1,3 +1,7 @@
+use std::sync::OnceLock;
+
+use mimeassoc::Environment;
+
 pub const APP_ID: &str = "org.zakariya.MimeAssoc";
 pub const APP_VERSION: &str = "0.0.1";
 pub const APP_ICON: &str = "org.zakariya.MimeAssoc";
@@ -5,3 +9,21 @@ pub const APP_NAME: &str = "MimeAssoc";
 pub const APP_DEVELOPER: &str = "Shamyl Zakariya";
 pub const APP_ISSUES_URL: &str = "https://github.com/ShamylZakariya/mimeassoc/issues";
 pub const APP_WEBSITE_URL: &str = "https://github.com/ShamylZakariya/mimeassoc";
+
+static ENVIRONMENT: OnceLock<Environment> = OnceLock::new();
+
+/// Set the environment the stores are loaded against, e.g., from the `--root` and `--home` options.
+/// Only the first environment set is used.
+pub fn set_environment(environment: Environment) {
+    if ENVIRONMENT.set(environment).is_err() {
+        log::warn!("common::set_environment - environment was already set");
+    }
+}
+
+/// The environment the stores are loaded against; the process environment unless set with `set_environment`
+pub fn environment() -> Result<Environment, mimeassoc::Error> {
+    match ENVIRONMENT.get() {
+        Some(environment) => Ok(environment.clone()),
+        None => Environment::from_process(),
+    }
+}
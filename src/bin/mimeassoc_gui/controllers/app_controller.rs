@@ -1,5 +1,6 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::time::Duration;
 
 use adw::subclass::prelude::*;
 use adw::{prelude::*, *};
@@ -10,7 +11,7 @@ use crate::model::*;
 use crate::resources::Strings;
 use crate::ui::MainWindow;
 
-use super::{ApplicationsModeController, MimeTypesModeController};
+use super::{ApplicationsModeController, MimeTypesModeController, MimeTypesModeKind};
 
 /// Represents a command which can be sent to the main window. This is primarily
 /// meant for easing manual testing, but could be used to handle gui cmdline arguments,
@@ -21,11 +22,12 @@ pub enum MainWindowCommand {
     ShowApplication(DesktopEntryId),
 }
 
-/// Represents the top-level "page" the app is displaying, Applications or Mime Types.
+/// Represents the top-level "page" the app is displaying, Applications, Mime Types or URL Schemes.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Mode {
     ApplicationMode,
     MimeTypeMode,
+    SchemeMode,
 }
 
 /// Represents the change in precision of a filter string; client selection behavior
@@ -83,9 +85,11 @@ mod imp {
         pub window: OnceCell<WeakRef<MainWindow>>,
         pub stores: OnceCell<Rc<RefCell<Stores>>>,
         pub mime_types_mode_controller: OnceCell<MimeTypesModeController>,
+        pub schemes_mode_controller: OnceCell<MimeTypesModeController>,
         pub applications_mode_controller: OnceCell<ApplicationsModeController>,
         pub current_search_string: RefCell<Option<String>>,
         pub current_detail_view_mode: RefCell<DetailViewMode>,
+        pub is_querying_reload: Cell<bool>,
     }
 
     // The central trait for subclassing a GObject
@@ -125,6 +129,17 @@ impl AppController {
             .set(MimeTypesModeController::new(
                 window.clone(),
                 weak_self.clone(),
+                MimeTypesModeKind::MimeTypes,
+            ))
+            .unwrap();
+
+        instance
+            .imp()
+            .schemes_mode_controller
+            .set(MimeTypesModeController::new(
+                window.clone(),
+                weak_self.clone(),
+                MimeTypesModeKind::Schemes,
             ))
             .unwrap();
 
@@ -157,18 +172,31 @@ impl AppController {
         match mode {
             Mode::ApplicationMode => {
                 self.mime_types_mode_controller().deactivate();
+                self.schemes_mode_controller().deactivate();
                 window.imp().mode_selector_mime_types.set_active(false);
+                window.imp().mode_selector_schemes.set_active(false);
 
                 self.applications_mode_controller().activate();
                 window.imp().mode_selector_applications.set_active(true);
             }
             Mode::MimeTypeMode => {
                 self.applications_mode_controller().deactivate();
+                self.schemes_mode_controller().deactivate();
                 window.imp().mode_selector_applications.set_active(false);
+                window.imp().mode_selector_schemes.set_active(false);
 
                 self.mime_types_mode_controller().activate();
                 window.imp().mode_selector_mime_types.set_active(true);
             }
+            Mode::SchemeMode => {
+                self.applications_mode_controller().deactivate();
+                self.mime_types_mode_controller().deactivate();
+                window.imp().mode_selector_applications.set_active(false);
+                window.imp().mode_selector_mime_types.set_active(false);
+
+                self.schemes_mode_controller().activate();
+                window.imp().mode_selector_schemes.set_active(true);
+            }
         }
     }
 
@@ -201,10 +229,32 @@ impl AppController {
         self.store_was_mutated();
     }
 
+    /// Assigns an application to handle every mime type of a role it supports. E.g., assign Firefox as the web browser
+    pub fn assign_application_to_role(&self, role_id: &str, desktop_entry_id: &DesktopEntryId) {
+        log::debug!(
+            "AppController::assign_application_to_role application: {} role: {}",
+            desktop_entry_id,
+            role_id,
+        );
+
+        if let Err(e) = self
+            .stores()
+            .borrow_mut()
+            .set_application_to_role_binding(role_id, desktop_entry_id)
+        {
+            self.show_error("Unable to assign application to role", &e);
+            return;
+        }
+
+        // Assignment was successful, mark changes were made
+        self.store_was_mutated();
+    }
+
     pub fn reload_active_mode(&self) {
         match self.mode() {
             Mode::ApplicationMode => self.applications_mode_controller().reload_detail(),
             Mode::MimeTypeMode => self.mime_types_mode_controller().reload_detail(),
+            Mode::SchemeMode => self.schemes_mode_controller().reload_detail(),
         }
     }
 
@@ -237,18 +287,78 @@ impl AppController {
     }
 
     fn setup_stores(&self) {
-        match Stores::new() {
+        let stores = crate::common::environment()
+            .map_err(anyhow::Error::from)
+            .and_then(|environment| Stores::new(&environment));
+        match stores {
             Ok(stores) => {
                 self.imp()
                     .stores
                     .set(Rc::new(RefCell::new(stores)))
                     .expect("AppController::setup_models() should only be set once");
                 self.store_was_mutated();
+                self.watch_for_external_changes();
             }
             Err(e) => self.show_error("Unable to load necessary data", &e),
         }
     }
 
+    /// Periodically check for changes other applications made to the files the stores were loaded from,
+    /// e.g., a package install or `xdg-mime default`, so the UI doesn't show stale data.
+    fn watch_for_external_changes(&self) {
+        glib::timeout_add_local(
+            Duration::from_millis(250),
+            clone!(@weak self as app_controller => @default-return glib::ControlFlow::Break, move || {
+                app_controller.check_for_external_changes();
+                glib::ControlFlow::Continue
+            }),
+        );
+    }
+
+    fn check_for_external_changes(&self) {
+        if self.imp().is_querying_reload.get() {
+            return;
+        }
+
+        let stores = self.stores();
+        let Some(changes) = stores.borrow_mut().poll_for_external_changes() else {
+            return;
+        };
+
+        log::debug!(
+            "AppController::check_for_external_changes paths: {:?}",
+            changes.paths()
+        );
+
+        let is_dirty = stores.borrow().is_dirty();
+        if is_dirty {
+            self.query_reload_for_external_changes();
+        } else {
+            self.reload_stores();
+        }
+    }
+
+    /// Reload the stores from disk and rebuild the models of each mode to match
+    fn reload_stores(&self) {
+        log::debug!("AppController::reload_stores");
+
+        let result = crate::common::environment()
+            .map_err(anyhow::Error::from)
+            .and_then(|environment| self.stores().borrow_mut().reload(&environment));
+        if let Err(e) = result {
+            self.show_error("Unable to reload changes made by other applications", &e);
+            return;
+        }
+
+        self.applications_mode_controller().reload_model();
+        self.mime_types_mode_controller().reload_model();
+        self.schemes_mode_controller().reload_model();
+
+        self.store_was_mutated();
+        self.reload_active_mode();
+        self.show_toast(Strings::reloaded_external_changes_toast());
+    }
+
     fn reset_user_default_application_assignments(&self) {
         log::debug!("AppController::reset_user_default_application_assignments",);
 
@@ -295,6 +405,20 @@ impl AppController {
         self.reload_active_mode();
     }
 
+    fn fix_problems(&self, diagnostics: &[LintDiagnostic]) {
+        log::debug!("AppController::fix_problems",);
+
+        let fixed = self.stores().borrow_mut().fix_lint(diagnostics);
+        log::debug!("AppController::fix_problems fixed: {}", fixed);
+        if fixed == 0 {
+            return;
+        }
+
+        // Persist our changes and reload display; the fixes can be undone, and the undo committed
+        self.commit_changes();
+        self.reload_active_mode();
+    }
+
     fn window(&self) -> MainWindow {
         self.imp()
             .window
@@ -311,6 +435,13 @@ impl AppController {
             .expect("Expect MimeTypesModeController to be assigned")
     }
 
+    pub fn schemes_mode_controller(&self) -> &MimeTypesModeController {
+        self.imp()
+            .schemes_mode_controller
+            .get()
+            .expect("Expect schemes MimeTypesModeController to be assigned")
+    }
+
     pub fn applications_mode_controller(&self) -> &ApplicationsModeController {
         self.imp()
             .applications_mode_controller
@@ -441,6 +572,129 @@ impl AppController {
         dialog.present();
     }
 
+    /// Show user the problems found in the mimeapps.list files, offering to fix those in their own.
+    pub fn show_problems(&self) {
+        log::debug!("AppController::show_problems",);
+
+        let diagnostics = self.stores().borrow().lint();
+        let fixable = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_fixable())
+            .count();
+
+        let window = self.window();
+        let close_response = "close";
+        let fix_response = "fix";
+
+        // Create new dialog
+        let dialog = adw::MessageDialog::builder()
+            .heading(Strings::problems_dialog_title())
+            .body(Strings::problems_dialog_body(diagnostics.len(), fixable))
+            .transient_for(&window)
+            .modal(true)
+            .destroy_with_parent(true)
+            .close_response(close_response)
+            .default_response(close_response)
+            .build();
+        dialog.add_responses(&[
+            (close_response, Strings::close()),
+            (fix_response, Strings::problems_dialog_action_fix()),
+        ]);
+
+        dialog.set_response_appearance(fix_response, ResponseAppearance::Suggested);
+        dialog.set_response_enabled(fix_response, fixable > 0);
+
+        if !diagnostics.is_empty() {
+            let list = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .build();
+            list.add_css_class("boxed-list");
+
+            for diagnostic in diagnostics.iter() {
+                let icon_name = match diagnostic.severity {
+                    LintSeverity::Error => "dialog-error-symbolic",
+                    LintSeverity::Warning => "dialog-warning-symbolic",
+                    LintSeverity::Info => "dialog-information-symbolic",
+                };
+                let row = ActionRow::new();
+                // problems quote the file, which isn't markup
+                row.set_use_markup(false);
+                row.set_title(&diagnostic.problem.to_string());
+                row.set_subtitle(&Strings::problem_row_subtitle(diagnostic));
+                row.add_prefix(&gtk::Image::from_icon_name(icon_name));
+                list.append(&row);
+            }
+
+            let scrolled_window = gtk::ScrolledWindow::builder()
+                .min_content_height(320)
+                .child(&list)
+                .build();
+            dialog.set_extra_child(Some(&scrolled_window));
+        }
+
+        dialog.connect_response(
+            None,
+            clone!(@weak self as app_controller => move |dialog, response|{
+                dialog.destroy();
+                if response != fix_response {
+                    return;
+                }
+
+                app_controller.fix_problems(&diagnostics);
+            }),
+        );
+
+        dialog.present();
+    }
+
+    /// Show user a dialog asking if they want to reload changes made by other applications, discarding their uncommitted changes.
+    fn query_reload_for_external_changes(&self) {
+        log::debug!("AppController::query_reload_for_external_changes",);
+
+        let window = self.window();
+        let keep_response = "keep";
+        let reload_response = "reload";
+
+        // Create new dialog
+        let dialog = adw::MessageDialog::builder()
+            .heading(Strings::reload_external_changes_dialog_title())
+            .body(Strings::reload_external_changes_dialog_body())
+            .transient_for(&window)
+            .modal(true)
+            .destroy_with_parent(true)
+            .close_response(keep_response)
+            .default_response(keep_response)
+            .build();
+        dialog.add_responses(&[
+            (
+                keep_response,
+                Strings::reload_external_changes_dialog_action_keep(),
+            ),
+            (
+                reload_response,
+                Strings::reload_external_changes_dialog_action_proceed(),
+            ),
+        ]);
+
+        dialog.set_response_appearance(reload_response, ResponseAppearance::Destructive);
+
+        self.imp().is_querying_reload.set(true);
+        dialog.connect_response(
+            None,
+            clone!(@weak self as app_controller => move |dialog, response|{
+                dialog.destroy();
+                app_controller.imp().is_querying_reload.set(false);
+                if response != reload_response {
+                    return;
+                }
+
+                app_controller.reload_stores();
+            }),
+        );
+
+        dialog.present();
+    }
+
     pub fn perform_command(&self, command: MainWindowCommand) {
         match command {
             MainWindowCommand::ShowApplication(desktop_entry_id) => {
@@ -448,6 +702,10 @@ impl AppController {
                 self.applications_mode_controller()
                     .select_application(&desktop_entry_id);
             }
+            MainWindowCommand::ShowMimeType(mime_type) if mime_type.is_scheme_handler() => {
+                self.set_mode(Mode::SchemeMode);
+                self.schemes_mode_controller().select_mime_type(&mime_type);
+            }
             MainWindowCommand::ShowMimeType(mime_type) => {
                 self.set_mode(Mode::MimeTypeMode);
                 self.mime_types_mode_controller()
@@ -493,16 +751,18 @@ impl AppController {
             error
         );
 
+        let error_description = match error.downcast_ref::<mimeassoc::Error>() {
+            Some(error) => Strings::error_dialog_error_description(error),
+            None => error.to_string(),
+        };
+
         let window = self.window();
         let copy_to_clipboard_response = "ctcp";
 
         // Create new dialog
         let dialog = adw::MessageDialog::builder()
             .heading(Strings::error_dialog_title())
-            .body(Strings::error_dialog_body(
-                message,
-                error.to_string().as_str(),
-            ))
+            .body(Strings::error_dialog_body(message, &error_description))
             .transient_for(&window)
             .modal(true)
             .destroy_with_parent(true)
@@ -566,6 +826,9 @@ impl AppController {
             Mode::MimeTypeMode => self
                 .mime_types_mode_controller()
                 .on_search_changed(self.current_search_string().as_deref(), change_type),
+            Mode::SchemeMode => self
+                .schemes_mode_controller()
+                .on_search_changed(self.current_search_string().as_deref(), change_type),
         }
     }
 
@@ -4,7 +4,7 @@ use std::rc::Rc;
 use adw::subclass::prelude::*;
 use adw::{prelude::*, *};
 use gtk::{glib::*, *};
-use mimeassoc::DesktopEntryId;
+use mimeassoc::{DesktopEntryId, DesktopEntryValidation, LintSeverity};
 
 use crate::model::*;
 use crate::resources::Strings;
@@ -149,6 +149,11 @@ impl ApplicationsModeController {
         for s_id in signal_handler_ids.into_iter() {
             window.imp().collections_list.disconnect(s_id);
         }
+
+        window
+            .imp()
+            .application_mode_detail_problems_list
+            .set_visible(false);
     }
 
     pub fn on_search_changed(
@@ -213,6 +218,19 @@ impl ApplicationsModeController {
         self.select_all_or_none(false);
     }
 
+    /// Called when the user picks a role from the roles menu, to make the selected application its handler
+    pub fn on_assign_role(&self, role_id: &str) {
+        if let Some(application_entry) = self.current_selection() {
+            let desktop_entry_id = application_entry
+                .desktop_entry_id()
+                .expect("Expect ApplicationEntry to have a valid DesktopEntryId");
+
+            self.app_controller()
+                .assign_application_to_role(role_id, &desktop_entry_id);
+            self.show_detail(&application_entry);
+        }
+    }
+
     fn select_all_or_none(&self, all: bool) {
         let app_controller = self.app_controller();
         if let Some(application_entry) = self.current_selection() {
@@ -247,18 +265,7 @@ impl ApplicationsModeController {
             return;
         }
 
-        let stores = self.stores();
-        let borrowed_stores = stores.borrow();
-        let apps = borrowed_stores.desktop_entry_store();
-
-        let mut all_desktop_entries = apps.desktop_entries();
-        all_desktop_entries.sort_by(|a, b| a.cmp_by_name_alpha_inensitive(b));
-
-        let application_entries = all_desktop_entries
-            .iter()
-            .filter(|de| !de.mime_types().is_empty())
-            .map(|de| ApplicationEntry::new(de.id(), stores.clone()))
-            .collect::<Vec<_>>();
+        let application_entries = self.application_entries();
 
         let list_store = gio::ListStore::with_type(ApplicationEntry::static_type());
         list_store.extend_from_slice(&application_entries);
@@ -282,6 +289,38 @@ impl ApplicationsModeController {
         self.imp().selection_model.set(selection_model).unwrap();
     }
 
+    /// Rebuilds the contents of the applications listbox model, e.g., after the stores were reloaded
+    pub fn reload_model(&self) {
+        let Some(list_store) = self
+            .imp()
+            .filter_model
+            .get()
+            .and_then(|filter_model| filter_model.model())
+            .and_downcast::<gio::ListStore>()
+        else {
+            return;
+        };
+
+        let application_entries = self.application_entries();
+        list_store.splice(0, list_store.n_items(), &application_entries);
+    }
+
+    /// The entries of the applications listbox, one per visible application which handles mime types
+    fn application_entries(&self) -> Vec<ApplicationEntry> {
+        let stores = self.stores();
+        let borrowed_stores = stores.borrow();
+        let apps = borrowed_stores.desktop_entry_store();
+
+        let mut all_desktop_entries = apps.visible_desktop_entries();
+        all_desktop_entries.sort_by(|a, b| a.cmp_by_name_alpha_inensitive(b));
+
+        all_desktop_entries
+            .iter()
+            .filter(|de| !de.mime_types().is_empty())
+            .map(|de| ApplicationEntry::new(de.id(), stores.clone()))
+            .collect()
+    }
+
     fn show_detail(&self, application_entry: &ApplicationEntry) {
         let mime_type_assignments = application_entry.mime_type_assignments();
         let model = NoSelection::new(Some(mime_type_assignments));
@@ -305,6 +344,8 @@ impl ApplicationsModeController {
 
         self.update_detail_labels(application_entry);
         self.update_select_all_and_none_buttons();
+        self.update_roles_menu(application_entry);
+        self.update_problems_list(application_entry);
     }
 
     fn update_detail_labels(&self, application_entry: &ApplicationEntry) {
@@ -332,6 +373,7 @@ impl ApplicationsModeController {
                 let stores = self.stores();
                 let stores = stores.borrow();
                 let mime_associations_store = stores.mime_associations_store();
+                let desktop_entry_store = stores.desktop_entry_store();
                 let mut num_assigned = 0;
 
                 for i in 0..n_items {
@@ -340,7 +382,11 @@ impl ApplicationsModeController {
                             .expect("ApplicationsEntry::mime_type_assignments() model should contain instances of MimeTypeEntry only");
 
                     let is_assigned_application = mime_associations_store
-                        .default_application_for(&mime_type_entry.mime_type())
+                        .installed_default_application_for(
+                            &mime_type_entry.mime_type(),
+                            desktop_entry_store,
+                        )
+                        .map(DesktopEntry::id)
                         == Some(&desktop_entry_id);
                     if is_assigned_application {
                         num_assigned += 1;
@@ -361,6 +407,76 @@ impl ApplicationsModeController {
         }
     }
 
+    /// Populate the roles menu with the roles the application can fill, e.g., "Use as Web Browser"
+    fn update_roles_menu(&self, application_entry: &ApplicationEntry) {
+        let menu = gio::Menu::new();
+
+        if let Some(desktop_entry) = application_entry.desktop_entry() {
+            let stores = self.stores();
+            let stores = stores.borrow();
+
+            for role in stores.role_store().roles_for(&desktop_entry) {
+                let role_handlers = stores
+                    .mime_associations_store()
+                    .role_handlers(role, stores.desktop_entry_store());
+                let label = Strings::assign_role_menu_item(role, &role_handlers, &desktop_entry);
+
+                let item = gio::MenuItem::new(Some(&label), None);
+                item.set_action_and_target_value(
+                    Some("win.assign-role"),
+                    Some(&role.id().to_variant()),
+                );
+                menu.append_item(&item);
+            }
+        }
+
+        let roles_menu_button = &self.window().imp().roles_menu_button;
+        roles_menu_button.set_sensitive(menu.n_items() > 0);
+        roles_menu_button.set_menu_model(Some(&menu));
+    }
+
+    /// List the problems found in the application's desktop entry, e.g., to explain why it isn't offered
+    /// for its mime types. The list is hidden if there are none.
+    fn update_problems_list(&self, application_entry: &ApplicationEntry) {
+        let window = self.window();
+        let list_box = &window.imp().application_mode_detail_problems_list;
+        while let Some(row) = list_box.first_child() {
+            list_box.remove(&row);
+        }
+
+        let Some(validation) = application_entry
+            .desktop_entry()
+            .zip(crate::common::environment().ok())
+            .and_then(|(desktop_entry, environment)| {
+                DesktopEntryValidation::validate_for_environment(desktop_entry.path(), &environment)
+                    .ok()
+            })
+        else {
+            list_box.set_visible(false);
+            return;
+        };
+
+        for diagnostic in validation.diagnostics.iter() {
+            let icon_name = match diagnostic.severity {
+                LintSeverity::Error => "dialog-error-symbolic",
+                LintSeverity::Warning => "dialog-warning-symbolic",
+                LintSeverity::Info => "dialog-information-symbolic",
+            };
+            let row = ActionRow::new();
+            // problems quote the file, which isn't markup
+            row.set_use_markup(false);
+            row.set_title(&diagnostic.problem.to_string());
+            row.set_subtitle(&Strings::desktop_entry_problem_row_subtitle(
+                &validation.path,
+                diagnostic,
+            ));
+            row.add_prefix(&Image::from_icon_name(icon_name));
+            list_box.append(&row);
+        }
+
+        list_box.set_visible(!validation.diagnostics.is_empty());
+    }
+
     fn create_primary_row(application_entry: &ApplicationEntry) -> ListBoxRow {
         let application_name_label = Label::builder()
             .wrap(true)
@@ -418,12 +534,14 @@ impl ApplicationsModeController {
             let stores = self.stores();
             let stores = stores.borrow();
             let mime_associations_store = stores.mime_associations_store();
+            let desktop_entry_store = stores.desktop_entry_store();
 
             let is_system_default_application = mime_associations_store
                 .system_default_application_for(&mime_type)
                 == Some(desktop_entry.id());
             let is_assigned_application = mime_associations_store
-                .default_application_for(&mime_type)
+                .installed_default_application_for(&mime_type, desktop_entry_store)
+                .map(DesktopEntry::id)
                 == Some(desktop_entry.id());
             (is_system_default_application, is_assigned_application)
         };
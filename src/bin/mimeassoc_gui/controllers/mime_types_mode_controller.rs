@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use adw::subclass::prelude::*;
@@ -13,6 +13,24 @@ use crate::ui::MainWindow;
 use super::app_controller::{DetailViewMode, FilterPrecisionChange};
 use super::AppController;
 
+/// The kind of mime types a MimeTypesModeController lists. URL scheme handlers, e.g., `x-scheme-handler/mailto`,
+/// are presented by scheme in their own mode, rather than alongside the regular mime types.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MimeTypesModeKind {
+    #[default]
+    MimeTypes,
+    Schemes,
+}
+
+impl MimeTypesModeKind {
+    fn lists(&self, mime_type: &MimeType) -> bool {
+        match self {
+            Self::MimeTypes => !mime_type.is_scheme_handler(),
+            Self::Schemes => mime_type.is_scheme_handler(),
+        }
+    }
+}
+
 mod imp {
     use super::*;
     use std::cell::OnceCell;
@@ -23,6 +41,7 @@ mod imp {
     pub struct MimeTypesModeController {
         pub window: OnceCell<WeakRef<MainWindow>>,
         pub app_controller: OnceCell<WeakRef<AppController>>,
+        pub kind: Cell<MimeTypesModeKind>,
         pub application_check_button_group: RefCell<Option<CheckButton>>,
         pub current_selection: RefCell<Option<MimeTypeEntry>>,
         pub signal_handlers: RefCell<Vec<SignalHandlerId>>,
@@ -51,10 +70,15 @@ glib::wrapper! {
 }
 
 impl MimeTypesModeController {
-    pub fn new(window: WeakRef<MainWindow>, app_controller: WeakRef<AppController>) -> Self {
+    pub fn new(
+        window: WeakRef<MainWindow>,
+        app_controller: WeakRef<AppController>,
+        kind: MimeTypesModeKind,
+    ) -> Self {
         let instance: MimeTypesModeController = Object::builder().build();
         instance.imp().window.set(window).unwrap();
         instance.imp().app_controller.set(app_controller).unwrap();
+        instance.imp().kind.set(kind);
         instance
     }
 
@@ -222,18 +246,7 @@ impl MimeTypesModeController {
             return;
         }
 
-        let stores = self.stores();
-        let borrowed_stores = stores.borrow();
-        let mime_associations_store = borrowed_stores.mime_associations_store();
-
-        let mut all_mime_types = mime_associations_store.mime_types();
-        all_mime_types.sort();
-
-        let mime_type_entries = all_mime_types
-            .iter()
-            .map(|mt| MimeTypeEntry::new(mt, stores.clone()))
-            .filter(|e| e.supported_application_entries().n_items() > 0)
-            .collect::<Vec<_>>();
+        let mime_type_entries = self.mime_type_entries();
 
         let list_store = gio::ListStore::with_type(MimeTypeEntry::static_type());
         list_store.extend_from_slice(&mime_type_entries);
@@ -258,6 +271,52 @@ impl MimeTypesModeController {
         self.imp().selection_model.set(selection_model).unwrap();
     }
 
+    /// Rebuilds the contents of the mime types listbox model, e.g., after the stores were reloaded
+    pub fn reload_model(&self) {
+        let Some(list_store) = self
+            .imp()
+            .filter_model
+            .get()
+            .and_then(|filter_model| filter_model.model())
+            .and_downcast::<gio::ListStore>()
+        else {
+            return;
+        };
+
+        let mime_type_entries = self.mime_type_entries();
+        list_store.splice(0, list_store.n_items(), &mime_type_entries);
+    }
+
+    /// The entries of the mime types listbox, one per mime type of this mode's kind which an application supports
+    fn mime_type_entries(&self) -> Vec<MimeTypeEntry> {
+        let stores = self.stores();
+        let borrowed_stores = stores.borrow();
+        let mime_associations_store = borrowed_stores.mime_associations_store();
+
+        let kind = self.kind();
+        let mut all_mime_types = match kind {
+            MimeTypesModeKind::MimeTypes => mime_associations_store
+                .mime_types()
+                .into_iter()
+                .filter(|mt| kind.lists(mt))
+                .cloned()
+                .collect::<Vec<_>>(),
+            // as with the CLI, schemes installed applications declare are listed even if no mimeapps.list mentions them
+            MimeTypesModeKind::Schemes => mime_associations_store
+                .schemes(borrowed_stores.desktop_entry_store())
+                .iter()
+                .filter_map(|scheme| MimeType::for_scheme(scheme).ok())
+                .collect::<Vec<_>>(),
+        };
+        all_mime_types.sort();
+
+        all_mime_types
+            .iter()
+            .map(|mt| MimeTypeEntry::new(mt, stores.clone()))
+            .filter(|e| e.supported_application_entries().n_items() > 0)
+            .collect()
+    }
+
     /// Creates a row for the primary/selection list box
     fn create_primary_row(&self, model: &MimeTypeEntry) -> ListBoxRow {
         let stores = self.stores();
@@ -275,7 +334,7 @@ impl MimeTypesModeController {
             .css_classes(vec!["mime-type"])
             .build();
 
-        mime_type_label.set_text(&mime_type.to_string());
+        mime_type_label.set_text(&self.title_for(mime_type));
 
         let content = gtk::Box::builder()
             .orientation(Orientation::Vertical)
@@ -284,7 +343,7 @@ impl MimeTypesModeController {
 
         content.append(&mime_type_label);
 
-        if let Some(name) = mime_info.and_then(|info| info.comment()) {
+        if let Some(name) = self.subtitle_for(mime_type, mime_info) {
             let file_type_name_label = Label::builder()
                 .wrap(true)
                 .wrap_mode(pango::WrapMode::Word)
@@ -293,7 +352,7 @@ impl MimeTypesModeController {
                 .css_classes(vec!["mime-type-description"])
                 .build();
 
-            file_type_name_label.set_text(name);
+            file_type_name_label.set_text(&name);
 
             content.append(&file_type_name_label);
         }
@@ -389,15 +448,38 @@ impl MimeTypesModeController {
         let detail_label_primary = &window.imp().detail_title;
         let detail_label_secondary = &window.imp().detail_sub_title;
 
-        detail_label_primary.set_text(&mime_type_entry.id());
+        detail_label_primary.set_text(&self.title_for(mime_type));
 
-        if let Some(name) = mime_info.and_then(|info| info.comment()) {
-            detail_label_secondary.set_text(name);
+        if let Some(name) = self.subtitle_for(mime_type, mime_info) {
+            detail_label_secondary.set_text(&name);
         } else {
             detail_label_secondary.set_text("");
         }
     }
 
+    /// The primary label for a mime type; URL scheme handlers are shown by scheme, e.g., "mailto"
+    fn title_for(&self, mime_type: &MimeType) -> String {
+        match (self.kind(), mime_type.scheme()) {
+            (MimeTypesModeKind::Schemes, Some(scheme)) => scheme.to_string(),
+            _ => mime_type.to_string(),
+        }
+    }
+
+    /// The secondary label for a mime type; its description for regular mime types, or the
+    /// underlying pseudo mime type for URL scheme handlers
+    fn subtitle_for(
+        &self,
+        mime_type: &MimeType,
+        mime_info: Option<&MimeTypeInfo>,
+    ) -> Option<String> {
+        match self.kind() {
+            MimeTypesModeKind::MimeTypes => mime_info
+                .and_then(|info| info.comment())
+                .map(str::to_string),
+            MimeTypesModeKind::Schemes => Some(mime_type.to_string()),
+        }
+    }
+
     fn create_detail_row(
         &self,
         mime_type_entry: &MimeTypeEntry,
@@ -416,6 +498,7 @@ impl MimeTypesModeController {
                 let stores = self.stores();
                 let stores = stores.borrow();
                 let mime_associations_store = stores.mime_associations_store();
+                let desktop_entry_store = stores.desktop_entry_store();
                 let desktop_entry_id = application_entry
                     .desktop_entry_id()
                     .expect("Expect to get desktop entry id");
@@ -424,7 +507,8 @@ impl MimeTypesModeController {
                     .system_default_application_for(&mime_type)
                     == Some(&desktop_entry_id);
                 let is_assigned_application = mime_associations_store
-                    .default_application_for(&mime_type)
+                    .installed_default_application_for(&mime_type, desktop_entry_store)
+                    .map(DesktopEntry::id)
                     == Some(&desktop_entry_id);
                 (is_default_application, is_assigned_application)
             };
@@ -473,12 +557,8 @@ impl MimeTypesModeController {
             let desktop_entry_store = stores.desktop_entry_store();
 
             let an_application_is_assigned = mime_associations_store
-                .default_application_for(&mime_type)
-                .is_some_and(|desktop_entry_id| {
-                    desktop_entry_store
-                        .find_desktop_entry_with_id(desktop_entry_id)
-                        .is_some_and(|e| e.appears_valid_application())
-                });
+                .installed_default_application_for(&mime_type, desktop_entry_store)
+                .is_some_and(|e| e.appears_valid_application());
 
             let can_assign_none = !mime_associations_store
                 .system_default_application_for(&mime_type)
@@ -544,6 +624,10 @@ impl MimeTypesModeController {
         }
     }
 
+    fn kind(&self) -> MimeTypesModeKind {
+        self.imp().kind.get()
+    }
+
     fn window(&self) -> MainWindow {
         self.imp()
             .window
@@ -7,3 +7,4 @@ pub use app_controller::MainWindowCommand;
 pub use app_controller::Mode;
 pub use applications_mode_controller::ApplicationsModeController;
 pub use mime_types_mode_controller::MimeTypesModeController;
+pub use mime_types_mode_controller::MimeTypesModeKind;
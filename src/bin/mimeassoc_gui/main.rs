@@ -102,6 +102,24 @@ fn main() -> glib::ExitCode {
         .application_id(crate::common::APP_ID)
         .build();
 
+    app.add_main_option(
+        "root",
+        glib::Char::from(b'r'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Read and write configuration beneath this directory, e.g., a container image or chroot",
+        Some("PATH"),
+    );
+    app.add_main_option(
+        "home",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Use this directory as the user's home, rather than $HOME",
+        Some("PATH"),
+    );
+    app.connect_handle_local_options(handle_local_options);
+
     app.connect_startup(|app| {
         setup_shortcuts(app);
         load_css();
@@ -111,6 +129,34 @@ fn main() -> glib::ExitCode {
     app.run()
 }
 
+/// Apply the `--root` and `--home` options to the environment the stores will be loaded against
+fn handle_local_options(_app: &adw::Application, options: &glib::VariantDict) -> i32 {
+    let root = options.lookup::<String>("root").ok().flatten();
+    let home = options.lookup::<String>("home").ok().flatten();
+    if root.is_none() && home.is_none() {
+        // continue with the default handling
+        return -1;
+    }
+
+    let environment = match (root, home) {
+        (Some(root), Some(home)) => mimeassoc::Environment::for_root(root, home),
+        (None, Some(home)) => mimeassoc::Environment::for_home(home),
+        (root, None) => match mimeassoc::Environment::from_process() {
+            Ok(environment) => match root {
+                Some(root) => mimeassoc::Environment::for_root(root, environment.home()),
+                None => environment,
+            },
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return 1;
+            }
+        },
+    };
+    crate::common::set_environment(environment);
+
+    -1
+}
+
 fn load_css() {
     let provider = CssProvider::new();
     provider.load_from_resource("/org/zakariya/MimeAssoc/style.css");
@@ -133,6 +179,7 @@ fn setup_shortcuts(app: &adw::Application) {
     // bind accelerators to actions
     app.set_accels_for_action("win.show-mime-types", &["<Ctrl>M"]);
     app.set_accels_for_action("win.show-applications", &["<Ctrl>A"]);
+    app.set_accels_for_action("win.show-schemes", &["<Ctrl>U"]);
     app.set_accels_for_action("win.undo", &["<Ctrl>Z"]);
     app.set_accels_for_action("win.log-history-stack", &["<Ctrl><Shift>H"]);
     app.set_accels_for_action("win.search", &["<Ctrl>F"]);
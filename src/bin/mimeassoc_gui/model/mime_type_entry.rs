@@ -75,8 +75,13 @@ impl MimeTypeEntry {
         let stores = self.stores();
         let borrowed_stores = stores.borrow();
         let desktop_entry_store = borrowed_stores.desktop_entry_store();
+        let mime_associations_store = borrowed_stores.mime_associations_store();
 
-        let mut desktop_entries = desktop_entry_store.find_desktop_entries_for_mimetype(&mime_type);
+        let mut desktop_entries = mime_associations_store
+            .desktop_entries_for(&mime_type, desktop_entry_store)
+            .into_iter()
+            .filter(|desktop_entry| desktop_entry_store.is_launchable(desktop_entry))
+            .collect::<Vec<_>>();
         desktop_entries.sort_by(|a, b| a.cmp_by_name_alpha_inensitive(b));
 
         let application_entries = desktop_entries
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, path::Path};
 
 use mimeassoc::*;
 
@@ -13,6 +13,9 @@ enum HistoryEntry {
         mime_type: MimeType,
         previous_desktop_entry_id: Option<DesktopEntryId>,
     },
+    FixLintProblems {
+        previous_user_scopes: Vec<MimeTypeAssociationScope>,
+    },
     // DiscardUncommittedChanges {
     //     previous_user_scope: MimeAssociationScope,
     // },
@@ -45,6 +48,12 @@ impl Debug for HistoryEntry {
                 .field("mime_type", mime_type)
                 .field("previous_desktop_entry_id", previous_desktop_entry_id)
                 .finish(),
+            Self::FixLintProblems {
+                previous_user_scopes,
+            } => f
+                .debug_struct("FixLintProblems")
+                .field("previous_user_scopes", &previous_user_scopes.len())
+                .finish(),
         }
     }
 }
@@ -53,6 +62,8 @@ pub struct Stores {
     mime_associations_store: MimeTypeAssociationStore,
     desktop_entry_store: DesktopEntryStore,
     mime_info_store: MimeTypeInfoStore,
+    role_store: RoleStore,
+    watcher: Option<StoreWatcher>,
 
     history: Vec<HistoryEntry>,
 }
@@ -65,11 +76,19 @@ impl std::fmt::Debug for Stores {
 }
 
 impl Stores {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(environment: &Environment) -> anyhow::Result<Self> {
         let mut instance = Self {
-            mime_associations_store: MimeTypeAssociationStore::load(&mimeapps_lists_paths()?)?,
-            desktop_entry_store: DesktopEntryStore::load(&desktop_entry_dirs()?)?,
-            mime_info_store: MimeTypeInfoStore::load(&mimeinfo_paths()?)?,
+            mime_associations_store: MimeTypeAssociationStore::load_for_environment(
+                &environment.mimeapps_lists_paths()?,
+                environment,
+            )?,
+            desktop_entry_store: DesktopEntryStore::load_for_desktops(
+                &environment.desktop_entry_dirs(),
+                environment.current_desktops().to_vec(),
+            )?,
+            mime_info_store: MimeTypeInfoStore::load(&environment.mimeinfo_paths()?)?,
+            role_store: RoleStore::load(environment.user_roles_path())?,
+            watcher: None,
             history: vec![],
         };
 
@@ -83,9 +102,44 @@ impl Stores {
             }
         }
 
+        // live reload is a nicety; without it, e.g., if the inotify watch limit is reached, carry on
+        match StoreWatcher::new(
+            environment,
+            &instance.mime_associations_store,
+            &instance.desktop_entry_store,
+            &instance.mime_info_store,
+        ) {
+            Ok(watcher) => instance.watcher = Some(watcher),
+            Err(e) => log::warn!("Stores::new - unable to watch for changes: {}", e),
+        }
+
         Ok(instance)
     }
 
+    /// Reload every store from disk, discarding uncommitted changes and the undo history.
+    /// If loading fails, the stores are left as they were.
+    pub fn reload(&mut self, environment: &Environment) -> anyhow::Result<()> {
+        *self = Self::new(environment)?;
+        Ok(())
+    }
+
+    /// Returns the changes other applications have made to the files the stores were loaded from,
+    /// e.g., by installing a package or running `xdg-mime default`, once they have settled.
+    pub fn poll_for_external_changes(&mut self) -> Option<StoreChanges> {
+        let watcher = self.watcher.as_mut()?;
+        match watcher.poll() {
+            Ok(changes) => changes.filter(|changes| !changes.is_empty()),
+            Err(e) => {
+                log::error!(
+                    "Stores::poll_for_external_changes - no longer watching for changes: {}",
+                    e
+                );
+                self.watcher = None;
+                None
+            }
+        }
+    }
+
     pub fn mime_associations_store(&self) -> &MimeTypeAssociationStore {
         &self.mime_associations_store
     }
@@ -98,6 +152,58 @@ impl Stores {
         &self.mime_info_store
     }
 
+    pub fn role_store(&self) -> &RoleStore {
+        &self.role_store
+    }
+
+    /// Assign an application to every mime type of a role it supports, recording one history
+    /// entry per mime type, as if each had been assigned individually.
+    pub fn set_application_to_role_binding(
+        &mut self,
+        role_id: &str,
+        desktop_entry_id: &DesktopEntryId,
+    ) -> anyhow::Result<()> {
+        let Some(role) = self.role_store.find_role(role_id) else {
+            anyhow::bail!("Unrecognized role id")
+        };
+
+        let Some(desktop_entry) = self
+            .desktop_entry_store
+            .find_desktop_entry_with_id(desktop_entry_id)
+        else {
+            anyhow::bail!("Unrecognized desktop entry id")
+        };
+
+        let previous_assigned_handlers = role
+            .mime_types()
+            .iter()
+            .map(|mime_type| {
+                (
+                    mime_type.clone(),
+                    self.mime_associations_store
+                        .installed_default_application_for(mime_type, &self.desktop_entry_store)
+                        .map(|desktop_entry| desktop_entry.id().clone()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let assigned_mime_types = self
+            .mime_associations_store
+            .set_default_handler_for_role(role, desktop_entry)?;
+
+        for (mime_type, previous_desktop_entry_id) in previous_assigned_handlers {
+            if assigned_mime_types.contains(&mime_type) {
+                self.history.push(HistoryEntry::DesktopEntryAssignment {
+                    mime_type,
+                    previous_desktop_entry_id,
+                    new_desktop_entry_id: desktop_entry_id.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn set_application_to_mimetype_binding(
         &mut self,
         mime_type: &MimeType,
@@ -106,8 +212,8 @@ impl Stores {
         if let Some(desktop_entry_id) = desktop_entry_id {
             let previous_assigned_handler = self
                 .mime_associations_store
-                .default_application_for(mime_type)
-                .cloned();
+                .installed_default_application_for(mime_type, &self.desktop_entry_store)
+                .map(|desktop_entry| desktop_entry.id().clone());
 
             let Some(desktop_entry) = self
                 .desktop_entry_store
@@ -127,8 +233,8 @@ impl Stores {
         } else {
             let previous_assigned_handler = self
                 .mime_associations_store
-                .default_application_for(mime_type)
-                .cloned();
+                .installed_default_application_for(mime_type, &self.desktop_entry_store)
+                .map(|desktop_entry| desktop_entry.id().clone());
 
             self.mime_associations_store
                 .remove_assigned_applications_for(mime_type);
@@ -146,7 +252,7 @@ impl Stores {
         // attempt to reload; if there's an error pop the change, which will re-assign the user scope state
         if let Err(e) = self.mime_associations_store.reload() {
             self.undo()?;
-            Err(e)
+            Err(e.into())
         } else {
             Ok(())
         }
@@ -166,8 +272,39 @@ impl Stores {
         Ok(result.into_iter().collect())
     }
 
+    /// Check each mimeapps.list for problems, e.g., lines which can't be parsed, or applications which aren't installed
+    pub fn lint(&self) -> Vec<LintDiagnostic> {
+        self.mime_associations_store
+            .lint(&self.desktop_entry_store, &self.mime_info_store)
+    }
+
+    /// Apply the suggested fixes of the problems `lint` found in the user's mimeapps.list files, returning how many were fixed.
+    /// The fixes are recorded as one history entry, so they can be undone together.
+    pub fn fix_lint(&mut self, diagnostics: &[LintDiagnostic]) -> usize {
+        let previous_user_scopes = self.mime_associations_store.snapshot_user_scopes();
+        let fixed = self.mime_associations_store.fix_lint(diagnostics).len();
+        if fixed > 0 {
+            self.history.push(HistoryEntry::FixLintProblems {
+                previous_user_scopes,
+            });
+        }
+        fixed
+    }
+
     pub fn save(&mut self) -> anyhow::Result<()> {
-        self.mime_associations_store.save()
+        let written_paths = self
+            .mime_associations_store
+            .dirty_sources()
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect::<Vec<_>>();
+        self.mime_associations_store.save()?;
+
+        // our own writes aren't external changes, but those which arrived before the save still are
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.ignore_changes_to(&written_paths)?;
+        }
+        Ok(())
     }
 
     pub fn can_undo(&self) -> bool {
@@ -211,6 +348,12 @@ impl Stores {
                             .remove_assigned_applications_for(&mime_type);
                     }
                 }
+                HistoryEntry::FixLintProblems {
+                    previous_user_scopes,
+                } => {
+                    self.mime_associations_store
+                        .restore_user_scopes(&previous_user_scopes);
+                }
             }
         }
 
@@ -42,6 +42,22 @@ impl Strings {
         format!("System default handler for {}", mime_type)
     }
 
+    /// Label for an item of the roles menu in the MainWindowPage::Applications view, which makes
+    /// the selected application the handler of every mime type of the role it supports.
+    pub fn assign_role_menu_item(
+        role: &Role,
+        role_handlers: &RoleHandlers,
+        desktop_entry: &DesktopEntry,
+    ) -> String {
+        if role_handlers.owner() != Some(desktop_entry.id()) {
+            format!("Use as {}", role.name())
+        } else if role_handlers.is_partially_owned() {
+            format!("Use as {} (partially assigned)", role.name())
+        } else {
+            format!("Default {}", role.name())
+        }
+    }
+
     pub fn assign_no_application_list_item() -> &'static str {
         "None"
     }
@@ -71,6 +87,78 @@ impl Strings {
         "Clear"
     }
 
+    // Strings for the problems dialog
+
+    pub fn problems_dialog_title() -> &'static str {
+        "Problems"
+    }
+
+    pub fn problems_dialog_body(problems: usize, fixable: usize) -> String {
+        if problems == 0 {
+            "No problems were found in the mimeapps.list files.".to_string()
+        } else if fixable == 0 {
+            format!(
+                "{} problems were found in the system's mimeapps.list files. They aren't yours to fix, but may explain unexpected assignments.",
+                problems
+            )
+        } else {
+            format!(
+                "{} problems were found, {} of them in your mimeapps.list files, which can be fixed.",
+                problems, fixable
+            )
+        }
+    }
+
+    /// Subtitle of a problem in the problems dialog, locating it and describing its fix, if it has one
+    pub fn problem_row_subtitle(diagnostic: &LintDiagnostic) -> String {
+        match &diagnostic.fix {
+            Some(fix) => format!(
+                "{}:{} \u{2014} fix: {}",
+                diagnostic.path.display(),
+                diagnostic.line,
+                fix
+            ),
+            None => format!("{}:{}", diagnostic.path.display(), diagnostic.line),
+        }
+    }
+
+    pub fn problems_dialog_action_fix() -> &'static str {
+        "Fix"
+    }
+
+    /// Subtitle of a problem found in an application's desktop entry, locating it
+    pub fn desktop_entry_problem_row_subtitle(
+        path: &std::path::Path,
+        diagnostic: &DesktopEntryDiagnostic,
+    ) -> String {
+        match diagnostic.line {
+            Some(line) => format!("{}:{}", path.display(), line),
+            None => path.display().to_string(),
+        }
+    }
+
+    // Strings for reloading changes made by other applications
+
+    pub fn reload_external_changes_dialog_title() -> &'static str {
+        "Reload changes made by other applications?"
+    }
+
+    pub fn reload_external_changes_dialog_body() -> &'static str {
+        "Your application assignments or installed applications were changed outside MimeAssoc. Reloading will discard the changes you haven't committed."
+    }
+
+    pub fn reload_external_changes_dialog_action_keep() -> &'static str {
+        "Keep My Changes"
+    }
+
+    pub fn reload_external_changes_dialog_action_proceed() -> &'static str {
+        "Reload"
+    }
+
+    pub fn reloaded_external_changes_toast() -> &'static str {
+        "Reloaded changes made by other applications"
+    }
+
     // Strings for Error dialog
 
     pub fn error_dialog_title() -> &'static str {
@@ -81,6 +169,42 @@ impl Strings {
         format!("{}\n{}", message, error)
     }
 
+    /// Describes library errors the user can act on in plain words; others are shown as they are
+    pub fn error_dialog_error_description(error: &Error) -> String {
+        match error {
+            Error::NoWritableScope | Error::ReadOnlyScope(_) => {
+                "Your mimeapps.list can't be written to, so changes can't be saved.".to_string()
+            }
+            Error::InvalidApplication(id) => format!(
+                "{} doesn't appear to be an installed application which can be launched.",
+                id
+            ),
+            Error::UnsupportedMimeType {
+                desktop_entry,
+                mime_type,
+            } => format!("{} doesn't support {}.", desktop_entry, mime_type),
+            Error::UnsupportedRole {
+                desktop_entry,
+                role,
+            } => format!(
+                "{} doesn't support any of the mime types of the {} role.",
+                desktop_entry, role
+            ),
+            Error::SaveConflict { mime_types, .. } => format!(
+                "Another application changed the assignments of {} since they were loaded. Discard your changes to see theirs, then make yours again.",
+                mime_types
+                    .iter()
+                    .map(MimeType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Error::Parse { path, line, .. } => {
+                format!("{}, line {} is malformed:\n{}", path.display(), line, error)
+            }
+            _ => error.to_string(),
+        }
+    }
+
     pub fn error_dialog_copy_to_clipboard() -> &'static str {
         "Copy to Clipboard"
     }
@@ -113,4 +237,8 @@ impl Strings {
     pub fn cancel() -> &'static str {
         "Cancel"
     }
+
+    pub fn close() -> &'static str {
+        "Close"
+    }
 }
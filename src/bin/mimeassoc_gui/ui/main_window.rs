@@ -25,6 +25,9 @@ mod imp {
         #[template_child]
         pub mode_selector_mime_types: TemplateChild<ToggleButton>,
 
+        #[template_child]
+        pub mode_selector_schemes: TemplateChild<ToggleButton>,
+
         #[template_child]
         pub collections_list: TemplateChild<ListBox>,
 
@@ -58,9 +61,15 @@ mod imp {
         #[template_child]
         pub select_none_button: TemplateChild<Button>,
 
+        #[template_child]
+        pub roles_menu_button: TemplateChild<MenuButton>,
+
         #[template_child]
         pub mime_type_mode_detail_info_label: TemplateChild<Label>,
 
+        #[template_child]
+        pub application_mode_detail_problems_list: TemplateChild<ListBox>,
+
         #[template_child]
         pub toast_overlay: TemplateChild<ToastOverlay>,
 
@@ -160,6 +169,11 @@ impl MainWindow {
                 window.app_controller().set_mode(crate::controllers::Mode::MimeTypeMode);
             }));
 
+        imp.mode_selector_schemes
+            .connect_clicked(clone!(@weak self as window => move |_| {
+                window.app_controller().set_mode(crate::controllers::Mode::SchemeMode);
+            }));
+
         imp.commit_button
             .connect_clicked(clone!(@weak self as window => move |_|{
                 window.app_controller().commit_changes();
@@ -202,6 +216,12 @@ impl MainWindow {
         }));
         self.add_action(&action_show_applications);
 
+        let action_show_schemes = gtk::gio::SimpleAction::new("show-schemes", None);
+        action_show_schemes.connect_activate(clone!(@weak self as window => move |_, _|{
+            window.app_controller().set_mode(crate::controllers::Mode::SchemeMode);
+        }));
+        self.add_action(&action_show_schemes);
+
         let action_reset_user_default_application_assignments =
             gtk::gio::SimpleAction::new("reset-user-default-applications", None);
         action_reset_user_default_application_assignments.connect_activate(
@@ -211,6 +231,15 @@ impl MainWindow {
         );
         self.add_action(&action_reset_user_default_application_assignments);
 
+        let action_assign_role =
+            gtk::gio::SimpleAction::new("assign-role", Some(&String::static_variant_type()));
+        action_assign_role.connect_activate(clone!(@weak self as window => move |_, parameter| {
+            if let Some(role_id) = parameter.and_then(|p| p.get::<String>()) {
+                window.app_controller().applications_mode_controller().on_assign_role(&role_id);
+            }
+        }));
+        self.add_action(&action_assign_role);
+
         let action_clear_orphaned_application_assignments =
             gtk::gio::SimpleAction::new("prune-orphaned-application-assignments", None);
         action_clear_orphaned_application_assignments.connect_activate(
@@ -220,6 +249,12 @@ impl MainWindow {
         );
         self.add_action(&action_clear_orphaned_application_assignments);
 
+        let show_problems_action = gtk::gio::SimpleAction::new("show-problems", None);
+        show_problems_action.connect_activate(clone!(@weak self as window => move |_, _| {
+            window.app_controller().show_problems();
+        }));
+        self.add_action(&show_problems_action);
+
         let about_action = gtk::gio::SimpleAction::new("show-about", None);
         about_action.connect_activate(
             clone!(@weak self as window => move |_, _| { window.app_controller().show_about(); }),
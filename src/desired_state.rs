@@ -0,0 +1,86 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{profile, DesktopEntryId, Error, MimeType, ProfileFormat, SkippedProfileEntry};
+
+/// A declarative description of the user's assignments, e.g., checked in alongside other configuration, which
+/// `MimeTypeAssociationStore::plan` compares against the current assignments to find the changes needed to converge.
+///
+/// `default_applications` and `added_associations` are as in a `Profile`: each mime type maps to application
+/// identifiers, resolved with `lookup_desktop_entry`. `reset` lists mime types, or wildcards such as `image/*`,
+/// whose user assignments should be removed, falling back to the system defaults. If `exclusive` is set, every
+/// user assignment of a mime type not listed in `default_applications` is removed. E.g., in TOML:
+///
+/// ```toml
+/// exclusive = false
+/// reset = ["video/*"]
+///
+/// [default_applications]
+/// "text/html" = ["Firefox", "chromium"]
+///
+/// [added_associations]
+/// "image/png" = ["org.gimp.GIMP.desktop"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DesiredState {
+    #[serde(default)]
+    pub exclusive: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reset: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default_applications: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub added_associations: BTreeMap<String, Vec<String>>,
+}
+
+impl DesiredState {
+    pub fn parse(contents: &str, format: ProfileFormat) -> Result<Self, Error> {
+        profile::parse_in(contents, format)
+    }
+
+    /// Read the desired state at `path`, in the format implied by its extension, or JSON if the extension is unknown
+    pub fn load<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        profile::load_in(path)
+    }
+}
+
+/// A change to the user's assignments planned by `MimeTypeAssociationStore::plan`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum PlannedChange {
+    /// Make `to` the default application of the mime type, replacing `from`
+    SetDefaultApplication {
+        mime_type: MimeType,
+        from: Option<DesktopEntryId>,
+        to: DesktopEntryId,
+    },
+    /// Remove the user's assignment of `from` to the mime type, falling back to the system default
+    ResetDefaultApplication {
+        mime_type: MimeType,
+        from: Option<DesktopEntryId>,
+    },
+    /// Add the application to the mime type's added associations
+    AddAssociation {
+        mime_type: MimeType,
+        desktop_entry: DesktopEntryId,
+    },
+}
+
+/// The changes which converge the user's assignments to a `DesiredState`, and the entries of the desired state
+/// which can't be satisfied on this machine, e.g., because none of their applications are installed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    pub changes: Vec<PlannedChange>,
+    pub unresolved: Vec<SkippedProfileEntry>,
+}
+
+impl Plan {
+    /// True if the assignments already match the desired state, i.e., there are no changes to make and no
+    /// entries which can't be satisfied
+    pub fn is_converged(&self) -> bool {
+        self.changes.is_empty() && self.unresolved.is_empty()
+    }
+}
@@ -7,25 +7,45 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use is_executable::IsExecutable;
-
-use super::MimeType;
+use super::{desktop_entry_exec, Error, ExecCommandLine, KeyFileValue, Locale, MimeType};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize)]
 pub struct DesktopEntryId(String);
 
 impl DesktopEntryId {
-    pub fn parse(desktop_entry: &str) -> anyhow::Result<DesktopEntryId> {
+    pub fn parse(desktop_entry: &str) -> Result<DesktopEntryId, Error> {
         if desktop_entry.ends_with(".desktop") {
             Ok(DesktopEntryId(desktop_entry.to_string()))
         } else {
-            anyhow::bail!(
-                "id: \"{}\" not a valid Gnome .desktop file name",
-                desktop_entry
-            )
+            Err(Error::InvalidDesktopEntryId(desktop_entry.to_string()))
         }
     }
 
+    /// Derive the id of a desktop entry from its path relative to the `applications` dir it was
+    /// found in, per the Desktop Entry spec: path separators become `-`, so `kde4/okular.desktop`
+    /// has the id `kde4-okular.desktop`.
+    pub fn from_relative_path<P>(relative_path: P) -> Result<DesktopEntryId, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let relative_path = relative_path.as_ref();
+        let mut components = vec![];
+        for component in relative_path.components() {
+            // the path must be relative, without `.` or `..`, and valid UTF-8
+            let Some(component) = (match component {
+                std::path::Component::Normal(component) => component.to_str(),
+                _ => None,
+            }) else {
+                return Err(Error::InvalidDesktopEntryId(
+                    relative_path.to_string_lossy().to_string(),
+                ));
+            };
+            components.push(component);
+        }
+
+        Self::parse(&components.join("-"))
+    }
+
     pub fn id(&self) -> &str {
         &self.0
     }
@@ -92,21 +112,40 @@ pub struct DesktopEntry {
     fields: HashMap<String, String>,
     mime_types: Vec<MimeType>,
     id: DesktopEntryId,
+    name: Option<String>,
 }
 
 impl DesktopEntry {
-    pub fn load<P>(path: P) -> anyhow::Result<DesktopEntry>
+    /// Load the desktop entry at `path`, using its file name as its id
+    pub fn load<P>(path: P) -> Result<DesktopEntry, Error>
     where
         P: AsRef<Path>,
     {
         let path = path.as_ref();
-        let file = File::open(path)?;
+        let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+            return Err(Error::InvalidDesktopEntryId(
+                path.to_string_lossy().to_string(),
+            ));
+        };
+        let id = DesktopEntryId::parse(file_name)?;
+
+        Self::load_with_id(path, id)
+    }
+
+    /// Load the desktop entry at `path` with an explicit id, e.g., one derived with
+    /// `DesktopEntryId::from_relative_path` for entries in subdirectories
+    pub fn load_with_id<P>(path: P, id: DesktopEntryId) -> Result<DesktopEntry, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
         let line_buffer = io::BufReader::new(file).lines();
         let mut desktop_entry_section: Option<DesktopEntrySections> = None;
         let mut fields = HashMap::new();
         let mut mime_types: Vec<MimeType> = vec![];
 
-        for line in line_buffer.flatten() {
+        for (line_index, line) in line_buffer.map_while(Result::ok).enumerate() {
             let line = line.trim();
             if line.starts_with('#') || line.is_empty() {
                 continue;
@@ -117,14 +156,17 @@ impl DesktopEntry {
             } else if desktop_entry_section.is_some() {
                 let field_components = line.splitn(2, '=').collect::<Vec<_>>();
                 if field_components.len() != 2 {
-                    anyhow::bail!("Desktop entry field entries should be in form \"Name=value\", line is malformed: \"{}\"", line);
+                    return Err(Error::parse(
+                        path,
+                        line_index + 1,
+                        format!("Desktop entry field entries should be in form \"Name=value\", line is malformed: \"{}\"", line),
+                    ));
                 }
                 let field_name = field_components[0].trim();
                 let field_value = field_components[1].trim();
                 if field_name == "MimeType" {
-                    let mime_type_strings = field_value.split(';').collect::<Vec<_>>();
-                    for mime_type_str in mime_type_strings {
-                        if let Ok(mime_type) = MimeType::parse(mime_type_str) {
+                    for mime_type_str in KeyFileValue::new(field_value).as_string_list() {
+                        if let Ok(mime_type) = MimeType::parse(&mime_type_str) {
                             mime_types.push(mime_type)
                         }
                     }
@@ -135,38 +177,30 @@ impl DesktopEntry {
         }
 
         if fields.is_empty() {
-            anyhow::bail!(
-                "DesktopEntry \"{:?}\" parsed but contained no [Desktop Entry] fields.",
-                path
-            );
+            return Err(Error::NotADesktopEntry(path.to_path_buf()));
         }
 
-        let id = if let Some(file_name) = path.file_name() {
-            if let Some(file_name) = file_name.to_str() {
-                DesktopEntryId::parse(file_name)?
-            } else {
-                anyhow::bail!(
-                    "Unable to extract DesktopEntryId from desktop entry \"{:?}\"",
-                    path
-                )
-            }
-        } else {
-            anyhow::bail!(
-                "Unable to extract DesktopEntryId from desktop entry \"{:?}\"",
-                path
-            )
-        };
-
-        Ok(Self {
+        let mut desktop_entry = Self {
             path: PathBuf::from(path),
             fields,
             mime_types,
             id,
-        })
+            name: None,
+        };
+        desktop_entry.name = desktop_entry.locale_string("Name", Locale::current().as_ref());
+
+        Ok(desktop_entry)
     }
 
+    /// The application's name in the user's language, per `$LC_ALL`, `$LC_MESSAGES` and `$LANG`,
+    /// falling back to the untranslated `Name`
     pub fn name(&self) -> Option<&str> {
-        self.fields.get("Name").map(|v| v.as_str())
+        self.name.as_deref()
+    }
+
+    /// The untranslated `Name`
+    pub fn untranslated_name(&self) -> Option<String> {
+        self.string("Name")
     }
 
     pub fn cmp_by_name_alpha_inensitive(&self, other: &DesktopEntry) -> core::cmp::Ordering {
@@ -179,9 +213,63 @@ impl DesktopEntry {
         &self.id
     }
 
-    pub fn localised_name(&self, locale: &str) -> Option<&str> {
-        let field_name = format!("Name[{}]", locale);
-        self.fields.get(&field_name).map(|v| v.as_str())
+    /// The `.desktop` file the entry was loaded from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The name for `locale`, e.g., "de_DE.UTF-8", following the spec's locale fallback rules
+    pub fn localised_name(&self, locale: &str) -> Option<String> {
+        self.locale_string("Name", Locale::parse(locale).as_ref())
+    }
+
+    /// The raw, undecoded value of `key` in the [Desktop Entry] group
+    pub fn value(&self, key: &str) -> Option<KeyFileValue<'_>> {
+        self.fields.get(key).map(|v| KeyFileValue::new(v))
+    }
+
+    /// The value of a `string` key, with escapes decoded
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.value(key).map(|v| v.as_string())
+    }
+
+    /// The value of a `localestring` key for `locale`: the first of the locale's fallbacks, e.g.,
+    /// `Name[de_DE]` then `Name[de]`, which is present, otherwise the unlocalized key.
+    pub fn locale_string(&self, key: &str, locale: Option<&Locale>) -> Option<String> {
+        self.localized_value(key, locale).map(|v| v.as_string())
+    }
+
+    pub fn boolean(&self, key: &str) -> Option<bool> {
+        self.value(key).and_then(|v| v.as_boolean())
+    }
+
+    pub fn numeric(&self, key: &str) -> Option<f64> {
+        self.value(key).and_then(|v| v.as_numeric())
+    }
+
+    /// The value of a `string(s)` key, split on unescaped `;`
+    pub fn string_list(&self, key: &str) -> Vec<String> {
+        self.value(key)
+            .map(|v| v.as_string_list())
+            .unwrap_or_default()
+    }
+
+    /// The value of a `localestring(s)` key, e.g., `Keywords`, for `locale`
+    pub fn locale_string_list(&self, key: &str, locale: Option<&Locale>) -> Vec<String> {
+        self.localized_value(key, locale)
+            .map(|v| v.as_string_list())
+            .unwrap_or_default()
+    }
+
+    fn localized_value(&self, key: &str, locale: Option<&Locale>) -> Option<KeyFileValue<'_>> {
+        if let Some(locale) = locale {
+            for suffix in locale.fallbacks() {
+                if let Some(value) = self.value(&format!("{}[{}]", key, suffix)) {
+                    return Some(value);
+                }
+            }
+        }
+        self.value(key)
     }
 
     pub fn mime_types(&self) -> &Vec<MimeType> {
@@ -197,6 +285,17 @@ impl DesktopEntry {
         false
     }
 
+    /// True if the application can open `mime_type`, or, for a wildcard such as `image/*`, any mime type it matches
+    pub fn can_open_mime_type_matching(&self, mime_type: &MimeType) -> bool {
+        if mime_type.is_minor_type_wildcard() {
+            self.mime_types
+                .iter()
+                .any(|mt| mime_type.wildcard_match(mt))
+        } else {
+            self.can_open_mime_type(mime_type)
+        }
+    }
+
     pub fn icon(&self) -> Option<&str> {
         self.fields.get("Icon").map(|v| v.as_str())
     }
@@ -205,36 +304,43 @@ impl DesktopEntry {
         self.fields.get("Exec").map(|v| v.as_str())
     }
 
-    /// Return the full path to the executable launched by executable_command(), or
-    /// an error if the executable is missing, or exists, but is not executable.
-    pub fn executable(&self) -> anyhow::Result<PathBuf> {
-        if let Some(executable) = self.executable_command() {
-            let executable = if let Some(first_space_idx) = executable.find(' ') {
-                &executable[0..first_space_idx]
-            } else {
-                executable
-            };
+    /// Expand the `Exec` command line to open `files_or_uris`, which may be paths or URIs, following
+    /// the spec's quoting and field code rules. Command lines taking a single file (`%f` or `%u`) produce
+    /// one invocation per file; otherwise, a single invocation receives them all.
+    pub fn command_line<S>(&self, files_or_uris: &[S]) -> Result<Vec<ExecCommandLine>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let Some(exec) = self.string("Exec") else {
+            return Err(Error::MissingExec(self.id.clone()));
+        };
 
-            let executable_path = if executable.contains('/') {
-                PathBuf::from(executable)
-            } else {
-                which::which(executable)?
-            };
+        let icon = self.string("Icon");
+        let context = desktop_entry_exec::ExecContext {
+            icon: icon.as_deref(),
+            name: self.name(),
+            desktop_file: &self.path,
+        };
+        desktop_entry_exec::expand(&exec, files_or_uris, &context)
+    }
 
-            if !executable_path.exists() {
-                anyhow::bail!("Executable at \"{:?}\" is missing", executable_path);
+    /// Return the full path to the executable launched by executable_command(), or
+    /// an error if the executable is missing, or exists, but is not executable.
+    /// Quoted paths are supported, and an `env VAR=value ...` prefix is skipped.
+    pub fn executable(&self) -> Result<PathBuf, Error> {
+        if let Some(exec) = self.string("Exec") {
+            let args = desktop_entry_exec::tokenize(&exec)?;
+            let executable = desktop_entry_exec::program(&args);
+            if executable.is_empty() {
+                return Err(Error::InvalidExec {
+                    exec,
+                    message: "has no program".to_string(),
+                });
             }
 
-            if !executable_path.is_executable() {
-                anyhow::bail!("Executable at \"{:?}\" is not executable", executable_path);
-            } else {
-                Ok(executable_path)
-            }
+            desktop_entry_exec::resolve_program(executable)
         } else {
-            anyhow::bail!(
-                "No executable command specified for desktop entry at \"{:?}\"",
-                self.path
-            );
+            Err(Error::MissingExec(self.id.clone()))
         }
     }
 
@@ -242,6 +348,70 @@ impl DesktopEntry {
         self.fields.get("Type").map(|t| DesktopEntryType::parse(t))
     }
 
+    /// True if `Hidden=true`, meaning the entry should be treated as deleted. A user-level copy
+    /// of a system entry with this set removes the application for that user.
+    pub fn is_hidden(&self) -> bool {
+        self.boolean("Hidden").unwrap_or(false)
+    }
+
+    /// True if `NoDisplay=true`, meaning the application exists, e.g., to handle mime types,
+    /// but shouldn't be shown in menus.
+    pub fn is_no_display(&self) -> bool {
+        self.boolean("NoDisplay").unwrap_or(false)
+    }
+
+    /// The desktops listed in `OnlyShowIn`; if non-empty, the entry is shown in no others
+    pub fn only_show_in(&self) -> Vec<String> {
+        self.string_list("OnlyShowIn")
+    }
+
+    /// The desktops listed in `NotShowIn`, in which the entry should not be shown
+    pub fn not_show_in(&self) -> Vec<String> {
+        self.string_list("NotShowIn")
+    }
+
+    pub fn try_exec(&self) -> Option<String> {
+        self.string("TryExec")
+    }
+
+    /// Returns true if the entry should be shown under the given desktops, e.g., as returned by
+    /// `xdg_current_desktops()`. As with GIO, desktops are checked in order, and the first listed in
+    /// either `OnlyShowIn` or `NotShowIn` decides; otherwise, the entry is shown unless it has
+    /// an `OnlyShowIn` list.
+    pub fn is_shown_in(&self, desktops: &[String]) -> bool {
+        let only_show_in = self.only_show_in();
+        let not_show_in = self.not_show_in();
+        for desktop in desktops.iter() {
+            if only_show_in.contains(desktop) {
+                return true;
+            }
+            if not_show_in.contains(desktop) {
+                return false;
+            }
+        }
+
+        only_show_in.is_empty()
+    }
+
+    /// Returns false if `TryExec` names a program which is missing or not executable, meaning the
+    /// application isn't actually installed. Entries without `TryExec` always succeed.
+    pub fn try_exec_succeeds(&self) -> bool {
+        let Some(try_exec) = self.try_exec() else {
+            return true;
+        };
+
+        desktop_entry_exec::resolve_program(&try_exec).is_ok()
+    }
+
+    /// Returns true if the desktop would show this entry in its menus under the given desktops:
+    /// it isn't `Hidden` or `NoDisplay`, is shown in one of `desktops`, and its `TryExec` succeeds.
+    pub fn should_show(&self, desktops: &[String]) -> bool {
+        !self.is_hidden()
+            && !self.is_no_display()
+            && self.is_shown_in(desktops)
+            && self.try_exec_succeeds()
+    }
+
     /// Returns true if this appears to be a valid desktop entry,
     /// e.g., has Name/Type/Exec/Icon fields, and the exec refers
     /// to some kind of executable
@@ -280,6 +450,185 @@ mod tests {
         assert!(DesktopEntryId::parse("Baz").is_err());
     }
 
+    #[test]
+    fn derives_desktop_entry_id_from_relative_path() -> anyhow::Result<()> {
+        assert_eq!(
+            DesktopEntryId::from_relative_path("okular.desktop")?.id(),
+            "okular.desktop"
+        );
+        assert_eq!(
+            DesktopEntryId::from_relative_path("kde4/okular.desktop")?.id(),
+            "kde4-okular.desktop"
+        );
+        assert_eq!(
+            DesktopEntryId::from_relative_path("a/b/c.desktop")?.id(),
+            "a-b-c.desktop"
+        );
+        assert!(DesktopEntryId::from_relative_path("/kde4/okular.desktop").is_err());
+        assert!(DesktopEntryId::from_relative_path("../okular.desktop").is_err());
+        assert!(DesktopEntryId::from_relative_path("kde4/okular").is_err());
+
+        Ok(())
+    }
+
+    /// Write `contents` to a scratch desktop entry named `name` and load it
+    fn load_entry(name: &str, contents: &str) -> anyhow::Result<DesktopEntry> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-desktop-entry-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.desktop", name));
+        std::fs::write(&path, contents)?;
+        let desktop_entry = DesktopEntry::load(&path);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(desktop_entry?)
+    }
+
+    #[test]
+    fn parses_visibility_keys() -> anyhow::Result<()> {
+        let entry = load_entry(
+            "hidden",
+            "[Desktop Entry]\nType=Application\nName=Test\nExec=test\nHidden=true\nNoDisplay=false\n\
+             OnlyShowIn=GNOME;KDE;\nNotShowIn=XFCE\nTryExec=/nonexistent/test\n",
+        )?;
+        assert!(entry.is_hidden());
+        assert!(!entry.is_no_display());
+        assert_eq!(entry.only_show_in(), ["GNOME", "KDE"]);
+        assert_eq!(entry.not_show_in(), ["XFCE"]);
+        assert_eq!(entry.try_exec().as_deref(), Some("/nonexistent/test"));
+        assert!(!entry.try_exec_succeeds());
+        assert!(!entry.should_show(&["GNOME".to_string()]));
+
+        let entry = load_entry(
+            "plain",
+            "[Desktop Entry]\nType=Application\nName=Test\nExec=test\n",
+        )?;
+        assert!(!entry.is_hidden());
+        assert!(!entry.is_no_display());
+        assert!(entry.only_show_in().is_empty());
+        assert!(entry.try_exec_succeeds());
+        assert!(entry.should_show(&[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn decodes_typed_values() -> anyhow::Result<()> {
+        let entry = load_entry(
+            "typed",
+            "[Desktop Entry]\nType=Application\nName=Two\\sWords\nExec=test\nVersion=1.5\n\
+             Terminal=false\nMimeType=image/png;text/x-odd\\;type;image/jpeg\n\
+             Keywords=one;two;\nKeywords[de]=eins;zwei;\n",
+        )?;
+        assert_eq!(entry.untranslated_name().as_deref(), Some("Two Words"));
+        assert_eq!(entry.value("Name").unwrap().raw(), "Two\\sWords");
+        assert_eq!(entry.numeric("Version"), Some(1.5));
+        assert_eq!(entry.boolean("Terminal"), Some(false));
+        assert_eq!(entry.boolean("Missing"), None);
+
+        // the escaped semicolon is part of an element rather than a separator
+        assert_eq!(
+            entry.mime_types(),
+            &vec![
+                MimeType::parse("image/png")?,
+                MimeType::parse("text/x-odd;type")?,
+                MimeType::parse("image/jpeg")?
+            ]
+        );
+
+        assert_eq!(entry.locale_string_list("Keywords", None), ["one", "two"]);
+        assert_eq!(
+            entry.locale_string_list("Keywords", Locale::parse("de_AT").as_ref()),
+            ["eins", "zwei"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn localized_names_follow_locale_fallbacks() -> anyhow::Result<()> {
+        let entry = load_entry(
+            "localized",
+            "[Desktop Entry]\nType=Application\nExec=test\nName=Viewer\nName[de]=Betrachter\n\
+             Name[sr_YU]=Pregledač\nName[sr@Latn]=Pregledac Latn\nName[sr_YU@Latn]=Pregledac YU Latn\n",
+        )?;
+
+        assert_eq!(entry.localised_name("de").as_deref(), Some("Betrachter"));
+        assert_eq!(
+            entry.localised_name("de_CH.UTF-8").as_deref(),
+            Some("Betrachter")
+        );
+        assert_eq!(
+            entry.localised_name("sr_YU.UTF-8@Latn").as_deref(),
+            Some("Pregledac YU Latn")
+        );
+        assert_eq!(
+            entry.localised_name("sr_BA@Latn").as_deref(),
+            Some("Pregledac Latn")
+        );
+        assert_eq!(entry.localised_name("sr_YU").as_deref(), Some("Pregledač"));
+        assert_eq!(entry.localised_name("fr_FR").as_deref(), Some("Viewer"));
+        assert_eq!(entry.localised_name("C").as_deref(), Some("Viewer"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn builds_command_lines_from_exec() -> anyhow::Result<()> {
+        let entry = load_entry(
+            "exec",
+            "[Desktop Entry]\nType=Application\nName=Viewer\nIcon=viewer\n\
+             Exec=\"/opt/My\\sViewer/viewer\" --icon-from %i %F\n",
+        )?;
+        let command_lines = entry.command_line(&["/tmp/a.png", "/tmp/b.png"])?;
+        assert_eq!(command_lines.len(), 1);
+        assert_eq!(command_lines[0].program(), "/opt/My Viewer/viewer");
+        assert_eq!(
+            command_lines[0].args(),
+            [
+                "--icon-from",
+                "--icon",
+                "viewer",
+                "/tmp/a.png",
+                "/tmp/b.png"
+            ]
+        );
+
+        let entry = load_entry(
+            "exec_env",
+            "[Desktop Entry]\nType=Application\nName=Env\nExec=env FOO=1 BAR=2 sh -c true\n",
+        )?;
+        assert_eq!(entry.executable()?, which::which("sh")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn applies_only_show_in_and_not_show_in() -> anyhow::Result<()> {
+        let desktops = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let only_kde = load_entry(
+            "only_kde",
+            "[Desktop Entry]\nType=Application\nName=Test\nExec=test\nOnlyShowIn=KDE;\n",
+        )?;
+        assert!(only_kde.is_shown_in(&desktops(&["KDE"])));
+        assert!(only_kde.is_shown_in(&desktops(&["ubuntu", "KDE"])));
+        assert!(!only_kde.is_shown_in(&desktops(&["GNOME"])));
+        assert!(!only_kde.is_shown_in(&[]));
+
+        let not_gnome = load_entry(
+            "not_gnome",
+            "[Desktop Entry]\nType=Application\nName=Test\nExec=test\nNotShowIn=GNOME;\n",
+        )?;
+        assert!(not_gnome.is_shown_in(&desktops(&["KDE"])));
+        assert!(not_gnome.is_shown_in(&[]));
+        assert!(!not_gnome.is_shown_in(&desktops(&["ubuntu", "GNOME"])));
+
+        Ok(())
+    }
+
     #[test]
     fn parses_desktop_entry_id() -> anyhow::Result<()> {
         assert_eq!(
@@ -0,0 +1,484 @@
+use is_executable::IsExecutable;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// A single invocation of a desktop entry's `Exec` command line, with field codes expanded
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ExecCommandLine {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ExecCommandLine {
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// The program followed by its arguments, e.g., for display
+    pub fn argv(&self) -> Vec<&str> {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Build a `std::process::Command` which runs this command line
+    pub fn command(&self) -> std::process::Command {
+        let mut command = std::process::Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+}
+
+/// An argument of an `Exec` command line. Field codes are only expanded in unquoted arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ExecArg {
+    Unquoted(String),
+    Quoted(String),
+}
+
+impl ExecArg {
+    pub(crate) fn text(&self) -> &str {
+        match self {
+            Self::Unquoted(text) => text,
+            Self::Quoted(text) => text,
+        }
+    }
+}
+
+/// The values substituted for the field codes which don't refer to files
+pub(crate) struct ExecContext<'a> {
+    pub icon: Option<&'a str>,
+    pub name: Option<&'a str>,
+    pub desktop_file: &'a Path,
+}
+
+/// Split an `Exec` value, already decoded as a key-file string, into arguments per the Desktop Entry
+/// spec's quoting rules: arguments are separated by spaces, and may be enclosed in double quotes, within
+/// which `"`, `` ` ``, `$` and `\` are escaped with a backslash.
+pub(crate) fn tokenize(exec: &str) -> Result<Vec<ExecArg>, Error> {
+    let mut args = vec![];
+    let mut chars = exec.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut text = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+            match c {
+                '"' => {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(c @ ('"' | '`' | '$' | '\\')) => text.push(c),
+                                Some(c) => {
+                                    text.push('\\');
+                                    text.push(c);
+                                }
+                                None => text.push('\\'),
+                            },
+                            Some(c) => text.push(c),
+                            None => {
+                                return Err(Error::InvalidExec {
+                                    exec: exec.to_string(),
+                                    message: "has an unterminated quoted argument".to_string(),
+                                })
+                            }
+                        }
+                    }
+                }
+                '\\' => {
+                    if let Some(c) = chars.next() {
+                        text.push(c);
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+
+        args.push(if quoted {
+            ExecArg::Quoted(text)
+        } else {
+            ExecArg::Unquoted(text)
+        });
+    }
+
+    Ok(args)
+}
+
+/// The program an `Exec` command line runs: its first argument, or, after an `env VAR=value ...` prefix, the
+/// program `env` runs. Empty if the command line has no arguments.
+pub(crate) fn program(args: &[ExecArg]) -> &str {
+    let mut args = args.iter().map(|arg| arg.text());
+    let program = args.next().unwrap_or_default();
+    if program == "env" || program.ends_with("/env") {
+        args.find(|arg| !arg.contains('=') && !arg.starts_with('-'))
+            .unwrap_or(program)
+    } else {
+        program
+    }
+}
+
+/// The dirs searched for programs beneath a root, as the image's `$PATH` is unknown
+const DEFAULT_PATH_DIRS: &[&str] = &[
+    "/usr/local/sbin",
+    "/usr/local/bin",
+    "/usr/sbin",
+    "/usr/bin",
+    "/sbin",
+    "/bin",
+];
+
+/// Resolve `program` to the executable it names, searching `$PATH` unless it contains a `/`
+pub(crate) fn resolve_program(program: &str) -> Result<PathBuf, Error> {
+    resolve_program_beneath(program, None)
+}
+
+/// Resolve `program` as `resolve_program` does, but, given a `root`, within the container image or chroot mounted
+/// there: an absolute path is moved beneath `root`, and a bare name is searched for in the usual bin dirs beneath it
+pub(crate) fn resolve_program_beneath(
+    program: &str,
+    root: Option<&Path>,
+) -> Result<PathBuf, Error> {
+    let not_found = || Error::ExecutableNotFound(PathBuf::from(program));
+    let path = match root {
+        None if program.contains('/') => PathBuf::from(program),
+        None => which::which(program).map_err(|_| not_found())?,
+        Some(root) if program.contains('/') => root.join(program.trim_start_matches('/')),
+        Some(root) => DEFAULT_PATH_DIRS
+            .iter()
+            .map(|dir| root.join(dir.trim_start_matches('/')).join(program))
+            .find(|path| path.is_file())
+            .ok_or_else(not_found)?,
+    };
+
+    if !path.exists() {
+        Err(Error::ExecutableNotFound(path))
+    } else if !path.is_executable() {
+        Err(Error::NotExecutable(path))
+    } else {
+        Ok(path)
+    }
+}
+
+/// Expand the field codes of an `Exec` value for `files_or_uris`. Command lines with `%f` or `%u` are
+/// expanded into one invocation per file; otherwise, all the files go to a single invocation.
+pub(crate) fn expand<S>(
+    exec: &str,
+    files_or_uris: &[S],
+    context: &ExecContext,
+) -> Result<Vec<ExecCommandLine>, Error>
+where
+    S: AsRef<str>,
+{
+    let args = tokenize(exec)?;
+    if args.is_empty() {
+        return Err(Error::InvalidExec {
+            exec: exec.to_string(),
+            message: "is empty".to_string(),
+        });
+    }
+
+    // `%%` is a literal `%`, so `%%f` isn't a field code
+    let codes = args
+        .iter()
+        .filter_map(|arg| match arg {
+            ExecArg::Unquoted(text) => Some(field_codes(text)),
+            ExecArg::Quoted(_) => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    let uses_single_file_code = codes.iter().any(|code| matches!(code, 'f' | 'u'));
+    let takes_local_files = codes.iter().any(|code| matches!(code, 'f' | 'F'));
+
+    // applications taking %f or %F can only open local files
+    let inputs = files_or_uris
+        .iter()
+        .map(AsRef::as_ref)
+        .filter_map(|input| {
+            if !takes_local_files {
+                Some(input.to_string())
+            } else if let Some(path) = local_path(input) {
+                Some(path)
+            } else {
+                log::warn!(
+                    "Exec command line \"{}\" only accepts local files, skipping \"{}\"",
+                    exec,
+                    input
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if inputs.is_empty() && !files_or_uris.is_empty() {
+        return Err(Error::UnsupportedUris {
+            exec: exec.to_string(),
+            uris: files_or_uris
+                .iter()
+                .map(|input| input.as_ref().to_string())
+                .collect(),
+        });
+    }
+
+    let invocations: Vec<(Option<&str>, &[String])> = if uses_single_file_code && !inputs.is_empty()
+    {
+        inputs
+            .iter()
+            .map(|input| (Some(input.as_str()), &inputs[..]))
+            .collect()
+    } else {
+        vec![(None, &inputs[..])]
+    };
+
+    let mut command_lines = vec![];
+    for (file, files) in invocations {
+        let mut argv: Vec<String> = vec![];
+        for arg in args.iter() {
+            match arg {
+                ExecArg::Quoted(text) => argv.push(text.clone()),
+                ExecArg::Unquoted(text) => match text.as_str() {
+                    "%F" | "%U" => argv.extend(files.iter().cloned()),
+                    "%f" | "%u" => argv.extend(file.map(str::to_string)),
+                    // deprecated codes are removed
+                    "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+                    "%i" => {
+                        if let Some(icon) = context.icon {
+                            argv.push("--icon".to_string());
+                            argv.push(icon.to_string());
+                        }
+                    }
+                    _ => argv.push(expand_inline(text, file, context)),
+                },
+            }
+        }
+
+        if argv.is_empty() || argv[0].is_empty() {
+            return Err(Error::InvalidExec {
+                exec: exec.to_string(),
+                message: "has no program".to_string(),
+            });
+        }
+        let program = argv.remove(0);
+        command_lines.push(ExecCommandLine {
+            program,
+            args: argv,
+        });
+    }
+
+    Ok(command_lines)
+}
+
+/// Expand field codes embedded within an argument, e.g., `--file=%f`. Codes which may only appear as
+/// standalone arguments, along with deprecated and unknown codes, expand to nothing.
+fn expand_inline(text: &str, file: Option<&str>, context: &ExecContext) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => result.push('%'),
+            Some('f') | Some('u') => result.push_str(file.unwrap_or_default()),
+            Some('c') => result.push_str(context.name.unwrap_or_default()),
+            Some('k') => result.push_str(&context.desktop_file.to_string_lossy()),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// The characters following each `%` of `text`, with `%%` yielding `%`; a trailing `%` yields a space, so it's
+/// reported as an unknown field code
+pub(crate) fn field_codes(text: &str) -> Vec<char> {
+    let mut codes = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            codes.push(chars.next().unwrap_or(' '));
+        }
+    }
+    codes
+}
+
+/// Returns the local path for `input`, which may be a path or a `file://` URI. Returns None for other URIs.
+pub(crate) fn local_path(input: &str) -> Option<String> {
+    if let Some(rest) = input.strip_prefix("file://") {
+        // skip the authority, e.g., "localhost", if any
+        let path = &rest[rest.find('/')?..];
+        return percent_decode(path);
+    }
+
+    if has_uri_scheme(input) {
+        None
+    } else {
+        Some(input.to_string())
+    }
+}
+
+/// True if `input` begins with an RFC 3986 scheme followed by `:`, e.g., `https:` or `mailto:`
+pub(crate) fn has_uri_scheme(input: &str) -> bool {
+    let Some((scheme, _)) = input.split_once(':') else {
+        return false;
+    };
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> ExecContext<'static> {
+        ExecContext {
+            icon: Some("viewer-icon"),
+            name: Some("Viewer"),
+            desktop_file: Path::new("/usr/share/applications/viewer.desktop"),
+        }
+    }
+
+    fn argvs(exec: &str, files: &[&str]) -> Vec<Vec<String>> {
+        expand(exec, files, &context())
+            .unwrap()
+            .iter()
+            .map(|command_line| {
+                command_line
+                    .argv()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_quoted_arguments() -> anyhow::Result<()> {
+        let args = tokenize(r#""/opt/My App/bin/app" --title "say \"hi\" \$HOME \\ \`x\`" %U"#)?;
+        assert_eq!(
+            args,
+            [
+                ExecArg::Quoted("/opt/My App/bin/app".to_string()),
+                ExecArg::Unquoted("--title".to_string()),
+                ExecArg::Quoted(r#"say "hi" $HOME \ `x`"#.to_string()),
+                ExecArg::Unquoted("%U".to_string()),
+            ]
+        );
+
+        assert!(tokenize(r#"app "unterminated"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn expands_multiple_file_codes_into_one_invocation() {
+        assert_eq!(
+            argvs("viewer %F", &["/a.png", "file:///b%20c.png"]),
+            [["viewer", "/a.png", "/b c.png"]]
+        );
+        assert_eq!(
+            argvs("viewer --new %U", &["/a.png", "https://example.com/"]),
+            [["viewer", "--new", "/a.png", "https://example.com/"]]
+        );
+        assert_eq!(argvs("viewer %F", &[]), [["viewer"]]);
+    }
+
+    #[test]
+    fn expands_single_file_codes_into_one_invocation_per_file() {
+        assert_eq!(
+            argvs("viewer --file=%f", &["/a.png", "/b.png"]),
+            [["viewer", "--file=/a.png"], ["viewer", "--file=/b.png"]]
+        );
+        assert_eq!(
+            argvs("viewer %u", &["https://example.com/"]),
+            [["viewer", "https://example.com/"]]
+        );
+        assert_eq!(argvs("viewer %f", &[]), [["viewer"]]);
+    }
+
+    #[test]
+    fn escaped_percents_are_not_field_codes() {
+        // `%%f` is a literal "%f", so the files go to %F in one invocation
+        assert_eq!(
+            argvs("viewer --format=%%f %F", &["/a.png", "/b.png"]),
+            [["viewer", "--format=%f", "/a.png", "/b.png"]]
+        );
+        // `%%F` doesn't restrict the application to local files
+        assert_eq!(
+            argvs("viewer --format=%%F %U", &["https://example.com/"]),
+            [["viewer", "--format=%F", "https://example.com/"]]
+        );
+    }
+
+    #[test]
+    fn expands_other_field_codes() {
+        assert_eq!(
+            argvs(
+                "env FOO=1 viewer %i --name %c --from %k 100%% \"%f\" %d",
+                &[]
+            ),
+            [[
+                "env",
+                "FOO=1",
+                "viewer",
+                "--icon",
+                "viewer-icon",
+                "--name",
+                "Viewer",
+                "--from",
+                "/usr/share/applications/viewer.desktop",
+                "100%",
+                "%f"
+            ]]
+        );
+    }
+
+    #[test]
+    fn local_file_codes_skip_remote_uris() {
+        assert_eq!(
+            argvs("viewer %F", &["https://example.com/a.png", "/b.png"]),
+            [["viewer", "/b.png"]]
+        );
+        assert!(expand("viewer %f", &["https://example.com/a.png"], &context()).is_err());
+    }
+
+    #[test]
+    fn detects_uri_schemes() {
+        assert!(has_uri_scheme("https://example.com"));
+        assert!(has_uri_scheme("mailto:someone@example.com"));
+        assert!(has_uri_scheme("x-scheme+1.a:foo"));
+        assert!(!has_uri_scheme("/home/user/a:b.txt"));
+        assert!(!has_uri_scheme("relative/file.txt"));
+        assert!(!has_uri_scheme("1abc:foo"));
+    }
+}
@@ -4,8 +4,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use super::{has_extension, mime_type::MimeType};
-use super::{DesktopEntry, DesktopEntryId, DesktopEntryType};
+use super::{has_extension, mime_type::MimeType, visit_dir_once, xdg_current_desktops};
+use super::{DesktopEntry, DesktopEntryId, DesktopEntryType, Error};
 
 /// Represents all the desktop entries in a particular scope, or specifically,
 /// a location on the filesystem such as /usr/share/applications
@@ -15,7 +15,7 @@ struct DesktopEntryScope {
 }
 
 impl DesktopEntryScope {
-    fn load<P>(dir: P) -> anyhow::Result<Self>
+    fn load<P>(dir: P) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
@@ -23,12 +23,69 @@ impl DesktopEntryScope {
         log::info!("DesktopEntryScope::load {:?}", directory);
 
         let mut application_entries = HashMap::new();
-        let contents = std::fs::read_dir(directory)?;
-        for file in contents.flatten() {
-            let file_path = file.path();
-            if has_extension(&file_path, "desktop") {
-                if let Ok(desktop_entry) = DesktopEntry::load(&file_path) {
-                    if let Some(DesktopEntryType::Application) = desktop_entry.entry_type() {
+        Self::load_dir(
+            directory,
+            directory,
+            &mut application_entries,
+            &mut HashSet::new(),
+        )?;
+
+        Ok(Self {
+            directory: PathBuf::from(&directory),
+            application_entries,
+        })
+    }
+
+    /// Load the desktop entries in `dir` and, recursively, its subdirectories. Ids are derived from each
+    /// entry's path relative to `root`, so `root/kde4/okular.desktop` becomes `kde4-okular.desktop`.
+    /// Entries in a directory are loaded before those of its subdirectories, and the first entry
+    /// found for an id shadows any later ones.
+    fn load_dir(
+        root: &Path,
+        dir: &Path,
+        application_entries: &mut HashMap<DesktopEntryId, DesktopEntry>,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<(), Error> {
+        if !visit_dir_once(dir, visited) {
+            log::warn!(
+                "DesktopEntryScope::load - skipping {:?}, which was already loaded through a symlink",
+                dir
+            );
+            return Ok(());
+        }
+
+        let mut paths = std::fs::read_dir(dir)
+            .map_err(|e| Error::io(dir, e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut subdirectories = vec![];
+        for file_path in paths {
+            if file_path.is_dir() {
+                subdirectories.push(file_path);
+            } else if has_extension(&file_path, "desktop") {
+                let Ok(relative_path) = file_path.strip_prefix(root) else {
+                    continue;
+                };
+                let Ok(id) = DesktopEntryId::from_relative_path(relative_path) else {
+                    continue;
+                };
+                if application_entries.contains_key(&id) {
+                    log::warn!(
+                        "DesktopEntryScope::load - {:?} is shadowed by an earlier entry with id \"{}\"",
+                        file_path,
+                        id
+                    );
+                    continue;
+                }
+                if let Ok(desktop_entry) = DesktopEntry::load_with_id(&file_path, id) {
+                    // a Hidden entry deletes the entries it overrides, so it's kept whatever its Type, e.g., an
+                    // override containing only `Hidden=true`
+                    if desktop_entry.is_hidden()
+                        || desktop_entry.entry_type() == Some(DesktopEntryType::Application)
+                    {
                         application_entries
                             .insert(desktop_entry.id().clone(), desktop_entry.clone());
                     }
@@ -36,25 +93,87 @@ impl DesktopEntryScope {
             }
         }
 
-        Ok(Self {
-            directory: PathBuf::from(&directory),
-            application_entries,
-        })
+        for subdirectory in subdirectories {
+            // an unreadable subdirectory shouldn't hide the rest of the scope
+            if let Err(e) = Self::load_dir(root, &subdirectory, application_entries, visited) {
+                log::warn!(
+                    "DesktopEntryScope::load - unable to read {:?}: {}",
+                    subdirectory,
+                    e
+                );
+            }
+        }
+
+        Ok(())
     }
 
     fn application_entry(&self, id: &DesktopEntryId) -> Option<&DesktopEntry> {
         self.application_entries.get(id)
     }
+
+    /// Find the `.desktop` file in `dir` or its subdirectories whose id is `id`, in the order `load_dir` visits them
+    fn find_file(
+        root: &Path,
+        dir: &Path,
+        id: &DesktopEntryId,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Option<PathBuf> {
+        if !visit_dir_once(dir, visited) {
+            return None;
+        }
+
+        let mut paths = std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let (subdirectories, files): (Vec<_>, Vec<_>) =
+            paths.into_iter().partition(|path| path.is_dir());
+        files
+            .into_iter()
+            .find(|path| {
+                has_extension(path, "desktop")
+                    && path
+                        .strip_prefix(root)
+                        .ok()
+                        .and_then(|relative_path| {
+                            DesktopEntryId::from_relative_path(relative_path).ok()
+                        })
+                        .as_ref()
+                        == Some(id)
+            })
+            .or_else(|| {
+                subdirectories
+                    .iter()
+                    .find_map(|subdirectory| Self::find_file(root, subdirectory, id, visited))
+            })
+    }
 }
 
 pub struct DesktopEntryStore {
     scopes: Vec<DesktopEntryScope>,
+    current_desktops: Vec<String>,
 }
 
 impl DesktopEntryStore {
     /// Load desktop entries from the directory paths provided, with desktop entries in
-    /// earlier dirs overriding those in later.
-    pub fn load<P>(scope_paths: &[P]) -> anyhow::Result<Self>
+    /// earlier dirs overriding those in later. `OnlyShowIn` and `NotShowIn` are evaluated
+    /// against `$XDG_CURRENT_DESKTOP`.
+    pub fn load<P>(scope_paths: &[P]) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_for_desktops(scope_paths, xdg_current_desktops())
+    }
+
+    /// Load desktop entries from the directory paths provided, evaluating `OnlyShowIn` and
+    /// `NotShowIn` against `current_desktops` rather than `$XDG_CURRENT_DESKTOP`.
+    pub fn load_for_desktops<P>(
+        scope_paths: &[P],
+        current_desktops: Vec<String>,
+    ) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
@@ -63,7 +182,28 @@ impl DesktopEntryStore {
             scopes.push(DesktopEntryScope::load(path)?);
         }
 
-        Ok(Self { scopes })
+        Ok(Self {
+            scopes,
+            current_desktops,
+        })
+    }
+
+    /// The desktops against which `OnlyShowIn` and `NotShowIn` are evaluated
+    pub fn current_desktops(&self) -> &[String] {
+        &self.current_desktops
+    }
+
+    /// Returns true if the desktop would show `desktop_entry` in its menus, taking `NoDisplay`,
+    /// `OnlyShowIn`/`NotShowIn` and `TryExec` into account.
+    pub fn should_show(&self, desktop_entry: &DesktopEntry) -> bool {
+        desktop_entry.should_show(&self.current_desktops)
+    }
+
+    /// Returns true if the desktop would offer `desktop_entry` as a handler, e.g., in an "Open With"
+    /// menu. Unlike `should_show`, `NoDisplay` entries qualify, since they are commonly installed
+    /// purely to handle mime types.
+    pub fn is_launchable(&self, desktop_entry: &DesktopEntry) -> bool {
+        desktop_entry.is_shown_in(&self.current_desktops) && desktop_entry.try_exec_succeeds()
     }
 
     /// Return the directories used to populate each scope in this store, in preferential chain order
@@ -73,7 +213,8 @@ impl DesktopEntryStore {
 
     /// Returns all desktop entries, with earlier scopes overriding later ones.
     /// E.g., if a user has a desktop entry which overrides a system one, the user
-    /// one will override the system one
+    /// one will override the system one. Entries marked `Hidden` are treated as
+    /// deleted, along with any entries they override.
     pub fn desktop_entries(&self) -> Vec<&DesktopEntry> {
         let mut table: HashMap<&DesktopEntryId, &DesktopEntry> = HashMap::new();
 
@@ -83,11 +224,23 @@ impl DesktopEntryStore {
             }
         }
 
-        let mut values = table.values().copied().collect::<Vec<_>>();
+        let mut values = table
+            .values()
+            .copied()
+            .filter(|desktop_entry| !desktop_entry.is_hidden())
+            .collect::<Vec<_>>();
         values.sort();
         values
     }
 
+    /// Returns the desktop entries the desktop would show in its menus, per `should_show`
+    pub fn visible_desktop_entries(&self) -> Vec<&DesktopEntry> {
+        self.desktop_entries()
+            .into_iter()
+            .filter(|desktop_entry| self.should_show(desktop_entry))
+            .collect()
+    }
+
     /// Return all unique desktop entry identifiers.
     pub fn desktop_entry_ids(&self) -> Vec<&DesktopEntryId> {
         self.desktop_entries()
@@ -98,10 +251,13 @@ impl DesktopEntryStore {
 
     /// Lookup the DesktopEntry with the specified identifier, returning the one
     /// earliest in the list provided at construction time, e.g., with user entries
-    /// overriding system.
+    /// overriding system. Returns None if that entry is marked `Hidden`.
     pub fn find_desktop_entry_with_id(&self, id: &DesktopEntryId) -> Option<&DesktopEntry> {
         for scope in self.scopes.iter() {
             if let Some(desktop_entry) = scope.application_entry(id) {
+                if desktop_entry.is_hidden() {
+                    return None;
+                }
                 return Some(desktop_entry);
             }
         }
@@ -109,6 +265,19 @@ impl DesktopEntryStore {
         None
     }
 
+    /// Find the `.desktop` file with the specified identifier in the earliest scope containing one, whether or not
+    /// it loaded, e.g., to explain why an entry which fails to load is missing
+    pub fn find_desktop_entry_file(&self, id: &DesktopEntryId) -> Option<PathBuf> {
+        self.scopes.iter().find_map(|scope| {
+            DesktopEntryScope::find_file(
+                &scope.directory,
+                &scope.directory,
+                id,
+                &mut HashSet::new(),
+            )
+        })
+    }
+
     /// Look up the desktop entries which can open a specific mimetype
     pub fn find_desktop_entries_for_mimetype(&self, mime_type: &MimeType) -> Vec<&DesktopEntry> {
         let mut entries = vec![];
@@ -121,15 +290,24 @@ impl DesktopEntryStore {
         entries
     }
 
+    /// Look up all URL schemes which desktop entries claim to handle via `x-scheme-handler/*` mime types, sorted
+    pub fn schemes(&self) -> Vec<String> {
+        let mut schemes = self
+            .mime_types()
+            .iter()
+            .filter_map(|mime_type| mime_type.scheme().map(str::to_string))
+            .collect::<Vec<_>>();
+        schemes.sort();
+        schemes
+    }
+
     /// Look up all mime types specified by desktop entries
     pub fn mime_types(&self) -> Vec<MimeType> {
         let mut mime_types: HashSet<MimeType> = HashSet::new();
 
-        for scope in self.scopes.iter().rev() {
-            for application in scope.application_entries.iter() {
-                for mime_type in application.1.mime_types() {
-                    mime_types.insert(mime_type.clone());
-                }
+        for desktop_entry in self.desktop_entries() {
+            for mime_type in desktop_entry.mime_types() {
+                mime_types.insert(mime_type.clone());
             }
         }
 
@@ -140,7 +318,7 @@ impl DesktopEntryStore {
 /// Covnenience function to "fuzzily" search for a DesktopEntry by name or id.
 /// First, if `identifier` is a valid DesktopEntryId and in `entries`, returns it. CASE-SENSITIVE.
 /// Second, if `identifier` can be turned into a DesktopEntryId by appending `.desktop`, and is in `entries`, returns it. CASE-SENSITIVE.
-/// Third, performs a CASE-INSENSITIVE search for the first DesktopEntry with a matching name.
+/// Third, performs a CASE-INSENSITIVE search for the first DesktopEntry with a matching localized, then untranslated, name.
 /// Finally, attempts to CASE-INSENSITIVE match the name to the id of each registered DesktopEntry, such that, e.g.,
 /// org.gnome.Evince.desktop` would match "Evince", by using the token preceding ".desktop".
 pub fn lookup_desktop_entry<'a>(
@@ -169,6 +347,15 @@ pub fn lookup_desktop_entry<'a>(
         }
     }
 
+    // the user may refer to an application by its untranslated name, regardless of locale
+    for entry in desktop_entries.iter() {
+        if let Some(entry_name) = entry.untranslated_name() {
+            if entry_name.eq_ignore_ascii_case(identifier) {
+                return Some(entry);
+            }
+        }
+    }
+
     for desktop_entry in desktop_entries {
         let components = desktop_entry.id().id().split('.').collect::<Vec<_>>();
         let count = components.len();
@@ -185,6 +372,7 @@ pub fn lookup_desktop_entry<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Environment;
 
     fn path(p: &str) -> PathBuf {
         let cwd = std::env::current_dir().unwrap();
@@ -210,6 +398,198 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn desktop_entry_scope_loads_subdirectories() -> anyhow::Result<()> {
+        let sys_scope = DesktopEntryScope::load(test_sys_applications())?;
+
+        let okular_id = DesktopEntryId::parse("kde4-okular.desktop")?;
+        let okular = sys_scope.application_entry(&okular_id).unwrap();
+        assert_eq!(okular.name(), Some("Okular"));
+        assert!(sys_scope
+            .application_entry(&DesktopEntryId::parse("okular.desktop")?)
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn symlink_loops_are_walked_once() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-desktop-entry-loop-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let applications_dir = dir.join("applications");
+        std::fs::create_dir_all(applications_dir.join("kde4"))?;
+        std::fs::write(
+            applications_dir.join("kde4/okular.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Okular\nExec=okular %U\n",
+        )?;
+        // each dir leads back to the applications dir
+        std::os::unix::fs::symlink("..", applications_dir.join("kde4/parent"))?;
+        std::os::unix::fs::symlink(&applications_dir, applications_dir.join("self"))?;
+
+        assert!(crate::is_valid_desktop_entry_dir(&applications_dir));
+        let store = DesktopEntryStore::load(&[&applications_dir])?;
+        let okular_id = DesktopEntryId::parse("kde4-okular.desktop")?;
+        assert_eq!(store.desktop_entries().len(), 1);
+        assert!(store.find_desktop_entry_with_id(&okular_id).is_some());
+        assert_eq!(
+            store.find_desktop_entry_file(&okular_id),
+            Some(applications_dir.join("kde4/okular.desktop"))
+        );
+        assert_eq!(
+            store.find_desktop_entry_file(&DesktopEntryId::parse("missing.desktop")?),
+            None
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn desktop_entry_scope_first_entry_shadows_subdirectory_entry_with_same_id(
+    ) -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-shadowed-applications-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("kde4"))?;
+
+        let entry = |name: &str| {
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=okular %U\n",
+                name
+            )
+        };
+        std::fs::write(dir.join("kde4-okular.desktop"), entry("Top Level"))?;
+        std::fs::write(dir.join("kde4/okular.desktop"), entry("Subdirectory"))?;
+
+        let scope = DesktopEntryScope::load(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let scope = scope?;
+        let okular_id = DesktopEntryId::parse("kde4-okular.desktop")?;
+        assert_eq!(scope.application_entries.len(), 1);
+        assert_eq!(
+            scope.application_entry(&okular_id).unwrap().name(),
+            Some("Top Level")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn hidden_user_entry_deletes_system_entry() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-hidden-applications-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join("org.gnome.Evince.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Document Viewer\nExec=evince\nHidden=true\n",
+        )?;
+        // an override need not repeat the Type of the entry it deletes
+        std::fs::write(
+            dir.join("org.gnome.eog.desktop"),
+            "[Desktop Entry]\nHidden=true\n",
+        )?;
+
+        let entries = DesktopEntryStore::load(&[&dir, &test_sys_applications()]);
+        let _ = std::fs::remove_dir_all(&dir);
+        let entries = entries?;
+
+        let evince_id = DesktopEntryId::parse("org.gnome.Evince.desktop")?;
+        assert!(entries.find_desktop_entry_with_id(&evince_id).is_none());
+        assert!(!entries.desktop_entry_ids().contains(&&evince_id));
+        assert!(lookup_desktop_entry(&entries, "Evince").is_none());
+
+        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        assert!(entries.find_desktop_entry_with_id(&eog_id).is_none());
+        assert!(!entries
+            .visible_desktop_entries()
+            .iter()
+            .any(|de| de.id() == &eog_id));
+
+        let image_tiff = MimeType::parse("image/tiff")?;
+        assert!(!entries
+            .find_desktop_entries_for_mimetype(&image_tiff)
+            .iter()
+            .any(|de| de.id() == &evince_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_entries_respect_current_desktops() -> anyhow::Result<()> {
+        let data_home = std::env::temp_dir().join(format!(
+            "mimeassoc-desktop-specific-applications-{}",
+            std::process::id()
+        ));
+        let dir = data_home.join("applications");
+        let _ = std::fs::remove_dir_all(&data_home);
+        std::fs::create_dir_all(&dir)?;
+        let entry = |extra: &str| {
+            format!(
+                "[Desktop Entry]\nType=Application\nName=Test\nExec=test\n{}\n",
+                extra
+            )
+        };
+        std::fs::write(dir.join("kde-only.desktop"), entry("OnlyShowIn=KDE;"))?;
+        std::fs::write(dir.join("not-kde.desktop"), entry("NotShowIn=KDE;"))?;
+        std::fs::write(dir.join("gnome-only.desktop"), entry("OnlyShowIn=GNOME;"))?;
+        std::fs::write(dir.join("not-gnome.desktop"), entry("NotShowIn=GNOME;"))?;
+        std::fs::write(dir.join("no-display.desktop"), entry("NoDisplay=true"))?;
+        std::fs::write(
+            dir.join("missing.desktop"),
+            entry("TryExec=/nonexistent/mimeassoc-test"),
+        )?;
+
+        let kde = DesktopEntryStore::load_for_desktops(&[&dir], vec!["KDE".to_string()]);
+        let gnome = DesktopEntryStore::load_for_desktops(&[&dir], vec!["GNOME".to_string()]);
+
+        // as the CLI and GUI load it, with the desktops of the environment
+        let environment = Environment::for_home(&data_home)
+            .with_data_home(&data_home)
+            .with_data_dirs(vec![])
+            .with_current_desktops(vec!["GNOME".to_string()]);
+        let from_environment = DesktopEntryStore::load_for_desktops(
+            &environment.desktop_entry_dirs(),
+            environment.current_desktops().to_vec(),
+        );
+        let _ = std::fs::remove_dir_all(&data_home);
+        let (kde, gnome, from_environment) = (kde?, gnome?, from_environment?);
+
+        let ids = |store: &DesktopEntryStore| {
+            store
+                .visible_desktop_entries()
+                .iter()
+                .map(|de| de.id().id().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&kde), ["kde-only.desktop", "not-gnome.desktop"]);
+        assert_eq!(ids(&gnome), ["gnome-only.desktop", "not-kde.desktop"]);
+        assert_eq!(
+            ids(&from_environment),
+            ["gnome-only.desktop", "not-kde.desktop"]
+        );
+
+        // NoDisplay entries are still offered as handlers, entries failing TryExec are not
+        let no_display = gnome
+            .find_desktop_entry_with_id(&DesktopEntryId::parse("no-display.desktop")?)
+            .unwrap();
+        let missing = gnome
+            .find_desktop_entry_with_id(&DesktopEntryId::parse("missing.desktop")?)
+            .unwrap();
+        assert!(gnome.is_launchable(no_display));
+        assert!(!gnome.is_launchable(missing));
+
+        Ok(())
+    }
+
     #[test]
     fn desktop_entries_loads_single_scopes() -> anyhow::Result<()> {
         assert!(!DesktopEntryStore::load(&[test_sys_applications()])?
@@ -311,6 +691,14 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn desktop_entry_store_gathers_schemes() -> anyhow::Result<()> {
+        let entries = DesktopEntryStore::load(&[path("test-data/open/applications")])?;
+        assert_eq!(entries.schemes(), ["https"]);
+
+        Ok(())
+    }
+
     #[test]
     fn fuzzy_lookup_works() -> anyhow::Result<()> {
         let entries =
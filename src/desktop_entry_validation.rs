@@ -0,0 +1,912 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    desktop_entry_exec::{self, field_codes, ExecArg},
+    Environment, Error, KeyFileValue, LintSeverity,
+};
+
+/// The keys the Desktop Entry spec defines for the `[Desktop Entry]` group
+const DESKTOP_ENTRY_KEYS: &[&str] = &[
+    "Type",
+    "Version",
+    "Name",
+    "GenericName",
+    "NoDisplay",
+    "Comment",
+    "Icon",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "DBusActivatable",
+    "TryExec",
+    "Exec",
+    "Path",
+    "Terminal",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+    "StartupNotify",
+    "StartupWMClass",
+    "URL",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+/// The keys the spec defines for `[Desktop Action ...]` groups
+const DESKTOP_ACTION_KEYS: &[&str] = &["Name", "Icon", "Exec"];
+
+/// Keys of earlier versions of the spec, or of KDE, which are no longer part of it
+const DEPRECATED_KEYS: &[&str] = &[
+    "Encoding",
+    "MiniIcon",
+    "TerminalOptions",
+    "Protocols",
+    "Extensions",
+    "BinaryPattern",
+    "MapNotify",
+    "SwallowTitle",
+    "SwallowExec",
+    "SortOrder",
+    "FilePattern",
+    "Patterns",
+    "DefaultApp",
+    "Dev",
+    "FSType",
+    "MountPoint",
+    "ReadOnly",
+    "UnmountIcon",
+];
+
+/// The keys which may have localized variants, e.g., `Name[de]`
+const LOCALIZABLE_KEYS: &[&str] = &["Name", "GenericName", "Comment", "Keywords", "Icon"];
+
+/// The keys whose values are `;` terminated lists
+const LIST_KEYS: &[&str] = &[
+    "OnlyShowIn",
+    "NotShowIn",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+];
+
+/// The field codes which are deprecated, and removed when the `Exec` command line is expanded
+const DEPRECATED_FIELD_CODES: &[char] = &['d', 'D', 'n', 'N', 'v', 'm'];
+
+/// A problem found in a `.desktop` file by `DesktopEntryValidation`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DesktopEntryProblem {
+    /// The line starts with `[`, but isn't a well-formed `[Group Name]` header
+    MalformedGroupHeader { text: String },
+    /// The first group of the file isn't `[Desktop Entry]`
+    MissingDesktopEntryGroup,
+    /// The group is neither `[Desktop Entry]`, a `[Desktop Action ...]` nor an `X-` extension group
+    UnknownGroup { group: String },
+    /// The group appears earlier in the file
+    DuplicateGroup { group: String },
+    /// The line isn't a comment, group header, or `Key=value` entry within a group
+    MalformedLine { text: String },
+    /// The key appears earlier in the same group
+    DuplicateKey { key: String },
+    /// A key the spec requires, e.g., `Name`, is missing from the group
+    MissingRequiredKey { group: String, key: String },
+    /// `Type` isn't `Application`, `Link` or `Directory`
+    InvalidType { value: String },
+    /// The key isn't defined by the spec, and isn't an `X-` extension
+    UnknownKey { key: String },
+    /// The key was defined by an earlier version of the spec, and is ignored
+    DeprecatedKey { key: String },
+    /// The key has a locale suffix, but the key can't be localized or the locale is malformed
+    InvalidLocaleKey { key: String },
+    /// An element of the `MimeType` list isn't a `major/minor` mime type
+    InvalidMimeType { value: String },
+    /// A list value doesn't end with `;`
+    UnterminatedList { key: String },
+    /// The `Exec` command line can't be parsed, e.g., a quoted argument is unterminated
+    InvalidExec { message: String },
+    /// The program named by `Exec` or `TryExec` can't be found, or isn't executable
+    ExecutableNotFound { key: String, program: String },
+    /// The `Exec` command line uses a field code the spec doesn't define
+    UnknownFieldCode { code: String },
+    /// The `Exec` command line uses a field code which is deprecated, and ignored
+    DeprecatedFieldCode { code: String },
+    /// A field code appears within a quoted argument, where it isn't expanded
+    QuotedFieldCode { code: String },
+    /// `%F`, `%U` or `%i` is part of a larger argument, rather than an argument of its own
+    EmbeddedFieldCode { code: String },
+    /// The `Exec` command line uses more than one of `%f`, `%F`, `%u` and `%U`
+    MultipleFileFieldCodes,
+}
+
+impl std::fmt::Display for DesktopEntryProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedGroupHeader { text } => {
+                write!(f, "\"{}\" isn't a valid group header", text)
+            }
+            Self::MissingDesktopEntryGroup => {
+                write!(f, "the first group isn't [Desktop Entry]")
+            }
+            Self::UnknownGroup { group } => write!(f, "[{}] isn't a known group", group),
+            Self::DuplicateGroup { group } => write!(f, "[{}] appears more than once", group),
+            Self::MalformedLine { text } => write!(f, "\"{}\" can't be parsed", text),
+            Self::DuplicateKey { key } => write!(f, "{} appears more than once", key),
+            Self::MissingRequiredKey { group, key } => {
+                write!(f, "[{}] is missing the required key {}", group, key)
+            }
+            Self::InvalidType { value } => write!(f, "\"{}\" isn't a valid Type", value),
+            Self::UnknownKey { key } => write!(f, "{} isn't a known key", key),
+            Self::DeprecatedKey { key } => write!(f, "{} is deprecated", key),
+            Self::InvalidLocaleKey { key } => {
+                write!(f, "{} isn't a valid localized key", key)
+            }
+            Self::InvalidMimeType { value } => {
+                write!(f, "\"{}\" in MimeType isn't a valid mime type", value)
+            }
+            Self::UnterminatedList { key } => {
+                write!(f, "the value of {} doesn't end with \";\"", key)
+            }
+            Self::InvalidExec { message } => write!(f, "Exec {}", message),
+            Self::ExecutableNotFound { key, program } => write!(
+                f,
+                "{} names \"{}\", which isn't installed or isn't executable",
+                key, program
+            ),
+            Self::UnknownFieldCode { code } => {
+                write!(f, "{} isn't a valid field code", code)
+            }
+            Self::DeprecatedFieldCode { code } => {
+                write!(f, "the field code {} is deprecated", code)
+            }
+            Self::QuotedFieldCode { code } => write!(
+                f,
+                "the field code {} is within a quoted argument, so isn't expanded",
+                code
+            ),
+            Self::EmbeddedFieldCode { code } => {
+                write!(f, "the field code {} must be an argument on its own", code)
+            }
+            Self::MultipleFileFieldCodes => {
+                write!(f, "Exec uses more than one of %f, %F, %u and %U")
+            }
+        }
+    }
+}
+
+/// A problem found in a `.desktop` file, and where
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DesktopEntryDiagnostic {
+    /// 1-based; None for problems with the file as a whole, e.g., a missing required key
+    pub line: Option<usize>,
+    /// Errors are problems which cause the entry, or the line, to be rejected or misbehave; warnings are
+    /// problems which desktops tolerate
+    pub severity: LintSeverity,
+    pub problem: DesktopEntryProblem,
+}
+
+/// The result of validating a `.desktop` file against the Desktop Entry spec, in the manner of
+/// `desktop-file-validate`. Where `DesktopEntry::appears_valid_application` only answers whether an entry
+/// is usable, this explains why not, e.g., when an application doesn't show up in "Open With" menus.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DesktopEntryValidation {
+    pub path: PathBuf,
+    pub diagnostics: Vec<DesktopEntryDiagnostic>,
+}
+
+impl DesktopEntryValidation {
+    /// Read and validate the `.desktop` file at `path`
+    pub fn validate<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        Ok(Self::parse(path, &contents))
+    }
+
+    /// Read and validate the `.desktop` file at `path`, as `DesktopEntryValidation::validate`, but resolving the
+    /// programs of `Exec` and `TryExec` beneath the root of `environment`, if it has one
+    pub fn validate_for_environment<P>(path: P, environment: &Environment) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        Ok(Self::parse_for_environment(path, &contents, environment))
+    }
+
+    /// Validate `contents`, the text of the `.desktop` file at `path`
+    pub fn parse<P>(path: P, contents: &str) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse_beneath(path.as_ref(), contents, None)
+    }
+
+    /// Validate `contents`, the text of the `.desktop` file at `path`, as `DesktopEntryValidation::parse`, but
+    /// resolving the programs of `Exec` and `TryExec` beneath the root of `environment`, if it has one
+    pub fn parse_for_environment<P>(path: P, contents: &str, environment: &Environment) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse_beneath(path.as_ref(), contents, environment.root())
+    }
+
+    fn parse_beneath(path: &Path, contents: &str, root: Option<&Path>) -> Self {
+        let mut validator = Validator {
+            root: root.map(Path::to_path_buf),
+            ..Default::default()
+        };
+        let groups = validator.parse_groups(contents);
+
+        match groups.first() {
+            Some(group) if group.name == "Desktop Entry" => {}
+            _ => validator.report(
+                None,
+                LintSeverity::Error,
+                DesktopEntryProblem::MissingDesktopEntryGroup,
+            ),
+        }
+
+        for group in groups.iter().filter(|group| !group.is_duplicate) {
+            if group.name == "Desktop Entry" {
+                validator.validate_desktop_entry_group(group);
+            } else if group.name.starts_with("Desktop Action ") {
+                validator.validate_desktop_action_group(group);
+            }
+        }
+
+        validator.diagnostics.sort_by_key(|d| d.line);
+        Self {
+            path: path.to_path_buf(),
+            diagnostics: validator.diagnostics,
+        }
+    }
+
+    /// True if no errors were found; warnings don't prevent desktops from using the entry
+    pub fn is_valid(&self) -> bool {
+        !self
+            .diagnostics
+            .iter()
+            .any(|d| d.severity == LintSeverity::Error)
+    }
+}
+
+struct Group<'a> {
+    name: &'a str,
+    is_duplicate: bool,
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Group<'a> {
+    fn value(&self, key: &str) -> Option<&'a str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value)
+    }
+}
+
+struct Entry<'a> {
+    line: usize,
+    key: &'a str,
+    value: &'a str,
+}
+
+#[derive(Default)]
+struct Validator {
+    diagnostics: Vec<DesktopEntryDiagnostic>,
+    // programs are resolved beneath this, if set
+    root: Option<PathBuf>,
+}
+
+impl Validator {
+    fn report(
+        &mut self,
+        line: Option<usize>,
+        severity: LintSeverity,
+        problem: DesktopEntryProblem,
+    ) {
+        self.diagnostics.push(DesktopEntryDiagnostic {
+            line,
+            severity,
+            problem,
+        });
+    }
+
+    /// Split `contents` into groups, reporting malformed lines and group headers, and unknown or repeated groups.
+    /// The entries following a malformed group header are skipped, since they belong to no known group.
+    fn parse_groups<'a>(&mut self, contents: &'a str) -> Vec<Group<'a>> {
+        let mut groups: Vec<Group> = vec![];
+        let mut in_malformed_group = false;
+
+        for (index, text) in contents.lines().enumerate() {
+            let line = Some(index + 1);
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            if text.starts_with('[') {
+                let name = text
+                    .strip_prefix('[')
+                    .and_then(|text| text.strip_suffix(']'))
+                    .filter(|name| {
+                        !name.is_empty()
+                            && !name.contains(['[', ']'])
+                            && !name.chars().any(char::is_control)
+                    });
+                let Some(name) = name else {
+                    self.report(
+                        line,
+                        LintSeverity::Error,
+                        DesktopEntryProblem::MalformedGroupHeader {
+                            text: text.to_string(),
+                        },
+                    );
+                    in_malformed_group = true;
+                    continue;
+                };
+
+                let is_duplicate = groups.iter().any(|group| group.name == name);
+                if is_duplicate {
+                    self.report(
+                        line,
+                        LintSeverity::Error,
+                        DesktopEntryProblem::DuplicateGroup {
+                            group: name.to_string(),
+                        },
+                    );
+                } else if name != "Desktop Entry"
+                    && !name.starts_with("Desktop Action ")
+                    && !name.starts_with("X-")
+                {
+                    self.report(
+                        line,
+                        LintSeverity::Error,
+                        DesktopEntryProblem::UnknownGroup {
+                            group: name.to_string(),
+                        },
+                    );
+                }
+
+                groups.push(Group {
+                    name,
+                    is_duplicate,
+                    entries: vec![],
+                });
+                in_malformed_group = false;
+                continue;
+            }
+
+            match (text.split_once('='), groups.last_mut()) {
+                (Some((key, value)), Some(group)) if !in_malformed_group => {
+                    group.entries.push(Entry {
+                        line: index + 1,
+                        key: key.trim(),
+                        value: value.trim(),
+                    })
+                }
+                (Some(_), _) if in_malformed_group => {}
+                _ => self.report(
+                    line,
+                    LintSeverity::Error,
+                    DesktopEntryProblem::MalformedLine {
+                        text: text.to_string(),
+                    },
+                ),
+            }
+        }
+
+        groups
+    }
+
+    fn validate_desktop_entry_group(&mut self, group: &Group) {
+        self.validate_keys(group, DESKTOP_ENTRY_KEYS);
+
+        let entry_type = group.value("Type");
+        for key in ["Type", "Name"] {
+            self.require(group, key);
+        }
+        match entry_type {
+            Some("Application") => {
+                let is_dbus_activatable = group
+                    .value("DBusActivatable")
+                    .and_then(|value| KeyFileValue::new(value).as_boolean())
+                    .unwrap_or(false);
+                if !is_dbus_activatable {
+                    self.require(group, "Exec");
+                }
+            }
+            Some("Link") => self.require(group, "URL"),
+            _ => {}
+        }
+    }
+
+    fn validate_desktop_action_group(&mut self, group: &Group) {
+        self.validate_keys(group, DESKTOP_ACTION_KEYS);
+        self.require(group, "Name");
+    }
+
+    fn require(&mut self, group: &Group, key: &str) {
+        if group.value(key).is_none() {
+            self.report(
+                None,
+                LintSeverity::Error,
+                DesktopEntryProblem::MissingRequiredKey {
+                    group: group.name.to_string(),
+                    key: key.to_string(),
+                },
+            );
+        }
+    }
+
+    fn validate_keys(&mut self, group: &Group, known_keys: &[&str]) {
+        let mut seen = HashSet::new();
+        for entry in group.entries.iter() {
+            let line = Some(entry.line);
+            let key = entry.key.to_string();
+            if !seen.insert(entry.key) {
+                self.report(
+                    line,
+                    LintSeverity::Error,
+                    DesktopEntryProblem::DuplicateKey { key },
+                );
+                continue;
+            }
+
+            let (base, locale) = match entry.key.split_once('[') {
+                Some((base, locale)) => match locale.strip_suffix(']') {
+                    Some(locale) => (base, Some(locale)),
+                    None => {
+                        self.report(
+                            line,
+                            LintSeverity::Error,
+                            DesktopEntryProblem::InvalidLocaleKey { key },
+                        );
+                        continue;
+                    }
+                },
+                None => (entry.key, None),
+            };
+
+            if base.is_empty() || !base.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                self.report(
+                    line,
+                    LintSeverity::Error,
+                    DesktopEntryProblem::UnknownKey { key },
+                );
+                continue;
+            }
+
+            if let Some(locale) = locale {
+                if !is_valid_locale(locale)
+                    || !(LOCALIZABLE_KEYS.contains(&base) || base.starts_with("X-"))
+                {
+                    self.report(
+                        line,
+                        LintSeverity::Error,
+                        DesktopEntryProblem::InvalidLocaleKey { key },
+                    );
+                }
+            } else if base.starts_with("X-") {
+                // extension keys may hold anything
+            } else if DEPRECATED_KEYS.contains(&base) {
+                self.report(
+                    line,
+                    LintSeverity::Warning,
+                    DesktopEntryProblem::DeprecatedKey { key },
+                );
+            } else if !known_keys.contains(&base) {
+                self.report(
+                    line,
+                    LintSeverity::Error,
+                    DesktopEntryProblem::UnknownKey { key },
+                );
+            } else {
+                self.validate_value(entry);
+            }
+        }
+    }
+
+    fn validate_value(&mut self, entry: &Entry) {
+        let line = Some(entry.line);
+        let value = KeyFileValue::new(entry.value);
+
+        if LIST_KEYS.contains(&entry.key) && !entry.value.is_empty() && !entry.value.ends_with(';')
+        {
+            self.report(
+                line,
+                LintSeverity::Warning,
+                DesktopEntryProblem::UnterminatedList {
+                    key: entry.key.to_string(),
+                },
+            );
+        }
+
+        match entry.key {
+            "Type" if !matches!(entry.value, "Application" | "Link" | "Directory") => {
+                self.report(
+                    line,
+                    LintSeverity::Error,
+                    DesktopEntryProblem::InvalidType {
+                        value: entry.value.to_string(),
+                    },
+                );
+            }
+            "MimeType" => {
+                for mime_type in value.as_string_list() {
+                    if !is_valid_mime_type(&mime_type) {
+                        self.report(
+                            line,
+                            LintSeverity::Error,
+                            DesktopEntryProblem::InvalidMimeType { value: mime_type },
+                        );
+                    }
+                }
+            }
+            "TryExec" => {
+                let program = value.as_string();
+                if desktop_entry_exec::resolve_program_beneath(&program, self.root.as_deref())
+                    .is_err()
+                {
+                    self.report(
+                        line,
+                        LintSeverity::Warning,
+                        DesktopEntryProblem::ExecutableNotFound {
+                            key: entry.key.to_string(),
+                            program,
+                        },
+                    );
+                }
+            }
+            "Exec" => self.validate_exec(entry.line, &value.as_string()),
+            _ => {}
+        }
+    }
+
+    fn validate_exec(&mut self, line: usize, exec: &str) {
+        let line = Some(line);
+        let args = match desktop_entry_exec::tokenize(exec) {
+            Ok(args) => args,
+            Err(Error::InvalidExec { message, .. }) => {
+                self.report(
+                    line,
+                    LintSeverity::Error,
+                    DesktopEntryProblem::InvalidExec { message },
+                );
+                return;
+            }
+            Err(_) => return,
+        };
+
+        let mut file_field_codes = 0;
+        for arg in args.iter() {
+            for code in field_codes(arg.text()) {
+                let problem = match (arg, code) {
+                    (_, '%') => None,
+                    (ExecArg::Quoted(_), _) => Some((
+                        LintSeverity::Error,
+                        DesktopEntryProblem::QuotedFieldCode {
+                            code: format!("%{}", code),
+                        },
+                    )),
+                    (ExecArg::Unquoted(text), 'F' | 'U' | 'i')
+                        if text.as_str() != format!("%{}", code) =>
+                    {
+                        Some((
+                            LintSeverity::Error,
+                            DesktopEntryProblem::EmbeddedFieldCode {
+                                code: format!("%{}", code),
+                            },
+                        ))
+                    }
+                    (_, 'f' | 'F' | 'u' | 'U') => {
+                        file_field_codes += 1;
+                        None
+                    }
+                    (_, 'i' | 'c' | 'k') => None,
+                    (_, code) if DEPRECATED_FIELD_CODES.contains(&code) => Some((
+                        LintSeverity::Warning,
+                        DesktopEntryProblem::DeprecatedFieldCode {
+                            code: format!("%{}", code),
+                        },
+                    )),
+                    (_, code) => Some((
+                        LintSeverity::Error,
+                        DesktopEntryProblem::UnknownFieldCode {
+                            code: format!("%{}", code),
+                        },
+                    )),
+                };
+                if let Some((severity, problem)) = problem {
+                    self.report(line, severity, problem);
+                }
+            }
+        }
+
+        if file_field_codes > 1 {
+            self.report(
+                line,
+                LintSeverity::Error,
+                DesktopEntryProblem::MultipleFileFieldCodes,
+            );
+        }
+
+        let program = desktop_entry_exec::program(&args);
+        if program.is_empty() {
+            self.report(
+                line,
+                LintSeverity::Error,
+                DesktopEntryProblem::InvalidExec {
+                    message: "has no program".to_string(),
+                },
+            );
+        } else if desktop_entry_exec::resolve_program_beneath(program, self.root.as_deref())
+            .is_err()
+        {
+            // as with TryExec, the program may simply not be installed here, e.g., on another machine
+            self.report(
+                line,
+                LintSeverity::Warning,
+                DesktopEntryProblem::ExecutableNotFound {
+                    key: "Exec".to_string(),
+                    program: program.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// True if `locale` has the form `lang_COUNTRY.ENCODING@MODIFIER`, where all but `lang` are optional
+fn is_valid_locale(locale: &str) -> bool {
+    let is_word = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric());
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+    let (locale, encoding) = match locale.split_once('.') {
+        Some((locale, encoding)) => (locale, Some(encoding)),
+        None => (locale, None),
+    };
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+
+    !lang.is_empty()
+        && lang.chars().all(|c| c.is_ascii_alphabetic())
+        && country.is_none_or(is_word)
+        && encoding.is_none_or(|e| {
+            !e.is_empty() && e.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && modifier.is_none_or(is_word)
+}
+
+/// True if `mime_type` has the form `major/minor`, without whitespace
+fn is_valid_mime_type(mime_type: &str) -> bool {
+    match mime_type.split_once('/') {
+        Some((major, minor)) => {
+            !major.is_empty()
+                && !minor.is_empty()
+                && !minor.contains('/')
+                && !mime_type.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(contents: &str) -> Vec<(Option<usize>, LintSeverity, DesktopEntryProblem)> {
+        DesktopEntryValidation::parse("test.desktop", contents)
+            .diagnostics
+            .into_iter()
+            .map(|d| (d.line, d.severity, d.problem))
+            .collect()
+    }
+
+    #[test]
+    fn validates_desktop_entries() {
+        let valid = "# comment\n[Desktop Entry]\nType=Application\nName=Viewer\nName[de_DE@euro]=Betrachter\nExec=env FOO=1 sh -c \"echo %%\" %U\nMimeType=image/png;image/*;\nActions=new;\nX-Custom[fr]=oui\n\n[Desktop Action new]\nName=New Window\nExec=sh %i\n\n[X-Extension]\nAnything=goes\n";
+        let validation = DesktopEntryValidation::parse("test.desktop", valid);
+        assert!(validation.diagnostics.is_empty(), "{:?}", validation);
+        assert!(validation.is_valid());
+
+        use DesktopEntryProblem::*;
+        use LintSeverity::*;
+        let invalid = "[Desktop Entry]\nType=Program\nName=Viewer\nName=Again\nComment[xx_]=bad\nExec[de]=sh\nEncoding=UTF-8\nColour=red\nMimeType=image/png;text;\nCategories=Graphics\nTryExec=mimeassoc-missing-program\nExec=mimeassoc-missing-program %f %F \"%u\" x%U %d %z\nno equals sign\n[Broken\nIgnored=entry\n[Desktop Action new]\nIcon=new\n[Desktop Entry]\n[Other]\n";
+        assert_eq!(
+            problems(invalid),
+            [
+                (
+                    None,
+                    Error,
+                    MissingRequiredKey {
+                        group: "Desktop Action new".into(),
+                        key: "Name".into()
+                    }
+                ),
+                (
+                    Some(2),
+                    Error,
+                    InvalidType {
+                        value: "Program".into()
+                    }
+                ),
+                (Some(4), Error, DuplicateKey { key: "Name".into() }),
+                (
+                    Some(5),
+                    Error,
+                    InvalidLocaleKey {
+                        key: "Comment[xx_]".into()
+                    }
+                ),
+                (
+                    Some(6),
+                    Error,
+                    InvalidLocaleKey {
+                        key: "Exec[de]".into()
+                    }
+                ),
+                (
+                    Some(7),
+                    Warning,
+                    DeprecatedKey {
+                        key: "Encoding".into()
+                    }
+                ),
+                (
+                    Some(8),
+                    Error,
+                    UnknownKey {
+                        key: "Colour".into()
+                    }
+                ),
+                (
+                    Some(9),
+                    Error,
+                    InvalidMimeType {
+                        value: "text".into()
+                    }
+                ),
+                (
+                    Some(10),
+                    Warning,
+                    UnterminatedList {
+                        key: "Categories".into()
+                    }
+                ),
+                (
+                    Some(11),
+                    Warning,
+                    ExecutableNotFound {
+                        key: "TryExec".into(),
+                        program: "mimeassoc-missing-program".into()
+                    }
+                ),
+                (Some(12), Error, QuotedFieldCode { code: "%u".into() }),
+                (Some(12), Error, EmbeddedFieldCode { code: "%U".into() }),
+                (Some(12), Warning, DeprecatedFieldCode { code: "%d".into() }),
+                (Some(12), Error, UnknownFieldCode { code: "%z".into() }),
+                (Some(12), Error, MultipleFileFieldCodes),
+                (
+                    Some(12),
+                    Warning,
+                    ExecutableNotFound {
+                        key: "Exec".into(),
+                        program: "mimeassoc-missing-program".into()
+                    }
+                ),
+                (
+                    Some(13),
+                    Error,
+                    MalformedLine {
+                        text: "no equals sign".into()
+                    }
+                ),
+                (
+                    Some(14),
+                    Error,
+                    MalformedGroupHeader {
+                        text: "[Broken".into()
+                    }
+                ),
+                (
+                    Some(18),
+                    Error,
+                    DuplicateGroup {
+                        group: "Desktop Entry".into()
+                    }
+                ),
+                (
+                    Some(19),
+                    Error,
+                    UnknownGroup {
+                        group: "Other".into()
+                    }
+                ),
+            ]
+        );
+
+        // the Desktop Entry group must come first, and Exec is only optional for D-Bus activatable applications
+        assert_eq!(
+            problems("Key=value\n[X-First]\n[Desktop Entry]\nType=Application\nName=Viewer\nExec=sh \"unterminated\n"),
+            [
+                (None, Error, MissingDesktopEntryGroup),
+                (Some(1), Error, MalformedLine { text: "Key=value".into() }),
+                (Some(6), Error, InvalidExec { message: "has an unterminated quoted argument".into() }),
+            ]
+        );
+        assert_eq!(
+            problems("[Desktop Entry]\nType=Application\nName=Viewer\n"),
+            [(
+                None,
+                Error,
+                MissingRequiredKey {
+                    group: "Desktop Entry".into(),
+                    key: "Exec".into()
+                }
+            )]
+        );
+        assert!(
+            problems("[Desktop Entry]\nType=Application\nName=Viewer\nDBusActivatable=true\n")
+                .is_empty()
+        );
+        assert_eq!(
+            problems("[Desktop Entry]\nType=Link\nName=Docs\n"),
+            [(
+                None,
+                Error,
+                MissingRequiredKey {
+                    group: "Desktop Entry".into(),
+                    key: "URL".into()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn resolves_programs_beneath_the_root() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root =
+            std::env::temp_dir().join(format!("mimeassoc-validation-root-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let bin_dir = root.join("usr/bin");
+        std::fs::create_dir_all(&bin_dir)?;
+        let program_path = bin_dir.join("mimeassoc-image-viewer");
+        std::fs::write(&program_path, "#!/bin/sh\n")?;
+        std::fs::set_permissions(&program_path, std::fs::Permissions::from_mode(0o755))?;
+
+        let contents = "[Desktop Entry]\nType=Application\nName=Viewer\nTryExec=/usr/bin/mimeassoc-image-viewer\nExec=mimeassoc-image-viewer %f\n";
+        let environment = Environment::for_root(&root, "/home/user");
+        let validation =
+            DesktopEntryValidation::parse_for_environment("test.desktop", contents, &environment);
+        assert!(validation.diagnostics.is_empty(), "{:?}", validation);
+
+        // the programs aren't on the host, which is only worth a warning
+        let validation = DesktopEntryValidation::parse("test.desktop", contents);
+        assert_eq!(validation.diagnostics.len(), 2);
+        assert!(validation
+            .diagnostics
+            .iter()
+            .all(|d| d.severity == LintSeverity::Warning));
+        assert!(validation.is_valid());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    default_xdg_config_dirs, default_xdg_data_dirs, has_extension, is_valid_desktop_entry_dir,
+    mimeapps_list_candidates, xdg_config_dirs, xdg_current_desktops, xdg_data_dirs, xdg_env_path,
+    Error,
+};
+
+/// The user's home, the XDG config, data and state dirs, and the current desktops, from which the locations of
+/// desktop entries, mimeapps.list files and mime info are discovered. Normally read from the process
+/// environment with `Environment::from_process`, but can be built for any home, or moved beneath a root
+/// directory to inspect a container image or chroot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Environment {
+    home: PathBuf,
+    config_home: PathBuf,
+    config_dirs: Vec<PathBuf>,
+    data_home: PathBuf,
+    data_dirs: Vec<PathBuf>,
+    state_home: PathBuf,
+    current_desktops: Vec<String>,
+    root: Option<PathBuf>,
+}
+
+impl Environment {
+    /// Read the environment from `$HOME`, `$XDG_CONFIG_HOME`, `$XDG_CONFIG_DIRS`, `$XDG_DATA_HOME`,
+    /// `$XDG_DATA_DIRS`, `$XDG_STATE_HOME` and `$XDG_CURRENT_DESKTOP`, with the defaults of the XDG Base Directory spec.
+    pub fn from_process() -> Result<Self, Error> {
+        let home = std::env::var("HOME")
+            .map(PathBuf::from)
+            .map_err(|_| Error::MissingEnvironmentVariable("HOME".to_string()))?;
+        let mut environment = Self::for_home(home)
+            .with_config_dirs(xdg_config_dirs())
+            .with_data_dirs(xdg_data_dirs())
+            .with_current_desktops(xdg_current_desktops());
+        if let Some(config_home) = xdg_env_path("XDG_CONFIG_HOME") {
+            environment.config_home = config_home;
+        }
+        if let Some(data_home) = xdg_env_path("XDG_DATA_HOME") {
+            environment.data_home = data_home;
+        }
+        if let Some(state_home) = xdg_env_path("XDG_STATE_HOME") {
+            environment.state_home = state_home;
+        }
+        Ok(environment)
+    }
+
+    /// Create an environment for the user whose home is `home`, with config, data and state homes at
+    /// `home/.config`, `home/.local/share` and `home/.local/state`, and the spec's default config and data dirs,
+    /// `/etc/xdg` and `/usr/local/share:/usr/share`. Nothing is read from the process, whose `$XDG_*` variables
+    /// describe its own user; there are no current desktops unless set with `Environment::with_current_desktops`.
+    pub fn for_home<P>(home: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let home = home.into();
+        Self {
+            config_home: home.join(".config"),
+            config_dirs: default_xdg_config_dirs(),
+            data_home: home.join(".local/share"),
+            data_dirs: default_xdg_data_dirs(),
+            state_home: home.join(".local/state"),
+            current_desktops: vec![],
+            root: None,
+            home,
+        }
+    }
+
+    /// Create an environment for the user whose home is `home` within the container image or chroot mounted at
+    /// `root`, as `Environment::for_home` does, with every path moved beneath `root`.
+    pub fn for_root<P, Q>(root: P, home: Q) -> Self
+    where
+        P: AsRef<Path>,
+        Q: Into<PathBuf>,
+    {
+        Self::for_home(home).with_root(root)
+    }
+
+    pub fn with_config_home<P>(mut self, config_home: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.config_home = config_home.into();
+        self
+    }
+
+    pub fn with_config_dirs(mut self, config_dirs: Vec<PathBuf>) -> Self {
+        self.config_dirs = config_dirs;
+        self
+    }
+
+    pub fn with_data_home<P>(mut self, data_home: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.data_home = data_home.into();
+        self
+    }
+
+    pub fn with_data_dirs(mut self, data_dirs: Vec<PathBuf>) -> Self {
+        self.data_dirs = data_dirs;
+        self
+    }
+
+    pub fn with_state_home<P>(mut self, state_home: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.state_home = state_home.into();
+        self
+    }
+
+    /// Set the desktops `OnlyShowIn`, `NotShowIn` and `$desktop-mimeapps.list` files are evaluated against,
+    /// e.g., ["ubuntu", "GNOME"]. Names are kept as given, since `OnlyShowIn` and `NotShowIn` are matched exactly.
+    pub fn with_current_desktops(mut self, current_desktops: Vec<String>) -> Self {
+        self.current_desktops = current_desktops;
+        self
+    }
+
+    /// Move every path of this environment beneath `root`, e.g., `/usr/share` becomes `<root>/usr/share`,
+    /// to discover the configuration of a container image or chroot mounted at `root`.
+    pub fn with_root<P>(self, root: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let rebase = |path: PathBuf| match path.strip_prefix("/") {
+            Ok(relative) => root.join(relative),
+            Err(_) => root.join(path),
+        };
+        Self {
+            home: rebase(self.home),
+            config_home: rebase(self.config_home),
+            config_dirs: self.config_dirs.into_iter().map(rebase).collect(),
+            data_home: rebase(self.data_home),
+            data_dirs: self.data_dirs.into_iter().map(rebase).collect(),
+            state_home: rebase(self.state_home),
+            current_desktops: self.current_desktops,
+            root: Some(root.to_path_buf()),
+        }
+    }
+
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    pub fn config_home(&self) -> &Path {
+        &self.config_home
+    }
+
+    pub fn config_dirs(&self) -> &[PathBuf] {
+        &self.config_dirs
+    }
+
+    pub fn data_home(&self) -> &Path {
+        &self.data_home
+    }
+
+    pub fn data_dirs(&self) -> &[PathBuf] {
+        &self.data_dirs
+    }
+
+    pub fn state_home(&self) -> &Path {
+        &self.state_home
+    }
+
+    pub fn current_desktops(&self) -> &[String] {
+        &self.current_desktops
+    }
+
+    /// The directory this environment was moved beneath with `Environment::with_root`, if any
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// True if `path` belongs to the user, e.g., is within their home or config dir
+    pub fn is_user_path<P>(&self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        path.starts_with(&self.home) || path.starts_with(&self.config_home)
+    }
+
+    /// Return `applications` in the data home and each data dir, whether or not they exist or contain desktop entries.
+    pub fn applications_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.data_home)
+            .chain(self.data_dirs.iter())
+            .map(|d| d.join("applications"))
+            .collect()
+    }
+
+    /// Return the application dirs, `applications` in the data home and each data dir, which contain desktop entries.
+    pub fn desktop_entry_dirs(&self) -> Vec<PathBuf> {
+        self.applications_dirs()
+            .into_iter()
+            .filter(|d| is_valid_desktop_entry_dir(d))
+            .collect()
+    }
+
+    /// Returns the path to the user mimeapps.list file, `mimeapps.list` in the config home.
+    /// If none exists, it will be created.
+    pub fn user_mimeapps_list_path(&self) -> Result<PathBuf, Error> {
+        let path = self.config_home.join("mimeapps.list");
+        if !path.exists() {
+            std::fs::create_dir_all(&self.config_home)
+                .map_err(|e| Error::io(&self.config_home, e))?;
+            File::create(&path).map_err(|e| Error::io(&path, e))?;
+        }
+
+        Ok(path)
+    }
+
+    /// Returns the path to the user's roles file, `mimeassoc/roles.conf` in the config home. The file need not exist.
+    pub fn user_roles_path(&self) -> PathBuf {
+        self.config_home.join("mimeassoc").join("roles.conf")
+    }
+
+    /// Returns the dir holding the snapshots of the user's mimeapps.list files, `mimeassoc/backups` in the state home.
+    /// The dir need not exist.
+    pub fn backups_dir(&self) -> PathBuf {
+        self.state_home.join("mimeassoc").join("backups")
+    }
+
+    /// Return the dirs which may hold mimeapps.list files, in lookup order: the config home, config dirs, and
+    /// `applications` in the data home and data dirs. They need not exist.
+    pub fn mimeapps_list_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.config_home.clone()];
+        dirs.extend(self.config_dirs.iter().cloned());
+        dirs.extend(self.applications_dirs());
+        dirs
+    }
+
+    /// Return the paths to the existing mimeapps.list files in the lookup order of the MIME Apps spec:
+    /// the config home, config dirs, and `applications` in the data home and data dirs, with each dir
+    /// contributing its `$desktop-mimeapps.list` files (for each current desktop) ahead of its `mimeapps.list`.
+    /// Legacy `defaults.list` files follow the `mimeapps.list` in their dir. The user's mimeapps.list is listed
+    /// even if missing, to be loaded as empty; it isn't created until saved, so merely reading another user's home,
+    /// or an image, writes nothing.
+    pub fn mimeapps_lists_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        let user_mimeapps_list_path = self.config_home.join("mimeapps.list");
+
+        Ok(
+            mimeapps_list_candidates(&self.mimeapps_list_dirs(), &self.current_desktops)
+                .into_iter()
+                .filter(|p| p.is_file() || *p == user_mimeapps_list_path)
+                .collect(),
+        )
+    }
+
+    /// Return `mime/packages` in the data dirs and data home, which hold mime info xml files, with the data home's
+    /// last, as it takes precedence. They need not exist.
+    pub fn mimeinfo_dirs(&self) -> Vec<PathBuf> {
+        self.data_dirs
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self.data_home))
+            .map(|d| d.join("mime").join("packages"))
+            .collect()
+    }
+
+    /// Return the paths of the mime info xml files in `mime/packages` of the data dirs and data home, to be
+    /// loaded by MimeTypeInfoStore. Since later sources take precedence, the data home's files come last.
+    pub fn mimeinfo_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut xml_paths = Vec::new();
+        for directory in self.mimeinfo_dirs() {
+            if !directory.is_dir() {
+                continue;
+            }
+
+            let contents = std::fs::read_dir(&directory).map_err(|e| Error::io(&directory, e))?;
+            let mut dir_paths = contents
+                .flatten()
+                .map(|file| file.path())
+                .filter(|file_path| has_extension(file_path, "xml"))
+                .collect::<Vec<_>>();
+            dir_paths.sort();
+            xml_paths.extend(dir_paths);
+        }
+
+        Ok(xml_paths)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(p: &str) -> PathBuf {
+        let cwd = std::env::current_dir().unwrap();
+        cwd.join(p)
+    }
+
+    fn test_environment() -> Environment {
+        Environment::for_home(path("test-data"))
+            .with_config_home(path("test-data/config"))
+            .with_config_dirs(vec![])
+            .with_data_home(path("test-data/local/share"))
+            .with_data_dirs(vec![path("test-data/usr/share")])
+            .with_state_home(path("test-data/state"))
+            .with_current_desktops(vec!["GNOME".to_string()])
+    }
+
+    #[test]
+    fn discovers_paths_within_environment() -> anyhow::Result<()> {
+        let environment = test_environment();
+        assert_eq!(environment.current_desktops(), ["GNOME"]);
+
+        assert_eq!(
+            environment.desktop_entry_dirs(),
+            [
+                path("test-data/local/share/applications"),
+                path("test-data/usr/share/applications")
+            ]
+        );
+
+        assert_eq!(
+            environment.mimeapps_lists_paths()?,
+            [
+                path("test-data/config/mimeapps.list"),
+                path("test-data/config/defaults.list"),
+                path("test-data/usr/share/applications/gnome-mimeapps.list"),
+                path("test-data/usr/share/applications/mimeapps.list"),
+            ]
+        );
+
+        let mimeinfo_paths = environment.mimeinfo_paths()?;
+        assert!(!mimeinfo_paths.is_empty());
+        assert!(mimeinfo_paths
+            .iter()
+            .all(|p| p.starts_with(path("test-data/usr/share/mime/packages"))));
+
+        assert_eq!(
+            environment.user_roles_path(),
+            path("test-data/config/mimeassoc/roles.conf")
+        );
+        assert_eq!(
+            environment.backups_dir(),
+            path("test-data/state/mimeassoc/backups")
+        );
+        assert!(environment.is_user_path(path("test-data/config/mimeapps.list")));
+        assert!(!environment.is_user_path("/usr/share/applications/mimeapps.list"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn home_uses_default_dirs_whatever_the_host() {
+        let environment = Environment::for_home("/home/user");
+        assert_eq!(environment.config_dirs(), [PathBuf::from("/etc/xdg")]);
+        assert_eq!(
+            environment.data_dirs(),
+            [
+                PathBuf::from("/usr/local/share"),
+                PathBuf::from("/usr/share")
+            ]
+        );
+        assert!(environment.current_desktops().is_empty());
+        assert!(Environment::for_root("/mnt/image", "/home/user")
+            .current_desktops()
+            .is_empty());
+    }
+
+    #[test]
+    fn home_creates_nothing() -> anyhow::Result<()> {
+        let home = std::env::temp_dir().join(format!("mimeassoc-home-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home)?;
+
+        // the user's mimeapps.list is listed, but not created until saved
+        let environment = Environment::for_home(&home);
+        let user_mimeapps_list_path = home.join(".config/mimeapps.list");
+        let paths = environment.mimeapps_lists_paths()?;
+        assert_eq!(paths.first(), Some(&user_mimeapps_list_path));
+        assert!(!home.join(".config").exists());
+
+        let mut store =
+            crate::MimeTypeAssociationStore::load_for_environment(&paths, &environment)?;
+        assert!(!user_mimeapps_list_path.exists());
+        let no_applications = crate::DesktopEntryStore::load::<PathBuf>(&[])?;
+        store.set_default_handlers_for_mime_type(
+            &crate::MimeType::parse("text/plain")?,
+            &[crate::DesktopEntryId::parse(
+                "org.gnome.TextEditor.desktop",
+            )?],
+            &no_applications,
+        )?;
+        store.save()?;
+        assert!(user_mimeapps_list_path.exists());
+
+        std::fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn root_moves_every_path() {
+        let environment = Environment::for_home("/home/user")
+            .with_config_dirs(vec![PathBuf::from("/etc/xdg")])
+            .with_data_dirs(vec![PathBuf::from("/usr/share")])
+            .with_root("/mnt/image");
+
+        assert_eq!(environment.home(), Path::new("/mnt/image/home/user"));
+        assert_eq!(
+            environment.config_home(),
+            Path::new("/mnt/image/home/user/.config")
+        );
+        assert_eq!(
+            environment.config_dirs(),
+            [PathBuf::from("/mnt/image/etc/xdg")]
+        );
+        assert_eq!(
+            environment.data_home(),
+            Path::new("/mnt/image/home/user/.local/share")
+        );
+        assert_eq!(
+            environment.data_dirs(),
+            [PathBuf::from("/mnt/image/usr/share")]
+        );
+        assert_eq!(
+            environment.state_home(),
+            Path::new("/mnt/image/home/user/.local/state")
+        );
+    }
+
+    #[test]
+    fn root_uses_default_dirs_and_creates_nothing() -> anyhow::Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("mimeassoc-environment-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let applications = root.join("usr/share/applications");
+        std::fs::create_dir_all(&applications)?;
+        std::fs::write(
+            applications.join("mimeapps.list"),
+            "[Default Applications]\ntext/html=firefox.desktop\n",
+        )?;
+
+        let environment = Environment::for_root(&root, "/home/user");
+        assert_eq!(environment.root(), Some(root.as_path()));
+        assert_eq!(environment.config_dirs(), [root.join("etc/xdg")]);
+        assert_eq!(
+            environment.data_dirs(),
+            [root.join("usr/local/share"), root.join("usr/share")]
+        );
+
+        // the user's mimeapps.list is listed, but not created, and loads as empty
+        let user_mimeapps_list_path = root.join("home/user/.config/mimeapps.list");
+        let paths = environment.mimeapps_lists_paths()?;
+        assert_eq!(
+            paths,
+            [
+                user_mimeapps_list_path.clone(),
+                applications.join("mimeapps.list")
+            ]
+        );
+        assert!(!root.join("home").exists());
+        crate::MimeTypeAssociationStore::load_for_environment(&paths, &environment)?;
+        assert!(!user_mimeapps_list_path.exists());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::{fmt::Display, path::PathBuf};
+
+use crate::{DesktopEntryId, MimeType};
+
+/// The errors produced by mimeassoc. Variants carry the offending path, line, mime type or
+/// desktop entry id, so callers can tell the failures apart without inspecting messages.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the file at `path` failed
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// An environment variable needed to locate the user's files, e.g., `$HOME`, is unset
+    MissingEnvironmentVariable(String),
+    /// The id is not a valid mime type, e.g., it lacks a `/`
+    InvalidMimeType(String),
+    /// The text is not a valid URL scheme, e.g., it begins with a digit
+    InvalidScheme(String),
+    /// The id is not a valid desktop entry id, e.g., it lacks the `.desktop` extension
+    InvalidDesktopEntryId(String),
+    /// Line `line` (1-based) of the file at `path` is malformed
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// A shared-mime-info `<match>` rule is malformed
+    InvalidMagic(String),
+    /// The file at `path` has no `[Desktop Entry]` group
+    NotADesktopEntry(PathBuf),
+    /// The desktop entry has no `Exec` key
+    MissingExec(DesktopEntryId),
+    /// The `Exec` command line is malformed, e.g., it has an unterminated quote, or no program
+    InvalidExec { exec: String, message: String },
+    /// The `Exec` command line accepts only local files, and none of the URIs passed were local
+    UnsupportedUris { exec: String, uris: Vec<String> },
+    /// The program of a desktop entry's `Exec` command line can't be found
+    ExecutableNotFound(PathBuf),
+    /// The program of a desktop entry's `Exec` command line exists, but is not executable
+    NotExecutable(PathBuf),
+    /// The desktop entry does not appear to be an installed, launchable application
+    InvalidApplication(DesktopEntryId),
+    /// The application does not support the mime type
+    UnsupportedMimeType {
+        desktop_entry: DesktopEntryId,
+        mime_type: MimeType,
+    },
+    /// The application supports none of the mime types of the role
+    UnsupportedRole {
+        desktop_entry: DesktopEntryId,
+        role: String,
+    },
+    /// An empty list of applications was provided for the mime type
+    NoDesktopEntries(MimeType),
+    /// A profile couldn't be parsed or written, e.g., it isn't valid JSON or TOML
+    InvalidProfile(String),
+    /// There is no user customizable mimeapps.list to make assignments in
+    NoWritableScope,
+    /// The mimeapps.list at `path` is not user customizable
+    ReadOnlyScope(PathBuf),
+    /// The mimeapps.list at `path` was changed by another program since it was loaded, and those
+    /// changes conflict with ours for `mime_types`
+    SaveConflict {
+        path: PathBuf,
+        mime_types: Vec<MimeType>,
+    },
+    /// Setting up or reading the file system watcher failed
+    Watch(std::io::Error),
+}
+
+impl Error {
+    pub(crate) fn io<P>(path: P, source: std::io::Error) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn parse<P>(path: P, line: usize, message: String) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::Parse {
+            path: path.into(),
+            line,
+            message,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{:?}: {}", path, source),
+            Self::MissingEnvironmentVariable(var) => {
+                write!(f, "Environment variable ${} is not set", var)
+            }
+            Self::InvalidMimeType(id) => write!(
+                f,
+                "A mimetype is expected to contain exactly one `/`. id: \"{}\" is invalid.",
+                id
+            ),
+            Self::InvalidScheme(scheme) => write!(f, "\"{}\" is not a valid URL scheme", scheme),
+            Self::InvalidDesktopEntryId(id) => {
+                write!(f, "id: \"{}\" not a valid Gnome .desktop file name", id)
+            }
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{:?}, line {}: {}", path, line, message),
+            Self::InvalidMagic(message) => write!(f, "Invalid magic match: {}", message),
+            Self::NotADesktopEntry(path) => write!(
+                f,
+                "DesktopEntry \"{:?}\" parsed but contained no [Desktop Entry] fields.",
+                path
+            ),
+            Self::MissingExec(id) => {
+                write!(
+                    f,
+                    "No executable command specified for desktop entry \"{}\"",
+                    id
+                )
+            }
+            Self::InvalidExec { exec, message } => {
+                write!(f, "Exec command line \"{}\" {}", exec, message)
+            }
+            Self::UnsupportedUris { exec, uris } => write!(
+                f,
+                "Exec command line \"{}\" is unable to open any of the provided URIs: {}",
+                exec,
+                uris.join(", ")
+            ),
+            Self::ExecutableNotFound(path) => write!(f, "Executable at {:?} is missing", path),
+            Self::NotExecutable(path) => write!(f, "Executable at {:?} is not executable", path),
+            Self::InvalidApplication(id) => write!(
+                f,
+                "DesktopEntry \"{}\" does not appear to be a valid launchable application",
+                id
+            ),
+            Self::UnsupportedMimeType {
+                desktop_entry,
+                mime_type,
+            } => write!(
+                f,
+                "DesktopEntry \"{}\" does not support mime type \"{}\"",
+                desktop_entry, mime_type
+            ),
+            Self::UnsupportedRole {
+                desktop_entry,
+                role,
+            } => write!(
+                f,
+                "DesktopEntry \"{}\" does not support any mime type of role \"{}\"",
+                desktop_entry, role
+            ),
+            Self::NoDesktopEntries(mime_type) => write!(
+                f,
+                "No DesktopEntryIds provided for mime type \"{}\"",
+                mime_type
+            ),
+            Self::InvalidProfile(message) => write!(f, "Invalid profile: {}", message),
+            Self::NoWritableScope => write!(f, "No customizable user scope available"),
+            Self::ReadOnlyScope(path) => {
+                write!(
+                    f,
+                    "MimeAssociationScope[{:?}] is not user customizable.",
+                    path
+                )
+            }
+            Self::SaveConflict { path, mime_types } => write!(
+                f,
+                "{:?} was changed by another program, conflicting with changes to: {}",
+                path,
+                mime_types
+                    .iter()
+                    .map(MimeType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Watch(source) => write!(f, "Unable to watch for changes: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Watch(source) => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::Display;
+
+/// A raw value from a key file such as a desktop entry, e.g., the `text after the =` in
+/// `Name=text after the =`, with typed accessors which decode it per the Desktop Entry spec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyFileValue<'a>(&'a str);
+
+impl<'a> KeyFileValue<'a> {
+    pub fn new(raw: &'a str) -> Self {
+        Self(raw)
+    }
+
+    /// The value exactly as it appears in the file
+    pub fn raw(&self) -> &'a str {
+        self.0
+    }
+
+    /// Decode a `string` or `localestring` value, expanding the `\s`, `\n`, `\t`, `\r` and `\\`
+    /// escapes. Unknown escapes are kept verbatim.
+    pub fn as_string(&self) -> String {
+        let mut result = String::with_capacity(self.0.len());
+        let mut chars = self.0.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('s') => result.push(' '),
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('r') => result.push('\r'),
+                Some('\\') => result.push('\\'),
+                Some(other) => {
+                    result.push('\\');
+                    result.push(other);
+                }
+                None => result.push('\\'),
+            }
+        }
+        result
+    }
+
+    /// Decode a `boolean` value. The spec only allows `true` and `false`, but like GKeyFile the
+    /// legacy `1` and `0` are accepted too. Returns None for anything else.
+    pub fn as_boolean(&self) -> Option<bool> {
+        match self.0.trim() {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn as_numeric(&self) -> Option<f64> {
+        self.0.trim().parse().ok()
+    }
+
+    /// Decode a list of `string` or `localestring` values separated by `;`. A `\;` is a literal
+    /// semicolon within an element, the trailing separator is optional, and empty elements are dropped.
+    pub fn as_string_list(&self) -> Vec<String> {
+        let mut elements = vec![];
+        let mut element = String::new();
+        let mut chars = self.0.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(';') => element.push(';'),
+                    Some(other) => {
+                        element.push('\\');
+                        element.push(other);
+                    }
+                    None => element.push('\\'),
+                },
+                ';' => elements.push(std::mem::take(&mut element)),
+                _ => element.push(c),
+            }
+        }
+        elements.push(element);
+
+        elements
+            .into_iter()
+            .map(|element| KeyFileValue(element.trim()).as_string())
+            .filter(|element| !element.is_empty())
+            .collect()
+    }
+}
+
+/// A POSIX locale of the form `lang_COUNTRY.ENCODING@MODIFIER`, where all but `lang` are optional,
+/// used to look up localized keys such as `Name[de_DE]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locale {
+    lang: String,
+    country: Option<String>,
+    modifier: Option<String>,
+}
+
+impl Locale {
+    /// Parse a locale such as `sr_YU.UTF-8@Latn`. The encoding is discarded, since it plays no
+    /// part in matching localized keys. Returns None for the `C` and `POSIX` locales, which have no translations.
+    pub fn parse(locale: &str) -> Option<Self> {
+        let locale = locale.trim();
+        let (locale, modifier) = match locale.split_once('@') {
+            Some((locale, modifier)) => (locale, Some(modifier)),
+            None => (locale, None),
+        };
+        let locale = locale.split('.').next().unwrap_or_default();
+        let (lang, country) = match locale.split_once('_') {
+            Some((lang, country)) => (lang, Some(country)),
+            None => (locale, None),
+        };
+
+        if lang.is_empty() || lang == "C" || lang == "POSIX" {
+            return None;
+        }
+
+        let non_empty = |s: Option<&str>| s.filter(|s| !s.is_empty()).map(str::to_string);
+        Some(Self {
+            lang: lang.to_string(),
+            country: non_empty(country),
+            modifier: non_empty(modifier),
+        })
+    }
+
+    /// The user's locale for messages, taken from the first non-empty of `$LC_ALL`, `$LC_MESSAGES` and `$LANG`
+    pub fn current() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+    }
+
+    /// The locale suffixes to try, most specific first, per the Desktop Entry spec's
+    /// matching rules: `lang_COUNTRY@MODIFIER`, `lang_COUNTRY`, `lang@MODIFIER`, `lang`.
+    pub fn fallbacks(&self) -> Vec<String> {
+        let mut fallbacks = vec![];
+        if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+            fallbacks.push(format!("{}_{}@{}", self.lang, country, modifier));
+        }
+        if let Some(country) = &self.country {
+            fallbacks.push(format!("{}_{}", self.lang, country));
+        }
+        if let Some(modifier) = &self.modifier {
+            fallbacks.push(format!("{}@{}", self.lang, modifier));
+        }
+        fallbacks.push(self.lang.clone());
+        fallbacks
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lang)?;
+        if let Some(country) = &self.country {
+            write!(f, "_{}", country)?;
+        }
+        if let Some(modifier) = &self.modifier {
+            write!(f, "@{}", modifier)?;
+        }
+        Ok(())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_string_escapes() {
+        assert_eq!(
+            KeyFileValue::new(r"a\sb\nc\td\re\\f").as_string(),
+            "a b\nc\td\re\\f"
+        );
+        assert_eq!(
+            KeyFileValue::new(r"keep\;this\").as_string(),
+            r"keep\;this\"
+        );
+    }
+
+    #[test]
+    fn decodes_booleans_and_numbers() {
+        assert_eq!(KeyFileValue::new("true").as_boolean(), Some(true));
+        assert_eq!(KeyFileValue::new("false").as_boolean(), Some(false));
+        assert_eq!(KeyFileValue::new("1").as_boolean(), Some(true));
+        assert_eq!(KeyFileValue::new("yes").as_boolean(), None);
+
+        assert_eq!(KeyFileValue::new("1.5").as_numeric(), Some(1.5));
+        assert_eq!(KeyFileValue::new("two").as_numeric(), None);
+    }
+
+    #[test]
+    fn decodes_string_lists() {
+        assert_eq!(
+            KeyFileValue::new("image/png;image/jpeg;").as_string_list(),
+            ["image/png", "image/jpeg"]
+        );
+        assert_eq!(
+            KeyFileValue::new(r"a\;b;c\sd;;e").as_string_list(),
+            ["a;b", "c d", "e"]
+        );
+        assert!(KeyFileValue::new("").as_string_list().is_empty());
+    }
+
+    #[test]
+    fn parses_locales() {
+        let locale = Locale::parse("sr_YU.UTF-8@Latn").unwrap();
+        assert_eq!(locale.to_string(), "sr_YU@Latn");
+        assert_eq!(locale.fallbacks(), ["sr_YU@Latn", "sr_YU", "sr@Latn", "sr"]);
+
+        assert_eq!(
+            Locale::parse("de_DE.UTF-8").unwrap().fallbacks(),
+            ["de_DE", "de"]
+        );
+        assert_eq!(Locale::parse("fr").unwrap().fallbacks(), ["fr"]);
+        assert_eq!(Locale::parse("C"), None);
+        assert_eq!(Locale::parse("C.UTF-8"), None);
+        assert_eq!(Locale::parse("POSIX"), None);
+    }
+}
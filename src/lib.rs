@@ -1,108 +1,258 @@
 use std::{
-    fs::File,
+    collections::HashSet,
+    fs::OpenOptions,
+    io::Write,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+pub mod backup;
+pub mod desired_state;
 pub mod desktop_entry;
+pub mod desktop_entry_exec;
 pub mod desktop_entry_store;
+pub mod desktop_entry_validation;
+pub mod environment;
+pub mod error;
+pub mod key_file;
+pub mod line_diff;
+pub mod lint;
 pub mod mime_type;
+mod mime_type_association_document;
 pub mod mime_type_association_store;
+pub mod mime_type_glob;
 pub mod mime_type_info;
-
+pub mod mime_type_magic;
+pub mod profile;
+pub mod resolution_trace;
+pub mod role;
+pub mod store_watcher;
+
+pub use backup::*;
+pub use desired_state::*;
 pub use desktop_entry::*;
+pub use desktop_entry_exec::*;
 pub use desktop_entry_store::*;
+pub use desktop_entry_validation::*;
+pub use environment::*;
+pub use error::*;
+pub use key_file::*;
+pub use line_diff::*;
+pub use lint::*;
 pub use mime_type::*;
 pub use mime_type_association_store::*;
+pub use mime_type_glob::*;
 pub use mime_type_info::*;
+pub use mime_type_magic::*;
+pub use profile::*;
+pub use resolution_trace::*;
+pub use role::*;
+pub use store_watcher::*;
 
 pub const LIB_LOG_DOMAIN: &str = "LibMimeAssoc";
 
-/// Return a vector of paths to the application dirs for the user.
-pub fn desktop_entry_dirs() -> anyhow::Result<Vec<PathBuf>> {
-    let xdg_data_dirs = std::env::var("XDG_DATA_DIRS")?
+/// Returns `$XDG_CONFIG_HOME`, defaulting to `$HOME/.config`
+pub fn xdg_config_home() -> Result<PathBuf, Error> {
+    Ok(Environment::from_process()?.config_home().to_path_buf())
+}
+
+/// Returns the entries of `$XDG_CONFIG_DIRS`, defaulting to `/etc/xdg`
+pub fn xdg_config_dirs() -> Vec<PathBuf> {
+    xdg_env_path_list("XDG_CONFIG_DIRS").unwrap_or_else(default_xdg_config_dirs)
+}
+
+/// The XDG Base Directory spec's default for `$XDG_CONFIG_DIRS`
+fn default_xdg_config_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from("/etc/xdg")]
+}
+
+/// Returns `$XDG_DATA_HOME`, defaulting to `$HOME/.local/share`
+pub fn xdg_data_home() -> Result<PathBuf, Error> {
+    Ok(Environment::from_process()?.data_home().to_path_buf())
+}
+
+/// Returns the entries of `$XDG_DATA_DIRS`, defaulting to `/usr/local/share:/usr/share`
+pub fn xdg_data_dirs() -> Vec<PathBuf> {
+    xdg_env_path_list("XDG_DATA_DIRS").unwrap_or_else(default_xdg_data_dirs)
+}
+
+/// The XDG Base Directory spec's default for `$XDG_DATA_DIRS`
+fn default_xdg_data_dirs() -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("/usr/local/share"),
+        PathBuf::from("/usr/share"),
+    ]
+}
+
+/// Returns the desktop names listed in `$XDG_CURRENT_DESKTOP`, in order, e.g., "ubuntu:GNOME" yields
+/// ["ubuntu", "GNOME"]. Case is preserved, since `OnlyShowIn` and `NotShowIn` are matched exactly; only
+/// `$desktop-mimeapps.list` file names are lowercased.
+pub fn xdg_current_desktops() -> Vec<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .map(|desktops| parse_current_desktops(&desktops))
+        .unwrap_or_default()
+}
+
+/// Read an absolute path from an environment variable. Per the XDG Base Directory spec,
+/// empty values are treated as unset, and relative paths are ignored.
+fn xdg_env_path(var: &str) -> Option<PathBuf> {
+    let value = std::env::var(var).ok()?;
+    let path = PathBuf::from(value);
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Read a colon-separated list of absolute paths from an environment variable,
+/// returning None if the variable is unset or yields no usable paths
+fn xdg_env_path_list(var: &str) -> Option<Vec<PathBuf>> {
+    let value = std::env::var(var).ok()?;
+    let paths = value
         .split(':')
         .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
         .collect::<Vec<_>>();
-    let user_data_dir = PathBuf::from(std::env::var("HOME")?).join(".local/share");
-    let mut data_dirs = vec![user_data_dir];
-    data_dirs.extend(xdg_data_dirs);
-
-    Ok(data_dirs
-        .iter()
-        .map(|d| d.join("applications"))
-        .filter(|d| is_valid_desktop_entry_dir(d))
-        .collect::<Vec<_>>())
+    if paths.is_empty() {
+        None
+    } else {
+        Some(paths)
+    }
+}
+
+fn parse_current_desktops(desktops: &str) -> Vec<String> {
+    desktops
+        .split(':')
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+/// Return a vector of paths to the application dirs for the user.
+/// See `Environment::desktop_entry_dirs`.
+pub fn desktop_entry_dirs() -> Result<Vec<PathBuf>, Error> {
+    Ok(Environment::from_process()?.desktop_entry_dirs())
 }
 
 fn is_valid_desktop_entry_dir<P>(path: P) -> bool
 where
     P: AsRef<Path>,
 {
-    let path = path.as_ref();
-    if path.exists() && path.is_dir() {
-        // verify this dir contains .desktop entries
-        if let Ok(contents) = std::fs::read_dir(path) {
-            for file in contents.flatten() {
-                if has_extension(file.path(), "desktop") {
-                    return true;
-                }
-            }
-        }
+    contains_desktop_entries(path.as_ref(), &mut HashSet::new())
+}
+
+/// True if `dir`, or one of its subdirs, contains .desktop entries. Dirs in `visited` aren't searched again.
+fn contains_desktop_entries(dir: &Path, visited: &mut HashSet<PathBuf>) -> bool {
+    if !dir.is_dir() || !visit_dir_once(dir, visited) {
+        return false;
     }
 
-    false
+    let Ok(contents) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    contents.flatten().any(|file| {
+        let file_path = file.path();
+        has_extension(&file_path, "desktop")
+            || (file_path.is_dir() && contains_desktop_entries(&file_path, visited))
+    })
 }
 
-/// Returns the path to the user mimeapps.list file. If none exists, it will be created.
-pub fn user_mimeapps_list_path() -> anyhow::Result<PathBuf> {
-    let path = PathBuf::from(std::env::var("HOME")?).join(".config/mimeapps.list");
-    if !path.exists() {
-        File::create(&path)?;
+/// Record `dir` in `visited` by its canonical path, returning false if it was already there. Walks of
+/// application dirs follow symlinked subdirs, which may lead back to a dir already walked, e.g., a link to `..`.
+fn visit_dir_once(dir: &Path, visited: &mut HashSet<PathBuf>) -> bool {
+    match dir.canonicalize() {
+        Ok(canonical_dir) => visited.insert(canonical_dir),
+        Err(_) => false,
     }
-
-    Ok(path)
 }
 
-/// Return a vector of paths to the mimeapps.list files for the user
-/// in system order
-pub fn mimeapps_lists_paths() -> anyhow::Result<Vec<PathBuf>> {
-    // ensure ~/.config/mimeapps.list exists
-    let _ = user_mimeapps_list_path()?;
+/// Returns the path to the user mimeapps.list file, `$XDG_CONFIG_HOME/mimeapps.list`.
+/// If none exists, it will be created.
+pub fn user_mimeapps_list_path() -> Result<PathBuf, Error> {
+    Environment::from_process()?.user_mimeapps_list_path()
+}
 
-    // ~/.config/mimeapps.list followed by the desktop_entry_dirs (with mimeapps.list appended), adding
-    // each that exists.
+/// Returns the path to the user's roles file, `$XDG_CONFIG_HOME/mimeassoc/roles.conf`, which
+/// defines roles in addition to the built-in ones. The file need not exist.
+pub fn user_roles_path() -> Result<PathBuf, Error> {
+    Ok(Environment::from_process()?.user_roles_path())
+}
 
-    let mut dirs = vec![PathBuf::from(std::env::var("HOME")?).join(".config")];
-    dirs.extend(desktop_entry_dirs()?);
+/// Return a vector of paths to the mimeapps.list files for the user
+/// in system order, following the lookup order of the MIME Apps spec:
+/// `$XDG_CONFIG_HOME`, `$XDG_CONFIG_DIRS`, `$XDG_DATA_HOME/applications` and
+/// `$XDG_DATA_DIRS/applications`, with each dir contributing its `$desktop-mimeapps.list`
+/// files (for each desktop in `$XDG_CURRENT_DESKTOP`) ahead of its `mimeapps.list`.
+/// Legacy `defaults.list` files follow the `mimeapps.list` in their dir.
+pub fn mimeapps_lists_paths() -> Result<Vec<PathBuf>, Error> {
+    Environment::from_process()?.mimeapps_lists_paths()
+}
 
-    let mut mimeapps_list_paths = vec![];
+/// Produce every candidate mimeapps.list path in lookup order for the given dirs and desktops,
+/// without checking whether they exist.
+fn mimeapps_list_candidates(dirs: &[PathBuf], desktops: &[String]) -> Vec<PathBuf> {
+    let mut candidates = vec![];
     for dir in dirs.iter() {
-        let mimeapps_list_path = dir.join("mimeapps.list");
-        if mimeapps_list_path.exists() && mimeapps_list_path.is_file() {
-            mimeapps_list_paths.push(mimeapps_list_path);
+        for desktop in desktops.iter() {
+            candidates.push(dir.join(format!("{}-mimeapps.list", desktop.to_lowercase())));
         }
-        let defaults_list_path = dir.join("defaults.list");
-        if defaults_list_path.exists() && defaults_list_path.is_file() {
-            mimeapps_list_paths.push(defaults_list_path);
+        candidates.push(dir.join("mimeapps.list"));
+        candidates.push(dir.join("defaults.list"));
+    }
+
+    // a dir may appear in more than one variable; only its first appearance counts
+    let mut unique = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if !unique.contains(&candidate) {
+            unique.push(candidate);
         }
     }
+    unique
+}
 
-    Ok(mimeapps_list_paths)
+/// Return a vector of paths to system mime info xml files, to be loaded by MimeInfoStore.
+/// See `Environment::mimeinfo_paths`.
+pub fn mimeinfo_paths() -> Result<Vec<PathBuf>, Error> {
+    Environment::from_process()?.mimeinfo_paths()
 }
 
-/// Return a vector of paths to system mime info xml files, to be loaded by MimeInfoStore
-pub fn mimeinfo_paths() -> anyhow::Result<Vec<PathBuf>> {
-    let directory = PathBuf::from("/usr/share/mime/packages");
-    let contents = std::fs::read_dir(directory)?;
-    let mut xml_paths = Vec::new();
-    for file in contents.flatten() {
-        let file_path = file.path();
-        if has_extension(&file_path, "xml") {
-            xml_paths.push(file_path);
-        }
+/// Makes temp file names unique among the writes of this process; the pid makes them unique among processes
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace the contents of the file at `path` atomically, by writing them to a uniquely named temp
+/// file beside it, then renaming that over it. Readers see the old contents or the new, never a mix.
+fn replace_file_contents(path: &Path, contents: &str) -> Result<(), Error> {
+    let temp_dir = path.parent().unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let temp_file_path = temp_dir.join(format!(
+        ".{}.mimeassoc-{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write = || -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_file_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    };
+    if let Err(e) = write() {
+        let _ = std::fs::remove_file(&temp_file_path);
+        return Err(Error::io(&temp_file_path, e));
     }
 
-    Ok(xml_paths)
+    if let Err(e) = std::fs::rename(&temp_file_path, path) {
+        let _ = std::fs::remove_file(&temp_file_path);
+        return Err(Error::io(path, e));
+    }
+    Ok(())
 }
 
 fn has_extension<P>(path: P, extension: &str) -> bool
@@ -175,6 +325,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parses_current_desktops() {
+        assert_eq!(parse_current_desktops("ubuntu:GNOME"), ["ubuntu", "GNOME"]);
+        assert_eq!(parse_current_desktops("KDE"), ["KDE"]);
+        assert!(parse_current_desktops("").is_empty());
+        assert!(parse_current_desktops(":").is_empty());
+    }
+
+    #[test]
+    fn mimeapps_list_candidates_follow_spec_order() {
+        let dirs = vec![
+            PathBuf::from("/home/user/.config"),
+            PathBuf::from("/etc/xdg"),
+            PathBuf::from("/usr/share/applications"),
+            PathBuf::from("/etc/xdg"),
+        ];
+        let desktops = vec!["ubuntu".to_string(), "GNOME".to_string()];
+
+        let candidates = mimeapps_list_candidates(&dirs, &desktops);
+        assert_eq!(
+            candidates,
+            [
+                "/home/user/.config/ubuntu-mimeapps.list",
+                "/home/user/.config/gnome-mimeapps.list",
+                "/home/user/.config/mimeapps.list",
+                "/home/user/.config/defaults.list",
+                "/etc/xdg/ubuntu-mimeapps.list",
+                "/etc/xdg/gnome-mimeapps.list",
+                "/etc/xdg/mimeapps.list",
+                "/etc/xdg/defaults.list",
+                "/usr/share/applications/ubuntu-mimeapps.list",
+                "/usr/share/applications/gnome-mimeapps.list",
+                "/usr/share/applications/mimeapps.list",
+                "/usr/share/applications/defaults.list",
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
     fn path(p: &str) -> PathBuf {
         let cwd = std::env::current_dir().unwrap();
         cwd.join(p)
@@ -200,7 +391,7 @@ mod tests {
         mime_associations: &'a MimeTypeAssociationStore,
         desktop_entries: &'a DesktopEntryStore,
     ) -> Option<&'a DesktopEntry> {
-        if let Some(desktop_entry_id) = mime_associations.default_application_for(&mime_type) {
+        if let Some(desktop_entry_id) = mime_associations.default_application_for(mime_type) {
             if let Some(desktop_entry) =
                 desktop_entries.find_desktop_entry_with_id(desktop_entry_id)
             {
@@ -212,7 +403,12 @@ mod tests {
 
     #[test]
     fn makes_correct_mime_to_desktop_associations() -> anyhow::Result<()> {
-        let mime_associations = MimeTypeAssociationStore::load(&test_mimeapps_lists_paths())?;
+        let environment = Environment::for_home(path("test-data/home"))
+            .with_config_home(path("test-data/config"));
+        let mime_associations = MimeTypeAssociationStore::load_for_environment(
+            &test_mimeapps_lists_paths(),
+            &environment,
+        )?;
         let desktop_entries = DesktopEntryStore::load(&test_desktop_entry_dirs())?;
 
         let text_plain = MimeType::parse("text/plain")?;
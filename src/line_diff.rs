@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+/// A line of a diff between two texts
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum LineChange {
+    Unchanged(String),
+    Removed(String),
+    Added(String),
+}
+
+impl LineChange {
+    pub fn line(&self) -> &str {
+        match self {
+            Self::Unchanged(line) | Self::Removed(line) | Self::Added(line) => line,
+        }
+    }
+
+    pub fn is_change(&self) -> bool {
+        !matches!(self, Self::Unchanged(_))
+    }
+}
+
+impl std::fmt::Display for LineChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unchanged(line) => write!(f, " {}", line),
+            Self::Removed(line) => write!(f, "-{}", line),
+            Self::Added(line) => write!(f, "+{}", line),
+        }
+    }
+}
+
+/// Compute the line by line changes which turn `old` into `new`, from their longest common
+/// subsequence of lines. Removals are listed before the additions which replace them.
+/// This is quadratic, which is fine for files the size of a mimeapps.list.
+pub fn line_diff(old: &str, new: &str) -> Vec<LineChange> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(LineChange::Unchanged(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            changes.push(LineChange::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            changes.push(LineChange::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    changes.extend(
+        old[i..]
+            .iter()
+            .map(|line| LineChange::Removed(line.to_string())),
+    );
+    changes.extend(
+        new[j..]
+            .iter()
+            .map(|line| LineChange::Added(line.to_string())),
+    );
+    changes
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_changed_added_and_removed_lines() {
+        let old = "[Default Applications]\ntext/html=firefox.desktop\nimage/png=gimp.desktop\n";
+        let new = "[Default Applications]\ntext/html=chromium.desktop\nimage/png=gimp.desktop\ntext/plain=gedit.desktop\n";
+
+        assert_eq!(
+            line_diff(old, new),
+            [
+                LineChange::Unchanged("[Default Applications]".to_string()),
+                LineChange::Removed("text/html=firefox.desktop".to_string()),
+                LineChange::Added("text/html=chromium.desktop".to_string()),
+                LineChange::Unchanged("image/png=gimp.desktop".to_string()),
+                LineChange::Added("text/plain=gedit.desktop".to_string()),
+            ]
+        );
+
+        assert!(line_diff(old, old).iter().all(|change| !change.is_change()));
+        assert_eq!(line_diff("a\n", ""), [LineChange::Removed("a".to_string())]);
+    }
+}
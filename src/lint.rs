@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{DesktopEntryId, MimeType};
+
+/// How serious a problem found by `MimeTypeAssociationStore::lint` is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum LintSeverity {
+    /// The line is ignored, e.g., it can't be parsed
+    Error,
+    /// The line has an effect, but probably not the intended one
+    Warning,
+    /// Worth knowing, but commonplace, e.g., a mimeapps.list naming an application which isn't installed as a
+    /// fallback for one which is
+    Info,
+}
+
+/// A problem found in a mimeapps.list
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum LintProblem {
+    /// The line isn't a section header, comment, or `mime/type=application.desktop;` entry, or it's a
+    /// `[Default Applications]` entry without applications
+    MalformedLine { text: String },
+    /// A later entry for the same mime type in the same section, possibly under a repeated section header,
+    /// replaces this one
+    DuplicateKey {
+        mime_type: MimeType,
+        overridden_by: usize,
+    },
+    /// The key is an alias, which lookups of the canonical mime type don't consult
+    AliasKey {
+        mime_type: MimeType,
+        canonical: MimeType,
+    },
+    /// The application isn't installed
+    NotInstalled {
+        mime_type: MimeType,
+        desktop_entry: DesktopEntryId,
+    },
+    /// The application doesn't declare it can open the mime type
+    UndeclaredMimeType {
+        mime_type: MimeType,
+        desktop_entry: DesktopEntryId,
+    },
+}
+
+impl std::fmt::Display for LintProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedLine { text } => write!(f, "\"{}\" can't be parsed", text),
+            Self::DuplicateKey {
+                mime_type,
+                overridden_by,
+            } => write!(
+                f,
+                "{} is overridden by the entry on line {}",
+                mime_type, overridden_by
+            ),
+            Self::AliasKey {
+                mime_type,
+                canonical,
+            } => write!(f, "{} is an alias of {}", mime_type, canonical),
+            Self::NotInstalled {
+                mime_type,
+                desktop_entry,
+            } => write!(
+                f,
+                "{} is assigned to {}, but isn't installed",
+                desktop_entry, mime_type
+            ),
+            Self::UndeclaredMimeType {
+                mime_type,
+                desktop_entry,
+            } => write!(
+                f,
+                "{} is assigned to {}, but doesn't declare support for it",
+                desktop_entry, mime_type
+            ),
+        }
+    }
+}
+
+/// A change which resolves a `LintProblem`, applied by `MimeTypeAssociationStore::fix_lint`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum LintFix {
+    /// Delete the line
+    RemoveLine,
+    /// Remove the application from the entry, deleting the entry if no applications remain
+    RemoveApplication { desktop_entry: DesktopEntryId },
+    /// Move the entry's applications to the entry for `to`, after those it already lists
+    RenameKey { to: MimeType },
+}
+
+impl std::fmt::Display for LintFix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RemoveLine => write!(f, "remove the line"),
+            Self::RemoveApplication { desktop_entry } => {
+                write!(f, "remove {} from the entry", desktop_entry)
+            }
+            Self::RenameKey { to } => write!(f, "move the entry to {}", to),
+        }
+    }
+}
+
+/// A problem found on a line of a mimeapps.list, and the suggested fix, if any
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LintDiagnostic {
+    pub path: PathBuf,
+    pub is_user_customizable: bool,
+    /// The section the line belongs to, e.g., "Default Applications"
+    pub section: String,
+    /// 1-based
+    pub line: usize,
+    pub severity: LintSeverity,
+    pub problem: LintProblem,
+    /// None if the line is best left as it is, e.g., it names an uninstalled fallback, which is portable
+    pub fix: Option<LintFix>,
+}
+
+impl LintDiagnostic {
+    /// True if `fix_lint` can apply the fix, i.e., there's a fix, and the file is the user's to edit
+    pub fn is_fixable(&self) -> bool {
+        self.is_user_customizable && self.fix.is_some()
+    }
+}
@@ -1,17 +1,19 @@
 use serde::Serialize;
 use std::fmt::Display;
 
+use crate::Error;
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
 pub struct MimeType(String);
 
 impl MimeType {
-    pub fn parse(id: &str) -> anyhow::Result<Self> {
+    /// The major type of the pseudo mime types used to assign URL scheme handlers, e.g., `x-scheme-handler/mailto`
+    pub const SCHEME_HANDLER_MAJOR_TYPE: &'static str = "x-scheme-handler";
+
+    pub fn parse(id: &str) -> Result<Self, Error> {
         let components = id.split('/').collect::<Vec<_>>();
         if components.len() != 2 {
-            anyhow::bail!(
-                "A mimetype is expected to contain exactly one `/`. id: \"{}\" is invalid.",
-                id
-            )
+            return Err(Error::InvalidMimeType(id.to_string()));
         }
         Ok(Self(id.to_string()))
     }
@@ -30,11 +32,49 @@ impl MimeType {
         &self.0[slash_pos + 1..self.0.len()]
     }
 
+    /// Returns the pseudo mime type which assigns handlers for URLs of `scheme`, e.g., `mailto` becomes
+    /// `x-scheme-handler/mailto`. Schemes are case-insensitive, so are lowercased. A trailing `:`, as in
+    /// `mailto:`, is ignored.
+    pub fn for_scheme(scheme: &str) -> Result<Self, Error> {
+        let scheme = scheme.trim().trim_end_matches(':').to_lowercase();
+        let mut chars = scheme.chars();
+        let is_valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        if !is_valid {
+            return Err(Error::InvalidScheme(scheme));
+        }
+
+        Ok(Self(format!(
+            "{}/{}",
+            Self::SCHEME_HANDLER_MAJOR_TYPE,
+            scheme
+        )))
+    }
+
+    /// True if this is a URL scheme handler pseudo mime type, e.g., `x-scheme-handler/https`
+    pub fn is_scheme_handler(&self) -> bool {
+        self.major_type() == Self::SCHEME_HANDLER_MAJOR_TYPE && !self.is_minor_type_wildcard()
+    }
+
+    /// The URL scheme this pseudo mime type assigns handlers for, e.g., `https` for `x-scheme-handler/https`
+    pub fn scheme(&self) -> Option<&str> {
+        if self.is_scheme_handler() {
+            Some(self.minor_type())
+        } else {
+            None
+        }
+    }
+
     /// True if the minor type is `*`, e.g., `image/*`
     pub fn is_minor_type_wildcard(&self) -> bool {
         self.minor_type() == "*"
     }
 
+    /// The wildcard matching every mime type of this major type, e.g., `image/*` for `image/png`
+    pub fn wildcard(&self) -> Self {
+        Self(format!("{}/*", self.major_type()))
+    }
+
     /// True if this MimeType has a wildcard minor type, and the passed-in MimeType matches
     pub fn wildcard_match(&self, other: &MimeType) -> bool {
         if self.is_minor_type_wildcard() {
@@ -84,6 +124,32 @@ mod tests {
 
         assert!(image_star.wildcard_match(&image_png));
         assert!(!image_png.wildcard_match(&image_star));
+        assert_eq!(image_png.wildcard(), image_star);
+        assert_eq!(image_star.wildcard(), image_star);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mime_type_scheme_helpers_work() -> anyhow::Result<()> {
+        let mailto = MimeType::for_scheme("mailto")?;
+        assert_eq!(mailto.id(), "x-scheme-handler/mailto");
+        assert!(mailto.is_scheme_handler());
+        assert_eq!(mailto.scheme(), Some("mailto"));
+
+        assert_eq!(MimeType::for_scheme("HTTPS:")?.scheme(), Some("https"));
+        assert_eq!(
+            MimeType::for_scheme("web+mastodon")?.id(),
+            "x-scheme-handler/web+mastodon"
+        );
+        assert!(MimeType::for_scheme("").is_err());
+        assert!(MimeType::for_scheme("1abc").is_err());
+        assert!(MimeType::for_scheme("not/a/scheme").is_err());
+
+        let image_png = MimeType::parse("image/png")?;
+        assert!(!image_png.is_scheme_handler());
+        assert_eq!(image_png.scheme(), None);
+        assert!(!MimeType::parse("x-scheme-handler/*")?.is_scheme_handler());
 
         Ok(())
     }
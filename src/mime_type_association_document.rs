@@ -0,0 +1,304 @@
+/// A single line of a mimeapps.list file, retaining its original text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum DocumentLine {
+    /// A `[Group Name]` header; `name` is the text between the brackets
+    Section { name: String, raw: String },
+    /// A `key=value` line belonging to the most recent section, if any
+    Entry {
+        section: Option<String>,
+        key: String,
+        raw: String,
+    },
+    /// Comments, blank lines, and anything else which is passed through untouched
+    Other(String),
+}
+
+impl DocumentLine {
+    fn parse(line: &str, section: Option<&str>) -> Self {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            return Self::Section {
+                name: trimmed[1..trimmed.len() - 1].to_string(),
+                raw: line.to_string(),
+            };
+        }
+
+        if !trimmed.starts_with('#') {
+            if let Some((key, _)) = trimmed.split_once('=') {
+                return Self::Entry {
+                    section: section.map(str::to_string),
+                    key: key.trim().to_string(),
+                    raw: line.to_string(),
+                };
+            }
+        }
+
+        Self::Other(line.to_string())
+    }
+
+    fn raw(&self) -> &str {
+        match self {
+            Self::Section { raw, .. } => raw,
+            Self::Entry { raw, .. } => raw,
+            Self::Other(raw) => raw,
+        }
+    }
+
+    fn is_entry(&self, section: &str, key: &str) -> bool {
+        matches!(self, Self::Entry { section: Some(s), key: k, .. } if s == section && k == key)
+    }
+
+    fn is_in_section(&self, section: &str) -> bool {
+        match self {
+            Self::Section { name, .. } => name == section,
+            Self::Entry {
+                section: Some(s), ..
+            } => s == section,
+            _ => false,
+        }
+    }
+}
+
+/// A line-oriented model of a mimeapps.list file which preserves comments, blank lines, ordering,
+/// unknown sections and the exact text of every line it isn't asked to change, so that writing
+/// it back out after an edit produces a minimal diff.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct MimeTypeAssociationDocument {
+    lines: Vec<DocumentLine>,
+    has_trailing_newline: bool,
+}
+
+impl MimeTypeAssociationDocument {
+    pub(crate) fn parse(text: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut section: Option<String> = None;
+        for line in text.lines() {
+            let line = DocumentLine::parse(line, section.as_deref());
+            if let DocumentLine::Section { name, .. } = &line {
+                section = Some(name.clone());
+            }
+            lines.push(line);
+        }
+
+        Self {
+            lines,
+            has_trailing_newline: text.is_empty() || text.ends_with('\n'),
+        }
+    }
+
+    /// Returns the raw value of the last entry for `key` in `section`, if any. Like GKeyFile,
+    /// later duplicates of a key override earlier ones.
+    pub(crate) fn value(&self, section: &str, key: &str) -> Option<&str> {
+        self.lines
+            .iter()
+            .rev()
+            .find(|line| line.is_entry(section, key))
+            .and_then(|line| line.raw().split_once('='))
+            .map(|(_, value)| value.trim())
+    }
+
+    /// Returns the 1-based line number of the entry `value` reads for `key` in `section`, if any
+    pub(crate) fn line_number(&self, section: &str, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .rposition(|line| line.is_entry(section, key))
+            .map(|index| index + 1)
+    }
+
+    /// Returns the keys of the entries in `section`, in document order, without duplicates
+    pub(crate) fn keys(&self, section: &str) -> Vec<&str> {
+        let mut keys: Vec<&str> = Vec::new();
+        for line in self.lines.iter() {
+            if let DocumentLine::Entry {
+                section: Some(s),
+                key,
+                ..
+            } = line
+            {
+                if s == section && !keys.contains(&key.as_str()) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+
+    /// Set the entry for `key` in `section` to `line`, a complete `key=value` line. An existing entry
+    /// is rewritten in place; a new entry is placed after the last entry of the section, creating the
+    /// section at the end of the document if needed.
+    pub(crate) fn set(&mut self, section: &str, key: &str, line: &str) {
+        let new_line = DocumentLine::Entry {
+            section: Some(section.to_string()),
+            key: key.to_string(),
+            raw: line.to_string(),
+        };
+
+        if let Some(index) = self.lines.iter().rposition(|l| l.is_entry(section, key)) {
+            self.lines[index] = new_line;
+            return;
+        }
+
+        if let Some(index) = self.lines.iter().rposition(|l| l.is_in_section(section)) {
+            self.lines.insert(index + 1, new_line);
+            return;
+        }
+
+        if self
+            .lines
+            .last()
+            .is_some_and(|line| !line.raw().trim().is_empty())
+        {
+            self.lines.push(DocumentLine::Other(String::new()));
+        }
+        self.lines.push(DocumentLine::Section {
+            name: section.to_string(),
+            raw: format!("[{}]", section),
+        });
+        self.lines.push(new_line);
+    }
+
+    /// Delete every entry for `key` in `section`. Returns true if any were removed.
+    pub(crate) fn remove(&mut self, section: &str, key: &str) -> bool {
+        let count = self.lines.len();
+        self.lines.retain(|line| !line.is_entry(section, key));
+        self.lines.len() != count
+    }
+
+    /// Delete the lines with the 1-based `line_numbers`, e.g., as reported by `line_number`. Returns those removed.
+    pub(crate) fn remove_lines(&mut self, line_numbers: &[usize]) -> Vec<usize> {
+        let mut removed = vec![];
+        let mut line_number = 0;
+        self.lines.retain(|_| {
+            line_number += 1;
+            let remove = line_numbers.contains(&line_number);
+            if remove {
+                removed.push(line_number);
+            }
+            !remove
+        });
+        removed
+    }
+}
+
+impl std::fmt::Display for MimeTypeAssociationDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", line.raw())?;
+        }
+        if self.has_trailing_newline && !self.lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "# managed by dotfiles\n\
+        [Default Applications]\n\
+        image/png=org.gimp.GIMP.desktop;\n\
+        \n\
+        # browsers\n\
+        text/html=firefox.desktop\n\
+        \n\
+        [X-Vendor Extension]\n\
+        Something=else\n";
+
+    #[test]
+    fn unmodified_document_roundtrips_exactly() {
+        let document = MimeTypeAssociationDocument::parse(INPUT);
+        assert_eq!(document.to_string(), INPUT);
+
+        let no_trailing_newline = "[Default Applications]\ntext/html=firefox.desktop";
+        let document = MimeTypeAssociationDocument::parse(no_trailing_newline);
+        assert_eq!(document.to_string(), no_trailing_newline);
+    }
+
+    #[test]
+    fn reads_values_by_section() {
+        let document = MimeTypeAssociationDocument::parse(INPUT);
+        assert_eq!(
+            document.value("Default Applications", "image/png"),
+            Some("org.gimp.GIMP.desktop;")
+        );
+        assert_eq!(
+            document.keys("Default Applications"),
+            ["image/png", "text/html"]
+        );
+        assert_eq!(document.keys("X-Vendor Extension"), ["Something"]);
+        assert_eq!(document.value("Added Associations", "image/png"), None);
+        assert_eq!(
+            document.line_number("Default Applications", "text/html"),
+            Some(6)
+        );
+        assert_eq!(
+            document.line_number("X-Vendor Extension", "text/html"),
+            None
+        );
+    }
+
+    #[test]
+    fn edits_touch_only_affected_lines() {
+        let mut document = MimeTypeAssociationDocument::parse(INPUT);
+
+        // rewrite in place
+        document.set(
+            "Default Applications",
+            "text/html",
+            "text/html=chromium.desktop",
+        );
+        // insert after the last entry of the section, before the trailing blank line
+        document.set(
+            "Default Applications",
+            "image/bmp",
+            "image/bmp=photopea.desktop",
+        );
+        // remove
+        assert!(document.remove("Default Applications", "image/png"));
+        assert!(!document.remove("Default Applications", "image/png"));
+        // remove by line number
+        document.remove_lines(&[1]);
+        // create a new section at the end
+        document.set(
+            "Added Associations",
+            "image/bmp",
+            "image/bmp=photopea.desktop;",
+        );
+
+        assert_eq!(
+            document.to_string(),
+            "[Default Applications]\n\
+            \n\
+            # browsers\n\
+            text/html=chromium.desktop\n\
+            image/bmp=photopea.desktop\n\
+            \n\
+            [X-Vendor Extension]\n\
+            Something=else\n\
+            \n\
+            [Added Associations]\n\
+            image/bmp=photopea.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn sets_into_empty_document() {
+        let mut document = MimeTypeAssociationDocument::parse("");
+        document.set(
+            "Default Applications",
+            "text/html",
+            "text/html=firefox.desktop",
+        );
+        assert_eq!(
+            document.to_string(),
+            "[Default Applications]\ntext/html=firefox.desktop\n"
+        );
+    }
+}
@@ -1,18 +1,28 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    io::{self, BufRead, Write},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fs::{File, Metadata},
+    hash::{Hash, Hasher},
+    io::Read,
     ops::Deref,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use crate::{DesktopEntryStore, MimeType};
+use crate::mime_type_association_document::MimeTypeAssociationDocument;
+use crate::{
+    lookup_desktop_entry, replace_file_contents, BackupStore, CandidateOrigin, DesiredState,
+    DesktopEntryStore, Environment, Error, FallbackTraceStep, LintDiagnostic, LintFix, LintProblem,
+    LintSeverity, MimeType, MimeTypeInfoStore, Plan, PlannedChange, Profile, ProfileIdentifiers,
+    ProfileImport, ProfileSection, ResolutionTrace, Role, RoleHandlers, RoleStore, ScopeTraceStep,
+    SkipReason, SkippedProfileEntry, TraceStep, TracedApplication, TracedOutcome,
+};
 
 use super::desktop_entry::{DesktopEntry, DesktopEntryId};
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum MimeTypeAssociationsSections {
     AddedAssociations,
+    RemovedAssociations,
     DefaultApplications,
 }
 
@@ -21,6 +31,8 @@ impl MimeTypeAssociationsSections {
         let desc = desc.trim();
         if desc == "[Added Associations]" {
             Some(Self::AddedAssociations)
+        } else if desc == "[Removed Associations]" {
+            Some(Self::RemovedAssociations)
         } else if desc == "[Default Applications]" {
             Some(Self::DefaultApplications)
         } else {
@@ -28,10 +40,59 @@ impl MimeTypeAssociationsSections {
         }
     }
 
-    fn to_string(&self) -> &'static str {
+    /// The section with the group name `name`, e.g. "Added Associations"
+    fn from_name(name: &str) -> Option<Self> {
+        Self::try_parse(&format!("[{}]", name))
+    }
+
+    /// The group name, without brackets, e.g. "Added Associations"
+    fn name(&self) -> &'static str {
         match self {
-            MimeTypeAssociationsSections::AddedAssociations => "[Added Associations]",
-            MimeTypeAssociationsSections::DefaultApplications => "[Default Applications]",
+            MimeTypeAssociationsSections::AddedAssociations => "Added Associations",
+            MimeTypeAssociationsSections::RemovedAssociations => "Removed Associations",
+            MimeTypeAssociationsSections::DefaultApplications => "Default Applications",
+        }
+    }
+
+    /// Association lists are written with a trailing `;` as GIO does, default applications are not
+    fn has_trailing_semicolon(&self) -> bool {
+        !matches!(self, MimeTypeAssociationsSections::DefaultApplications)
+    }
+}
+
+/// A line of one of the known sections of a mimeapps.list
+enum ScopeLine<'a> {
+    Entry {
+        section: MimeTypeAssociationsSections,
+        line: usize,
+        mime_type: MimeType,
+        ids: Vec<DesktopEntryId>,
+    },
+    /// A line which isn't a comment or a valid entry
+    Malformed {
+        section: MimeTypeAssociationsSections,
+        line: usize,
+        text: &'a str,
+    },
+}
+
+/// Identifies the state of a mimeapps.list on disk, so that changes made by other programs since
+/// it was loaded, e.g., GNOME Settings or `xdg-mime`, can be detected before saving over them.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct FileFingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileFingerprint {
+    fn new(metadata: &Metadata, contents: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hasher.finish(),
         }
     }
 }
@@ -42,79 +103,421 @@ pub struct MimeTypeAssociationScope {
     is_user_customizable: bool,
     is_dirty: bool,
     added_associations: HashMap<MimeType, Vec<DesktopEntryId>>,
-    default_applications: HashMap<MimeType, DesktopEntryId>,
+    removed_associations: HashMap<MimeType, Vec<DesktopEntryId>>,
+    // each mime type maps to an ordered preference list; the first installed application wins
+    default_applications: HashMap<MimeType, Vec<DesktopEntryId>>,
+    // the file as loaded, so that saving preserves comments, ordering and unknown sections; edited by lint fixes
+    document: MimeTypeAssociationDocument,
+    // the file as loaded or last saved, unedited, as the base of merging other programs' changes
+    base: MimeTypeAssociationDocument,
+    // the state of the file when loaded or last saved, to detect edits by other programs
+    fingerprint: Option<FileFingerprint>,
 }
 
 impl MimeTypeAssociationScope {
-    fn load<P>(mimeapps_file_path: P) -> anyhow::Result<Self>
+    fn load<P>(mimeapps_file_path: P, environment: &Environment) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
         let mimeapps_file_path = mimeapps_file_path.as_ref();
         log::info!("MimeAssociationScope::load {:?}", mimeapps_file_path);
 
-        let mimeapps_file = File::open(mimeapps_file_path)?;
-        let permissions = mimeapps_file.metadata()?.permissions();
-        let line_buffer = io::BufReader::new(mimeapps_file).lines();
+        let (contents, metadata) = match Self::read(mimeapps_file_path) {
+            Ok(result) => result,
+            // a user's mimeapps.list which doesn't exist yet, e.g., beneath a root, is empty until saved
+            Err(Error::Io { source, .. })
+                if source.kind() == std::io::ErrorKind::NotFound
+                    && environment.is_user_path(mimeapps_file_path) =>
+            {
+                let mut scope = Self::parse(mimeapps_file_path, "");
+                scope.is_user_customizable = true;
+                return Ok(scope);
+            }
+            Err(e) => return Err(e),
+        };
+        let mut scope = Self::parse(mimeapps_file_path, &contents);
+
+        // This file is user customizable iff it's in the user's dir (or their config dir, which
+        // $XDG_CONFIG_HOME may place elsewhere) and writable
+        scope.is_user_customizable =
+            environment.is_user_path(mimeapps_file_path) && !metadata.permissions().readonly();
+        scope.fingerprint = Some(FileFingerprint::new(&metadata, &contents));
+
+        Ok(scope)
+    }
+
+    /// The applications this scope assigns to `mime_type`, in preference order: those of its own entry, then those
+    /// of its wildcard entry, e.g., `image/*` for image/png, as a lower precedence match
+    fn default_applications_for<'a>(
+        &'a self,
+        mime_type: &MimeType,
+    ) -> impl Iterator<Item = &'a DesktopEntryId> {
+        let wildcard = Some(mime_type.wildcard()).filter(|wildcard| wildcard != mime_type);
+        self.default_applications
+            .get(mime_type)
+            .into_iter()
+            .chain(wildcard.and_then(|wildcard| self.default_applications.get(&wildcard)))
+            .flatten()
+    }
+
+    /// Read the contents and metadata of the file at `path`
+    fn read(path: &Path) -> Result<(String, Metadata), Error> {
+        let io_error = |e| Error::io(path, e);
+        let mut file = File::open(path).map_err(io_error)?;
+        let metadata = file.metadata().map_err(io_error)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(io_error)?;
+        Ok((contents, metadata))
+    }
+
+    /// Parse the `contents` of the mimeapps.list at `mimeapps_file_path`. The scope isn't user customizable.
+    /// Lines which can't be parsed are skipped, as GIO does; `MimeTypeAssociationStore::lint` reports them.
+    fn parse(mimeapps_file_path: &Path, contents: &str) -> Self {
         let mut added_associations = HashMap::new();
+        let mut removed_associations = HashMap::new();
         let mut default_applications = HashMap::new();
-        let mut current_section: Option<MimeTypeAssociationsSections> = None;
 
-        for line in line_buffer.flatten() {
-            if let Some(section) = MimeTypeAssociationsSections::try_parse(&line) {
-                // catch [Section] directives in the list
-                current_section = Some(section);
-            } else if let Some(current_section) = &current_section {
-                // if we have a current section, we can add associations to it.
-                let trimmed_line = line.trim();
-
-                if let Ok((mime_type, id)) = Self::parse_line(trimmed_line) {
-                    match current_section {
-                        MimeTypeAssociationsSections::AddedAssociations => {
-                            added_associations.insert(mime_type, id);
+        for line in Self::parse_lines(contents) {
+            match line {
+                ScopeLine::Entry {
+                    section,
+                    mime_type,
+                    ids,
+                    ..
+                } => {
+                    let associations = match section {
+                        MimeTypeAssociationsSections::AddedAssociations => &mut added_associations,
+                        MimeTypeAssociationsSections::RemovedAssociations => {
+                            &mut removed_associations
                         }
                         MimeTypeAssociationsSections::DefaultApplications => {
-                            if let Some(id) = id.first() {
-                                default_applications.insert(mime_type, id.clone());
-                            } else {
-                                anyhow::bail!(
-                                    "Line \"{}\" specified 0 DesktopEntryIds",
-                                    trimmed_line
-                                );
-                            }
+                            &mut default_applications
                         }
                     };
-                } else if !trimmed_line.starts_with('#') && !trimmed_line.is_empty() {
-                    // this line is not a section directive, MimeAssociation, or comment
-                    anyhow::bail!(
-                        "Unable to parse MimeAssociation from line: \"{}\"",
-                        trimmed_line
+                    associations.insert(mime_type, ids);
+                }
+                ScopeLine::Malformed { line, text, .. } => {
+                    log::warn!(
+                        "MimeAssociationScope::parse - skipping line {} of {:?}: \"{}\"",
+                        line,
+                        mimeapps_file_path,
+                        text
                     );
                 }
             }
         }
 
-        // This file is user customizable iff it's in the user's dir and writable
-        let home_dir = PathBuf::from(std::env::var("HOME")?);
-        let is_user_customizable =
-            mimeapps_file_path.starts_with(home_dir) && !permissions.readonly();
-
-        Ok(MimeTypeAssociationScope {
+        MimeTypeAssociationScope {
             file_path: PathBuf::from(mimeapps_file_path),
-            is_user_customizable,
+            is_user_customizable: false,
             is_dirty: false,
             added_associations,
+            removed_associations,
             default_applications,
-        })
+            document: MimeTypeAssociationDocument::parse(contents),
+            base: MimeTypeAssociationDocument::parse(contents),
+            fingerprint: None,
+        }
+    }
+
+    /// Classify the lines of the known sections of `contents`; comments, blank lines, and the lines of
+    /// unknown sections are skipped.
+    fn parse_lines(contents: &str) -> Vec<ScopeLine<'_>> {
+        let mut lines = vec![];
+        let mut current_section: Option<MimeTypeAssociationsSections> = None;
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let trimmed_line = line.trim();
+            if trimmed_line.starts_with('[') {
+                // catch [Section] directives in the list; unknown sections are skipped
+                current_section = MimeTypeAssociationsSections::try_parse(line);
+                continue;
+            }
+
+            let Some(section) = current_section else {
+                continue;
+            };
+            if trimmed_line.starts_with('#') || trimmed_line.is_empty() {
+                continue;
+            }
+
+            match Self::parse_line(trimmed_line) {
+                // a default application entry must name at least one application
+                Ok((mime_type, ids))
+                    if !ids.is_empty()
+                        || section != MimeTypeAssociationsSections::DefaultApplications =>
+                {
+                    lines.push(ScopeLine::Entry {
+                        section,
+                        line: line_index + 1,
+                        mime_type,
+                        ids,
+                    })
+                }
+                _ => lines.push(ScopeLine::Malformed {
+                    section,
+                    line: line_index + 1,
+                    text: trimmed_line,
+                }),
+            }
+        }
+
+        lines
+    }
+
+    /// Check the file this scope was loaded from for lines which are ignored, or which likely don't do what
+    /// was intended, e.g., assign an application which isn't installed
+    fn lint(
+        &self,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> Vec<LintDiagnostic> {
+        let contents = self.document.to_string();
+        let lines = Self::parse_lines(&contents);
+        let diagnostic = |section: MimeTypeAssociationsSections,
+                          line: usize,
+                          severity: LintSeverity,
+                          problem: LintProblem,
+                          fix: Option<LintFix>| LintDiagnostic {
+            path: self.file_path.clone(),
+            is_user_customizable: self.is_user_customizable,
+            section: section.name().to_string(),
+            line,
+            severity,
+            problem,
+            fix,
+        };
+
+        // like GKeyFile, the last entry for a mime type in a section is the one read
+        let mut last_lines = HashMap::new();
+        for scope_line in lines.iter() {
+            if let ScopeLine::Entry {
+                section,
+                line,
+                mime_type,
+                ..
+            } = scope_line
+            {
+                last_lines.insert((*section, mime_type), *line);
+            }
+        }
+
+        let mut diagnostics = vec![];
+        for scope_line in lines.iter() {
+            let (section, line, mime_type, ids) = match scope_line {
+                ScopeLine::Malformed {
+                    section,
+                    line,
+                    text,
+                } => {
+                    diagnostics.push(diagnostic(
+                        *section,
+                        *line,
+                        LintSeverity::Error,
+                        LintProblem::MalformedLine {
+                            text: text.to_string(),
+                        },
+                        Some(LintFix::RemoveLine),
+                    ));
+                    continue;
+                }
+                ScopeLine::Entry {
+                    section,
+                    line,
+                    mime_type,
+                    ids,
+                } => (*section, *line, mime_type, ids),
+            };
+
+            let overridden_by = last_lines[&(section, mime_type)];
+            if overridden_by != line {
+                diagnostics.push(diagnostic(
+                    section,
+                    line,
+                    LintSeverity::Warning,
+                    LintProblem::DuplicateKey {
+                        mime_type: mime_type.clone(),
+                        overridden_by,
+                    },
+                    Some(LintFix::RemoveLine),
+                ));
+                continue;
+            }
+
+            let canonical = mime_info_store.unalias(mime_type);
+            if canonical != mime_type {
+                diagnostics.push(diagnostic(
+                    section,
+                    line,
+                    LintSeverity::Warning,
+                    LintProblem::AliasKey {
+                        mime_type: mime_type.clone(),
+                        canonical: canonical.clone(),
+                    },
+                    Some(LintFix::RenameKey {
+                        to: canonical.clone(),
+                    }),
+                ));
+            }
+
+            // removing an association with an application which isn't installed, or can't open the type, is harmless
+            if section == MimeTypeAssociationsSections::RemovedAssociations {
+                continue;
+            }
+
+            // an uninstalled application listed alongside an installed one is a portable fallback, e.g., for
+            // another machine, rather than a mistake
+            let is_fallback = |id: &DesktopEntryId| {
+                ids.iter().any(|other| {
+                    other != id
+                        && desktop_entry_store
+                            .find_desktop_entry_with_id(other)
+                            .is_some()
+                })
+            };
+            for id in ids {
+                let remove_application = Some(LintFix::RemoveApplication {
+                    desktop_entry: id.clone(),
+                });
+                let (problem, severity, fix) =
+                    match desktop_entry_store.find_desktop_entry_with_id(id) {
+                        // system lists commonly name alternatives, not all of which are installed
+                        None if !self.is_user_customizable => (
+                            LintProblem::NotInstalled {
+                                mime_type: mime_type.clone(),
+                                desktop_entry: id.clone(),
+                            },
+                            LintSeverity::Info,
+                            remove_application,
+                        ),
+                        None if is_fallback(id) => (
+                            LintProblem::NotInstalled {
+                                mime_type: mime_type.clone(),
+                                desktop_entry: id.clone(),
+                            },
+                            LintSeverity::Info,
+                            None,
+                        ),
+                        None => (
+                            LintProblem::NotInstalled {
+                                mime_type: mime_type.clone(),
+                                desktop_entry: id.clone(),
+                            },
+                            LintSeverity::Warning,
+                            remove_application,
+                        ),
+                        // applications may declare the type by an alias
+                        Some(desktop_entry)
+                            if !desktop_entry.can_open_mime_type_matching(mime_type)
+                                && !desktop_entry.mime_types().iter().any(|declared| {
+                                    mime_info_store.unalias(declared) == canonical
+                                }) =>
+                        {
+                            (
+                                LintProblem::UndeclaredMimeType {
+                                    mime_type: mime_type.clone(),
+                                    desktop_entry: id.clone(),
+                                },
+                                LintSeverity::Warning,
+                                remove_application,
+                            )
+                        }
+                        Some(_) => continue,
+                    };
+                diagnostics.push(diagnostic(section, line, severity, problem, fix));
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Apply the fixes of `diagnostics`, which `lint` found in this scope. Returns those applied; the scope is
+    /// made dirty if any were.
+    fn fix_lint<'a>(&mut self, diagnostics: &[&'a LintDiagnostic]) -> Vec<&'a LintDiagnostic> {
+        let mut applied = vec![];
+        let mut removed_lines = vec![];
+        let mut renamed_keys = vec![];
+        for diagnostic in diagnostics.iter().copied() {
+            let Some(section) = MimeTypeAssociationsSections::from_name(&diagnostic.section) else {
+                continue;
+            };
+            match (&diagnostic.fix, &diagnostic.problem) {
+                (Some(LintFix::RemoveLine), _) => removed_lines.push(diagnostic),
+                (
+                    Some(LintFix::RemoveApplication { desktop_entry }),
+                    LintProblem::NotInstalled { mime_type, .. }
+                    | LintProblem::UndeclaredMimeType { mime_type, .. },
+                ) => {
+                    let associations = self.associations_mut(section);
+                    if let Some(ids) = associations.get_mut(mime_type) {
+                        let count = ids.len();
+                        ids.retain(|id| id != desktop_entry);
+                        if ids.len() != count {
+                            applied.push(diagnostic);
+                        }
+                        if ids.is_empty() {
+                            associations.remove(mime_type);
+                        }
+                    }
+                }
+                (Some(LintFix::RenameKey { to }), LintProblem::AliasKey { mime_type, .. }) => {
+                    renamed_keys.push((diagnostic, section, mime_type, to))
+                }
+                _ => {}
+            }
+        }
+
+        // rename last, so applications removed from an alias' entry aren't carried over
+        for (diagnostic, section, from, to) in renamed_keys {
+            let associations = self.associations_mut(section);
+            if let Some(ids) = associations.remove(from) {
+                let target = associations.entry(to.clone()).or_default();
+                for id in ids {
+                    if !target.contains(&id) {
+                        target.push(id);
+                    }
+                }
+                applied.push(diagnostic);
+            }
+        }
+
+        // the lines of duplicate keys and malformed entries aren't in the associations; drop them from the file
+        let line_numbers = removed_lines.iter().map(|d| d.line).collect::<Vec<_>>();
+        let removed = self.document.remove_lines(&line_numbers);
+        applied.extend(
+            removed_lines
+                .into_iter()
+                .filter(|diagnostic| removed.contains(&diagnostic.line)),
+        );
+
+        if !applied.is_empty() {
+            self.is_dirty = true;
+        }
+        applied
+    }
+
+    fn associations_mut(
+        &mut self,
+        section: MimeTypeAssociationsSections,
+    ) -> &mut HashMap<MimeType, Vec<DesktopEntryId>> {
+        match section {
+            MimeTypeAssociationsSections::AddedAssociations => &mut self.added_associations,
+            MimeTypeAssociationsSections::RemovedAssociations => &mut self.removed_associations,
+            MimeTypeAssociationsSections::DefaultApplications => &mut self.default_applications,
+        }
     }
 
-    fn reload(&mut self) -> anyhow::Result<()> {
+    fn reload(&mut self, environment: &Environment) -> Result<(), Error> {
         // Caveman reload: make a new object, move its values to self
-        let mut associations = Self::load(&self.file_path)?;
+        let mut associations = Self::load(&self.file_path, environment)?;
         self.is_user_customizable = associations.is_user_customizable;
         self.is_dirty = false;
         self.added_associations = std::mem::take(&mut associations.added_associations);
+        self.removed_associations = std::mem::take(&mut associations.removed_associations);
         self.default_applications = std::mem::take(&mut associations.default_applications);
+        self.document = std::mem::take(&mut associations.document);
+        self.base = std::mem::take(&mut associations.base);
+        self.fingerprint = associations.fingerprint.take();
 
         Ok(())
     }
@@ -123,42 +526,159 @@ impl MimeTypeAssociationScope {
     /// This is similar to the `is_dirty` but different. `is_dirty` is set when mutations have been made,
     /// but will not account for a series of transformations which result in the original state.
     #[allow(dead_code)]
-    fn differs_from_file_representation(&self) -> bool {
-        if let Ok(file_representation) = Self::load(&self.file_path) {
+    fn differs_from_file_representation(&self, environment: &Environment) -> bool {
+        if let Ok(file_representation) = Self::load(&self.file_path, environment) {
             self.default_applications != file_representation.default_applications
                 || self.added_associations != file_representation.added_associations
+                || self.removed_associations != file_representation.removed_associations
         } else {
             true
         }
     }
 
     /// Persist changes to this MimeAsociationScope.
-    fn save(&mut self) -> anyhow::Result<()> {
+    fn save(&mut self) -> Result<(), Error> {
         if !self.is_user_customizable {
-            anyhow::bail!(
-                "MimeAssociationScope[{:?}] is not user customizable.",
-                &self.file_path
-            );
+            return Err(Error::ReadOnlyScope(self.file_path.clone()));
         }
 
         if self.is_dirty {
-            // create a temp output file
-            let temp_dir = self.file_path.parent().unwrap();
-            let temp_file_path = temp_dir.join("mimeassoc.temp.list");
-            self.write_to_path(&temp_file_path)?;
+            self.merge_external_changes()?;
 
-            // rename this file to our original
-            std::fs::rename(&temp_file_path, &self.file_path)?;
+            let document = self.updated_document();
+            let contents = document.to_string();
+            if let Some(dir) = self.file_path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+            }
+            replace_file_contents(&self.file_path, &contents)?;
 
+            let metadata =
+                std::fs::metadata(&self.file_path).map_err(|e| Error::io(&self.file_path, e))?;
+            self.fingerprint = Some(FileFingerprint::new(&metadata, &contents));
+            self.base = document.clone();
+            self.document = document;
             self.is_dirty = false;
         }
         Ok(())
     }
 
-    fn parse_line(line: &str) -> anyhow::Result<(MimeType, Vec<DesktopEntryId>)> {
+    /// If another program changed the file since it was loaded, e.g., GNOME Settings, `xdg-mime` or
+    /// Nautilus' "Open With", merge their changes with ours: entries only they changed take their value,
+    /// entries only we changed keep ours. If both changed an entry differently, nothing is merged, and
+    /// `Error::SaveConflict` lists the affected mime types.
+    fn merge_external_changes(&mut self) -> Result<(), Error> {
+        let Some(fingerprint) = &self.fingerprint else {
+            return Ok(());
+        };
+
+        let (contents, metadata) = match Self::read(&self.file_path) {
+            Ok(result) => result,
+            // the file was deleted; there's nothing to merge with
+            Err(Error::Io { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        };
+        let current_fingerprint = FileFingerprint::new(&metadata, &contents);
+        if *fingerprint == current_fingerprint {
+            return Ok(());
+        }
+
+        log::info!(
+            "MimeAssociationScope::merge_external_changes - {:?} was changed since it was loaded",
+            self.file_path
+        );
+
+        let base = Self::parse(&self.file_path, &self.base.to_string());
+        let theirs = Self::parse(&self.file_path, &contents);
+
+        let mut conflicts = vec![];
+        let added_associations = Self::merge_associations(
+            &self.added_associations,
+            &base.added_associations,
+            &theirs.added_associations,
+            &mut conflicts,
+        );
+        let removed_associations = Self::merge_associations(
+            &self.removed_associations,
+            &base.removed_associations,
+            &theirs.removed_associations,
+            &mut conflicts,
+        );
+        let default_applications = Self::merge_associations(
+            &self.default_applications,
+            &base.default_applications,
+            &theirs.default_applications,
+            &mut conflicts,
+        );
+
+        if !conflicts.is_empty() {
+            conflicts.sort();
+            conflicts.dedup();
+            return Err(Error::SaveConflict {
+                path: self.file_path.clone(),
+                mime_types: conflicts,
+            });
+        }
+
+        self.added_associations = added_associations;
+        self.removed_associations = removed_associations;
+        self.default_applications = default_applications;
+        // build on the current file, so their formatting and comments are preserved too, unless ours was
+        // edited, e.g., by lint fixes removing lines, which their file would restore
+        if self.document == self.base {
+            self.document = theirs.document.clone();
+        }
+        self.base = theirs.document;
+        self.fingerprint = Some(current_fingerprint);
+
+        Ok(())
+    }
+
+    /// Three-way merge of one section's associations. Empty lists are equivalent to absent entries, but
+    /// those of `ours` are kept, since the store uses them to record mime types which have no associations.
+    fn merge_associations(
+        ours: &HashMap<MimeType, Vec<DesktopEntryId>>,
+        base: &HashMap<MimeType, Vec<DesktopEntryId>>,
+        theirs: &HashMap<MimeType, Vec<DesktopEntryId>>,
+        conflicts: &mut Vec<MimeType>,
+    ) -> HashMap<MimeType, Vec<DesktopEntryId>> {
+        let mut merged = ours.clone();
+        let mime_types = ours
+            .keys()
+            .chain(base.keys())
+            .chain(theirs.keys())
+            .collect::<HashSet<_>>();
+        for mime_type in mime_types {
+            let [our_ids, base_ids, their_ids] = [ours, base, theirs]
+                .map(|associations| associations.get(mime_type).filter(|ids| !ids.is_empty()));
+            if our_ids == their_ids || their_ids == base_ids {
+                continue;
+            }
+            if our_ids != base_ids {
+                conflicts.push(mime_type.clone());
+                continue;
+            }
+
+            // only they changed this entry
+            match their_ids {
+                Some(ids) => {
+                    merged.insert(mime_type.clone(), ids.clone());
+                }
+                None => {
+                    merged.remove(mime_type);
+                }
+            }
+        }
+
+        merged
+    }
+
+    fn parse_line(line: &str) -> Result<(MimeType, Vec<DesktopEntryId>), Error> {
         let components = line.split('=').collect::<Vec<_>>();
         if components.len() != 2 {
-            anyhow::bail!("A line from mimeapps.lst is expected to be in form \"mime/type=app.desktop\". Line \"{}\" was invalid", line);
+            // a line which isn't in form "mime/type=app.desktop" has no usable mime type key
+            return Err(Error::InvalidMimeType(line.trim().to_string()));
         }
 
         let mime_type_component = components[0].trim();
@@ -195,75 +715,101 @@ impl MimeTypeAssociationScope {
 
     fn generate_default_application_line(
         mime_type: &MimeType,
-        desktop_entry: &DesktopEntryId,
+        desktop_entries: &[DesktopEntryId],
     ) -> String {
-        format!("{}={}", mime_type, desktop_entry)
-    }
-
-    fn write(&self, output_file: &mut File) -> anyhow::Result<()> {
-        // write the added associations
-        if !self.added_associations.is_empty() {
-            writeln!(
-                output_file,
-                "{}",
-                MimeTypeAssociationsSections::AddedAssociations.to_string()
-            )?;
+        Self::generate_added_associations_line(mime_type, desktop_entries)
+    }
+
+    /// Produce the document to write for this scope: the document it was loaded from, with only
+    /// the entries which differ from the in-memory associations rewritten, added or removed.
+    fn updated_document(&self) -> MimeTypeAssociationDocument {
+        let mut document = self.document.clone();
+        let sections = [
+            (
+                MimeTypeAssociationsSections::AddedAssociations,
+                &self.added_associations,
+            ),
+            (
+                MimeTypeAssociationsSections::RemovedAssociations,
+                &self.removed_associations,
+            ),
+            (
+                MimeTypeAssociationsSections::DefaultApplications,
+                &self.default_applications,
+            ),
+        ];
+
+        for (section, associations) in sections {
+            let name = section.name();
+
+            // drop entries which no longer exist; keys which aren't mime types are left alone
+            let stale_keys = document
+                .keys(name)
+                .into_iter()
+                .filter(|key| {
+                    MimeType::parse(key).is_ok_and(|mime_type| {
+                        associations
+                            .get(&mime_type)
+                            .is_none_or(|ids| ids.is_empty())
+                    })
+                })
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            for key in stale_keys {
+                document.remove(name, &key);
+            }
 
-            let mut mime_types = self.added_associations.keys().collect::<Vec<_>>();
+            let mut mime_types = associations.keys().collect::<Vec<_>>();
             mime_types.sort();
 
             for mime_type in mime_types {
-                if let Some(desktop_entries) = self.added_associations.get(mime_type) {
-                    if !desktop_entries.is_empty() {
-                        writeln!(
-                            output_file,
-                            "{};",
-                            Self::generate_added_associations_line(mime_type, desktop_entries)
-                        )?;
-                    }
+                let ids = &associations[mime_type];
+                if ids.is_empty() {
+                    continue;
                 }
-            }
-
-            writeln!(output_file)?;
-        }
 
-        // write the default applications
-        if !self.default_applications.is_empty() {
-            writeln!(
-                output_file,
-                "{}",
-                MimeTypeAssociationsSections::DefaultApplications.to_string()
-            )?;
-
-            let mut mime_types = self.default_applications.keys().collect::<Vec<_>>();
-            mime_types.sort();
+                // leave lines which already say the same thing untouched, whatever their formatting
+                let existing = document
+                    .value(name, mime_type.id())
+                    .and_then(|value| Self::parse_line(&format!("{}={}", mime_type, value)).ok())
+                    .map(|(_, existing_ids)| existing_ids);
+                if existing.as_ref() == Some(ids) {
+                    continue;
+                }
 
-            for mime_type in mime_types {
-                if let Some(desktop_entry) = self.default_applications.get(mime_type) {
-                    writeln!(
-                        output_file,
-                        "{}",
-                        Self::generate_default_application_line(mime_type, desktop_entry)
-                    )?;
+                let mut line = match section {
+                    MimeTypeAssociationsSections::DefaultApplications => {
+                        Self::generate_default_application_line(mime_type, ids)
+                    }
+                    _ => Self::generate_added_associations_line(mime_type, ids),
+                };
+                if section.has_trailing_semicolon() {
+                    line.push(';');
                 }
+                document.set(name, mime_type.id(), &line);
             }
         }
 
-        Ok(())
+        document
     }
 
-    fn write_to_path<P>(&self, path: P) -> anyhow::Result<()>
+    /// Write this scope to `path`, replacing any existing file. Unlike `save`, there's no merging.
+    #[cfg(test)]
+    fn write_to_path<P>(&self, path: P) -> Result<(), Error>
     where
         P: AsRef<Path>,
     {
-        let mut output_file = File::create(path)?;
-        self.write(&mut output_file)
+        let path = path.as_ref();
+        std::fs::write(path, self.updated_document().to_string()).map_err(|e| Error::io(path, e))
     }
 }
 
 pub struct MimeTypeAssociationStore {
     scopes: Vec<MimeTypeAssociationScope>,
 
+    // decides which scopes are user customizable, when loading and reloading
+    environment: Environment,
+
     // this flag is used only for tests. Defaults to true, which means
     // that an app must be installed and executable (e.g., passes
     // DesktopEntry::appears_valid_application) for assignments. For
@@ -274,27 +820,41 @@ pub struct MimeTypeAssociationStore {
 
 impl MimeTypeAssociationStore {
     /// Load MimeAssocations in order of the provided paths. MimeAssocations earlier in
-    /// the list will override ones later in the list.
-    pub fn load<P>(mimeapps_file_paths: &[P]) -> anyhow::Result<Self>
+    /// the list will override ones later in the list. Files in the user's home or config dir,
+    /// as given by the process environment, are user customizable.
+    pub fn load<P>(mimeapps_file_paths: &[P]) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_for_environment(mimeapps_file_paths, &Environment::from_process()?)
+    }
+
+    /// Load MimeAssocations in order of the provided paths, as `MimeTypeAssociationStore::load`, but
+    /// deciding which files are user customizable from `environment` rather than the process environment.
+    pub fn load_for_environment<P>(
+        mimeapps_file_paths: &[P],
+        environment: &Environment,
+    ) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
         let mut scopes = Vec::new();
         for file_path in mimeapps_file_paths.iter() {
-            scopes.push(MimeTypeAssociationScope::load(file_path)?);
+            scopes.push(MimeTypeAssociationScope::load(file_path, environment)?);
         }
 
         Ok(Self {
             scopes,
+            environment: environment.clone(),
             verify_app_is_valid: true,
         })
     }
 
     /// Reload the mime associations passed in to `MimeAssociationStore::load` during construction.
     /// Effectively resets state, provided any changes to state weren't persisted via `MimeAssociationsStore::save`
-    pub fn reload(&mut self) -> anyhow::Result<()> {
+    pub fn reload(&mut self) -> Result<(), Error> {
         for scope in self.scopes.iter_mut() {
-            scope.reload()?;
+            scope.reload(&self.environment)?;
         }
 
         Ok(())
@@ -310,12 +870,48 @@ impl MimeTypeAssociationStore {
         self.scopes.iter_mut().filter(|s| s.is_user_customizable)
     }
 
+    /// Get the index of the scope changes are written to: the user's `mimeapps.list` in the config home, rather
+    /// than a higher priority `$desktop-mimeapps.list`, which only that desktop reads. Stores loaded without it
+    /// write to their first user-editable scope.
+    fn write_scope_index(&self) -> Option<usize> {
+        let path = self.environment.config_home().join("mimeapps.list");
+        self.scopes
+            .iter()
+            .position(|s| s.is_user_customizable && s.file_path == path)
+            .or_else(|| self.scopes.iter().position(|s| s.is_user_customizable))
+    }
+
+    /// Get the scope changes are written to; see `MimeTypeAssociationStore::write_scope_index`
+    fn write_scope_mut(&mut self) -> Option<&mut MimeTypeAssociationScope> {
+        let index = self.write_scope_index()?;
+        self.scopes.get_mut(index)
+    }
+
+    /// Delete the [Default Applications] entry for `mime_type` from the user-editable scopes ahead of the write
+    /// scope, e.g., `~/.config/gnome-mimeapps.list`, which would otherwise override an assignment written to it
+    fn remove_overriding_default_applications_entries(&mut self, mime_type: &MimeType) {
+        let Some(index) = self.write_scope_index() else {
+            return;
+        };
+        for scope in self.scopes[..index]
+            .iter_mut()
+            .filter(|s| s.is_user_customizable)
+        {
+            if scope.default_applications.remove(mime_type).is_some() {
+                scope.is_dirty = true;
+            }
+        }
+    }
+
     /// Return all mimetypes represented, in no particular order.
     pub fn mime_types(&self) -> Vec<&MimeType> {
         let mut mime_types = HashSet::new();
         for scope in self.scopes.iter().rev() {
+            // wildcard entries, e.g., image/*, assign many mime types rather than being one
             for (mime_type, _) in scope.default_applications.iter() {
-                mime_types.insert(mime_type);
+                if !mime_type.is_minor_type_wildcard() {
+                    mime_types.insert(mime_type);
+                }
             }
             for (mime_type, _) in scope.added_associations.iter() {
                 mime_types.insert(mime_type);
@@ -325,6 +921,26 @@ impl MimeTypeAssociationStore {
         mime_types.into_iter().collect()
     }
 
+    /// Return every URL scheme either assigned in a mimeapps.list or declared by an installed application
+    /// via an `x-scheme-handler/*` mime type, sorted
+    pub fn schemes(&self, desktop_entry_store: &DesktopEntryStore) -> Vec<String> {
+        let mut schemes = desktop_entry_store.schemes();
+        for mime_type in self.mime_types() {
+            if let Some(scheme) = mime_type.scheme() {
+                if !schemes.iter().any(|s| s == scheme) {
+                    schemes.push(scheme.to_string());
+                }
+            }
+        }
+        schemes.sort();
+        schemes
+    }
+
+    /// The environment this store was loaded for, which decides the user's files
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
     /// Return the sources used to create this store, in preferential chain order, e.g., user entries before system.
     pub fn sources(&self) -> Vec<&Path> {
         self.scopes.iter().map(|s| s.file_path.deref()).collect()
@@ -332,40 +948,279 @@ impl MimeTypeAssociationStore {
 
     /// Returns the assigned application to handle a given mime type. This is the application
     /// that would be used by the File manager to open a file of given mime type.
+    /// Note: This is the first entry of the highest priority preference list, whether or not that
+    /// application is installed; to show or compare against what actually opens the type, use
+    /// `MimeTypeAssociationStore::installed_default_application_for`.
     pub fn default_application_for(&self, mime_type: &MimeType) -> Option<&DesktopEntryId> {
+        self.default_applications_for(mime_type).into_iter().next()
+    }
+
+    /// Returns every application assigned to handle a given mime type, in preference order: each
+    /// scope's `[Default Applications]` list, from user to system, without duplicates. Within a scope, a
+    /// wildcard entry such as `image/*=org.gnome.eog.desktop` applies to every image type the scope doesn't
+    /// list itself, after the applications of the type's own entry.
+    pub fn default_applications_for(&self, mime_type: &MimeType) -> Vec<&DesktopEntryId> {
+        let mut ids: Vec<&DesktopEntryId> = vec![];
         for scope in self.scopes.iter() {
-            if let Some(id) = scope.default_applications.get(mime_type) {
+            for id in scope.default_applications_for(mime_type) {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids
+    }
+
+    /// Returns the first application in the preference order of `MimeTypeAssociationStore::default_applications_for`
+    /// which is installed, e.g., found in `desktop_entry_store`. Given `image/png=gimp.desktop;org.gnome.eog.desktop;`,
+    /// a machine without GIMP resolves to Eye of Gnome.
+    pub fn installed_default_application_for<'a>(
+        &self,
+        mime_type: &MimeType,
+        desktop_entry_store: &'a DesktopEntryStore,
+    ) -> Option<&'a DesktopEntry> {
+        self.default_applications_for(mime_type)
+            .into_iter()
+            .find_map(|id| desktop_entry_store.find_desktop_entry_with_id(id))
+    }
+
+    /// Returns the assigned application to handle a given mime type, falling back through the mime type
+    /// inheritance tree when the type has no assignment of its own, as GIO does. E.g., if nothing is
+    /// assigned to text/x-csrc, the application assigned to its parent text/plain is returned.
+    /// Aliases are resolved with `mime_info_store` before walking ancestors.
+    pub fn inherited_default_application_for(
+        &self,
+        mime_type: &MimeType,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> Option<&DesktopEntryId> {
+        if let Some(id) = self.default_application_for(mime_type) {
+            return Some(id);
+        }
+
+        let unaliased = mime_info_store.unalias(mime_type);
+        if unaliased != mime_type {
+            if let Some(id) = self.default_application_for(unaliased) {
                 return Some(id);
             }
         }
-        None
+
+        mime_info_store
+            .ancestors(mime_type)
+            .into_iter()
+            .find_map(|ancestor| self.default_application_for(ancestor))
     }
 
-    /// If the user scope(s) have assigned a default application to handle this mime type, return it.
-    pub fn user_default_application_for(&self, mime_type: &MimeType) -> Option<&DesktopEntryId> {
-        for scope in self.user_scopes_iter() {
-            if let Some(result) = scope.default_applications.get(mime_type) {
-                return Some(result);
+    /// Returns the application which should open content of `mime_type`, choosing as GIO does: the first installed
+    /// default application of the type, its alias, or failing those, its nearest ancestor, e.g., text/plain for
+    /// text/x-csrc. Without any default, the first launchable application supporting the type or one of its ancestors
+    /// is used, per `MimeTypeAssociationStore::desktop_entries_for`.
+    pub fn resolve_application_for<'a>(
+        &self,
+        mime_type: &MimeType,
+        desktop_entry_store: &'a DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> Option<&'a DesktopEntry> {
+        let mut mime_types = vec![mime_type];
+        let unaliased = mime_info_store.unalias(mime_type);
+        if unaliased != mime_type {
+            mime_types.push(unaliased);
+        }
+        mime_types.extend(mime_info_store.ancestors(mime_type));
+
+        mime_types
+            .iter()
+            .find_map(|mime_type| {
+                self.installed_default_application_for(mime_type, desktop_entry_store)
+            })
+            .or_else(|| {
+                mime_types.iter().find_map(|mime_type| {
+                    self.desktop_entries_for(mime_type, desktop_entry_store)
+                        .into_iter()
+                        .find(|desktop_entry| desktop_entry_store.is_launchable(desktop_entry))
+                })
+            })
+    }
+
+    /// Trace how `MimeTypeAssociationStore::resolve_application_for` chooses the application for `mime_type`: the
+    /// scopes consulted for the mime type, its alias and its ancestors, the line of each entry found, and why each
+    /// listed application was or wasn't chosen. Scopes below the one deciding are traced too, since a higher priority
+    /// scope, e.g., a vendor's gnome-mimeapps.list, silently overriding a lower one is a common surprise.
+    pub fn trace_resolution(
+        &self,
+        mime_type: &MimeType,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> ResolutionTrace {
+        let mut candidates = vec![(mime_type, CandidateOrigin::Requested)];
+        let unaliased = mime_info_store.unalias(mime_type);
+        if unaliased != mime_type {
+            candidates.push((unaliased, CandidateOrigin::Alias));
+        }
+        for ancestor in mime_info_store.ancestors(mime_type) {
+            if !candidates
+                .iter()
+                .any(|(candidate, _)| *candidate == ancestor)
+            {
+                candidates.push((ancestor, CandidateOrigin::Ancestor));
+            }
+        }
+
+        let mut steps = vec![];
+        let mut result: Option<DesktopEntryId> = None;
+
+        for (candidate, origin) in candidates.iter() {
+            let wildcard = candidate.wildcard();
+            for scope in self.scopes.iter() {
+                // a scope's wildcard entry, e.g., image/*, is consulted after the candidate's own entry
+                let mut keys = vec![(*candidate, *origin)];
+                if wildcard != **candidate && scope.default_applications.contains_key(&wildcard) {
+                    keys.push((&wildcard, CandidateOrigin::Wildcard));
+                }
+
+                for (key, origin) in keys {
+                    let ids = scope
+                        .default_applications
+                        .get(key)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    let applications = ids
+                        .iter()
+                        .map(|id| {
+                            let outcome = if result.is_some() {
+                                TracedOutcome::Overridden
+                            } else if desktop_entry_store.find_desktop_entry_with_id(id).is_some() {
+                                result = Some(id.clone());
+                                TracedOutcome::Chosen
+                            } else {
+                                TracedOutcome::NotInstalled
+                            };
+                            TracedApplication {
+                                desktop_entry: id.clone(),
+                                outcome,
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    steps.push(TraceStep::Scope(ScopeTraceStep {
+                        path: scope.file_path.clone(),
+                        is_user_customizable: scope.is_user_customizable,
+                        mime_type: key.clone(),
+                        origin,
+                        line: scope.document.line_number(
+                            MimeTypeAssociationsSections::DefaultApplications.name(),
+                            key.id(),
+                        ),
+                        applications,
+                    }));
+                }
+            }
+
+            if result.is_some() {
+                return ResolutionTrace {
+                    mime_type: mime_type.clone(),
+                    steps,
+                    result,
+                };
+            }
+        }
+
+        // no scope assigns an installed application, so the first launchable supporting application is used
+        for (candidate, origin) in candidates.iter() {
+            let mut applications = vec![];
+            for desktop_entry in self.desktop_entries_for(candidate, desktop_entry_store) {
+                let outcome = if result.is_some() {
+                    TracedOutcome::Overridden
+                } else if desktop_entry_store.is_launchable(desktop_entry) {
+                    result = Some(desktop_entry.id().clone());
+                    TracedOutcome::Chosen
+                } else {
+                    TracedOutcome::NotLaunchable
+                };
+                applications.push(TracedApplication {
+                    desktop_entry: desktop_entry.id().clone(),
+                    outcome,
+                });
+            }
+
+            let removed_associations = self.removed_associations_for(candidate);
+            for desktop_entry in desktop_entry_store.find_desktop_entries_for_mimetype(candidate) {
+                if removed_associations.contains(desktop_entry.id()) {
+                    applications.push(TracedApplication {
+                        desktop_entry: desktop_entry.id().clone(),
+                        outcome: TracedOutcome::Removed,
+                    });
+                }
             }
+
+            steps.push(TraceStep::Fallback(FallbackTraceStep {
+                mime_type: (*candidate).clone(),
+                origin: *origin,
+                applications,
+            }));
+
+            if result.is_some() {
+                break;
+            }
+        }
+
+        ResolutionTrace {
+            mime_type: mime_type.clone(),
+            steps,
+            result,
         }
-        None
+    }
+
+    /// If the user scope(s) have assigned a default application to handle this mime type, return it.
+    pub fn user_default_application_for(&self, mime_type: &MimeType) -> Option<&DesktopEntryId> {
+        self.user_scopes_iter()
+            .find_map(|scope| scope.default_applications_for(mime_type).next())
     }
 
     /// Returns the default (e.g., not considering the user's assignment) application to handle a given mime type.
     /// This is not necessarily what would be opened by the file manager; it is what would be used to open
     /// a file if we deleted the user's assignments.
     pub fn system_default_application_for(&self, mime_type: &MimeType) -> Option<&DesktopEntryId> {
-        for scope in self.scopes.iter().filter(|s| !s.is_user_customizable) {
-            if let Some(id) = scope.default_applications.get(mime_type) {
-                return Some(id);
+        self.scopes
+            .iter()
+            .filter(|s| !s.is_user_customizable)
+            .find_map(|scope| scope.default_applications_for(mime_type).next())
+    }
+
+    /// True if `desktop_entry_id` would handle `mime_type` without a user entry for it, e.g., it's the system default
+    /// and no user wildcard entry, such as `image/*`, would take over once the user's own entry is removed
+    fn is_default_without_user_entry(
+        &self,
+        mime_type: &MimeType,
+        desktop_entry_id: &DesktopEntryId,
+    ) -> bool {
+        if self.system_default_application_for(mime_type) != Some(desktop_entry_id) {
+            return false;
+        }
+
+        let wildcard = mime_type.wildcard();
+        mime_type.is_minor_type_wildcard()
+            || !self
+                .user_scopes_iter()
+                .any(|scope| scope.default_applications.contains_key(&wildcard))
+    }
+
+    /// Delete the user scopes' entries for exactly `mime_type`, which may itself be a wildcard, e.g., `image/*`,
+    /// leaving the entries it matches alone. Returns true if the store was made dirty by this action.
+    fn remove_user_default_applications_entry(&mut self, mime_type: &MimeType) -> bool {
+        let mut dirtied = false;
+        for scope in self.user_scopes_iter_mut() {
+            if scope.default_applications.remove(mime_type).is_some() {
+                scope.is_dirty = true;
+                dirtied = true;
             }
         }
-        None
+        dirtied
     }
 
     /// Deletes the application assignment(s) for a given mime type from user editable scopes.
     /// If the mime_type is a wildcard, will delete all matching assignments.
-    /// E.g., image/* would delete assignment for image/png, image/tif, etc.
+    /// E.g., image/* would delete assignment for image/png, image/tif, etc., and the `image/*` entry itself.
+    /// Deleting an exact mime type leaves any wildcard entry matching it in place.
     /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
     /// Returns true if the store was made dirty by this action.
     pub fn remove_assigned_applications_for(&mut self, mime_type: &MimeType) -> bool {
@@ -421,7 +1276,7 @@ impl MimeTypeAssociationStore {
     ) -> HashSet<DesktopEntryId> {
         let mut orphaned_ids = HashSet::new();
         for scope in self.user_scopes_iter_mut() {
-            for desktop_entry_id in scope.default_applications.values() {
+            for desktop_entry_id in scope.default_applications.values().flatten() {
                 if let Some(desktop_entry) =
                     desktop_entry_store.find_desktop_entry_with_id(desktop_entry_id)
                 {
@@ -433,9 +1288,10 @@ impl MimeTypeAssociationStore {
                 }
             }
 
-            scope
-                .default_applications
-                .retain(|_, id| !orphaned_ids.contains(id));
+            for ids in scope.default_applications.values_mut() {
+                ids.retain(|id| !orphaned_ids.contains(id));
+            }
+            scope.default_applications.retain(|_, ids| !ids.is_empty());
         }
 
         orphaned_ids
@@ -444,63 +1300,190 @@ impl MimeTypeAssociationStore {
     /// Returns the "added associations" for a given mimetype, walking down the scope chain, in scope
     /// order from user to system.
     /// Added Associations specify that an application can handle a mimetype, but not that is is assigned to open it.
+    /// An association listed in the [Removed Associations] of a scope hides the same association
+    /// in lower-priority scopes, but not ones added by that scope or those above it.
     pub fn added_associations_for(&self, mime_type: &MimeType) -> Vec<DesktopEntryId> {
         let mut added_associations = vec![];
+        let mut removed_associations: Vec<&DesktopEntryId> = vec![];
         for scope in self.scopes.iter() {
             if let Some(ids) = scope.added_associations.get(mime_type) {
                 for id in ids {
-                    added_associations.push(id.clone())
+                    if !removed_associations.contains(&id) && !added_associations.contains(id) {
+                        added_associations.push(id.clone())
+                    }
                 }
             }
+            if let Some(ids) = scope.removed_associations.get(mime_type) {
+                removed_associations.extend(ids.iter());
+            }
         }
         added_associations
     }
 
-    /// Add to the [Added Associations] block to the topmost mutable scope
-    pub fn add_added_associations(
-        &mut self,
-        mime_type: &MimeType,
-        desktop_entries: &[DesktopEntry],
-    ) -> anyhow::Result<()> {
-        // sanity checks
-        for desktop_entry in desktop_entries.iter() {
-            if self.verify_app_is_valid && !desktop_entry.appears_valid_application() {
-                anyhow::bail!(
-                    "DesktopEntry \"{}\" does not appear to be a valid launchable application",
-                    desktop_entry.id()
-                );
-            }
-            if !desktop_entry.can_open_mime_type(mime_type) {
-                anyhow::bail!(
-                    "DesktopEntry \"{}\" does not appear to support mime type {}",
-                    desktop_entry.id(),
-                    mime_type,
-                );
+    /// Returns the "removed associations" for a given mimetype across all scopes, in scope order
+    /// from user to system. These applications should not be offered to open the mimetype, even
+    /// if their desktop entries claim to support it.
+    pub fn removed_associations_for(&self, mime_type: &MimeType) -> Vec<DesktopEntryId> {
+        let mut removed_associations = vec![];
+        for scope in self.scopes.iter() {
+            if let Some(ids) = scope.removed_associations.get(mime_type) {
+                for id in ids {
+                    if !removed_associations.contains(id) {
+                        removed_associations.push(id.clone());
+                    }
+                }
             }
         }
+        removed_associations
+    }
 
-        // make assignment in first scope
-        let Some(scope) = self.user_scopes_iter_mut().next() else {
-            anyhow::bail!("No customizable user scope available");
-        };
-
-        if let Some(desktop_entry_ids) = scope.added_associations.get_mut(mime_type) {
-            for desktop_entry in desktop_entries {
-                if !desktop_entry_ids.contains(desktop_entry.id()) {
-                    desktop_entry_ids.push(desktop_entry.id().clone());
+    /// Returns the applications able to open a given mimetype: those from the "added associations"
+    /// followed by those whose desktop entries declare support for it, minus any removed associations.
+    /// This is the set of applications a file manager would offer in its "Open With" menu.
+    pub fn desktop_entries_for<'a>(
+        &self,
+        mime_type: &MimeType,
+        desktop_entry_store: &'a DesktopEntryStore,
+    ) -> Vec<&'a DesktopEntry> {
+        let mut desktop_entries: Vec<&DesktopEntry> = vec![];
+        for id in self.added_associations_for(mime_type) {
+            if let Some(desktop_entry) = desktop_entry_store.find_desktop_entry_with_id(&id) {
+                if !desktop_entries.contains(&desktop_entry) {
+                    desktop_entries.push(desktop_entry);
                 }
             }
-        } else {
-            scope.added_associations.insert(mime_type.clone(), vec![]);
         }
 
-        Ok(())
+        let removed_associations = self.removed_associations_for(mime_type);
+        for desktop_entry in desktop_entry_store.find_desktop_entries_for_mimetype(mime_type) {
+            if !removed_associations.contains(desktop_entry.id())
+                && !desktop_entries.contains(&desktop_entry)
+            {
+                desktop_entries.push(desktop_entry);
+            }
+        }
+
+        desktop_entries
     }
 
-    /// Make the provided DesktopEntry the default handler for the given mime type.
-    /// Will return an error if the DesktopEntry isn't a valid application, or if it doesn't
-    /// handle the specified mime type, or if there are no user customizable MimeAssociationScopes
-    /// in the chain.
+    /// Hide an application from the handlers of a mime type by adding it to the [Removed Associations]
+    /// block of the user's mimeapps.list, and dropping it from that scope's [Added Associations].
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    /// Returns true if the store was made dirty by this action.
+    pub fn remove_association(
+        &mut self,
+        mime_type: &MimeType,
+        desktop_entry_id: &DesktopEntryId,
+    ) -> Result<bool, Error> {
+        let Some(scope) = self.write_scope_mut() else {
+            return Err(Error::NoWritableScope);
+        };
+
+        let mut dirtied = false;
+        if let Some(ids) = scope.added_associations.get_mut(mime_type) {
+            let count = ids.len();
+            ids.retain(|id| id != desktop_entry_id);
+            dirtied = ids.len() != count;
+        }
+
+        let removed_ids = scope
+            .removed_associations
+            .entry(mime_type.clone())
+            .or_default();
+        if !removed_ids.contains(desktop_entry_id) {
+            removed_ids.push(desktop_entry_id.clone());
+            dirtied = true;
+        }
+
+        if dirtied {
+            scope.is_dirty = true;
+        }
+        Ok(dirtied)
+    }
+
+    /// Undo `MimeTypeAssociationStore::remove_association`, deleting the application from the
+    /// [Removed Associations] of the mime type in all user scopes. Removals made by system scopes
+    /// can't be restored this way.
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    /// Returns true if the store was made dirty by this action.
+    pub fn restore_association(
+        &mut self,
+        mime_type: &MimeType,
+        desktop_entry_id: &DesktopEntryId,
+    ) -> bool {
+        let mut dirtied = false;
+        for scope in self.user_scopes_iter_mut() {
+            if let Some(ids) = scope.removed_associations.get_mut(mime_type) {
+                let count = ids.len();
+                ids.retain(|id| id != desktop_entry_id);
+                if ids.len() != count {
+                    if ids.is_empty() {
+                        scope.removed_associations.remove(mime_type);
+                    }
+                    scope.is_dirty = true;
+                    dirtied = true;
+                }
+            }
+        }
+        dirtied
+    }
+
+    /// Add to the [Added Associations] block of the user's mimeapps.list
+    pub fn add_added_associations(
+        &mut self,
+        mime_type: &MimeType,
+        desktop_entries: &[DesktopEntry],
+    ) -> Result<(), Error> {
+        // sanity checks
+        for desktop_entry in desktop_entries.iter() {
+            self.verify_can_handle(mime_type, desktop_entry)?;
+        }
+
+        // make assignment in the user's mimeapps.list
+        let Some(scope) = self.write_scope_mut() else {
+            return Err(Error::NoWritableScope);
+        };
+
+        let desktop_entry_ids = scope
+            .added_associations
+            .entry(mime_type.clone())
+            .or_default();
+        for desktop_entry in desktop_entries {
+            if !desktop_entry_ids.contains(desktop_entry.id()) {
+                desktop_entry_ids.push(desktop_entry.id().clone());
+                scope.is_dirty = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that `desktop_entry` is a launchable application (unless verification is disabled) which supports `mime_type`
+    fn verify_can_handle(
+        &self,
+        mime_type: &MimeType,
+        desktop_entry: &DesktopEntry,
+    ) -> Result<(), Error> {
+        if self.verify_app_is_valid && !desktop_entry.appears_valid_application() {
+            return Err(Error::InvalidApplication(desktop_entry.id().clone()));
+        }
+
+        if !desktop_entry.can_open_mime_type_matching(mime_type) {
+            return Err(Error::UnsupportedMimeType {
+                desktop_entry: desktop_entry.id().clone(),
+                mime_type: mime_type.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Make the provided DesktopEntry the default handler for the given mime type.
+    /// Will return an error if the DesktopEntry isn't a valid application, or if it doesn't
+    /// handle the specified mime type, or if there are no user customizable MimeAssociationScopes
+    /// in the chain.
+    /// The mime type may be a wildcard, e.g., `image/*`, which writes a single entry assigning the application
+    /// every image type without an entry of its own; the application must handle at least one of them.
     /// Note: If the assigned application is the system default application
     /// the entry will be removed from the user scope. E.g., this case is equivalent
     /// to calling `delete_assigned_application_for` for the mime type.
@@ -509,39 +1492,86 @@ impl MimeTypeAssociationStore {
         &mut self,
         mime_type: &MimeType,
         desktop_entry: &DesktopEntry,
-    ) -> anyhow::Result<()> {
-        if self.verify_app_is_valid && !desktop_entry.appears_valid_application() {
-            anyhow::bail!(
-                "DesktopEntry \"{}\" does not appear to be a valid launchable application",
-                desktop_entry.id()
-            );
+    ) -> Result<(), Error> {
+        self.verify_can_handle(mime_type, desktop_entry)?;
+
+        // if this is the system default, delete from user scopes
+        if self.is_default_without_user_entry(mime_type, desktop_entry.id()) {
+            self.remove_user_default_applications_entry(mime_type);
+            return Ok(());
         }
 
-        if !desktop_entry.can_open_mime_type(mime_type) {
-            anyhow::bail!(
-                "DesktopEntry \"{}\" does not support mime type \"{}\"",
-                desktop_entry.id(),
-                mime_type
-            );
+        // make assignment in the user's mimeapps.list
+        self.remove_overriding_default_applications_entries(mime_type);
+        let Some(scope) = self.write_scope_mut() else {
+            return Err(Error::NoWritableScope);
+        };
+
+        let new_desktop_entry_ids = vec![desktop_entry.id().clone()];
+        let previous = scope
+            .default_applications
+            .insert(mime_type.clone(), new_desktop_entry_ids.clone());
+
+        if previous != Some(new_desktop_entry_ids) {
+            scope.is_dirty = true;
         }
 
-        // if this is the system default, delete from user scopes
-        if self.system_default_application_for(mime_type) == Some(desktop_entry.id()) {
-            self.remove_assigned_applications_for(mime_type);
+        Ok(())
+    }
+
+    /// Assign an ordered preference list of applications to handle the given mime type. When resolved
+    /// with `MimeTypeAssociationStore::installed_default_application_for` the first installed application
+    /// in the list wins, so a shared configuration can name applications which aren't installed on every machine.
+    /// Will return an error if the list is empty, if an installed application in it (e.g., one found in
+    /// `desktop_entry_store`) doesn't handle the mime type, or if there are no user customizable
+    /// MimeAssociationScopes in the chain.
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    pub fn set_default_handlers_for_mime_type(
+        &mut self,
+        mime_type: &MimeType,
+        desktop_entry_ids: &[DesktopEntryId],
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Result<(), Error> {
+        if desktop_entry_ids.is_empty() {
+            return Err(Error::NoDesktopEntries(mime_type.clone()));
+        }
+
+        for id in desktop_entry_ids {
+            if let Some(desktop_entry) = desktop_entry_store.find_desktop_entry_with_id(id) {
+                if !desktop_entry.can_open_mime_type_matching(mime_type) {
+                    return Err(Error::UnsupportedMimeType {
+                        desktop_entry: desktop_entry.id().clone(),
+                        mime_type: mime_type.clone(),
+                    });
+                }
+            }
+        }
+
+        // a single entry which matches the system default is equivalent to no assignment
+        if desktop_entry_ids.len() == 1
+            && self.is_default_without_user_entry(mime_type, &desktop_entry_ids[0])
+        {
+            self.remove_user_default_applications_entry(mime_type);
             return Ok(());
         }
 
-        // make assignment in first scope
-        let Some(scope) = self.user_scopes_iter_mut().next() else {
-            anyhow::bail!("No customizable user scope available");
+        self.remove_overriding_default_applications_entries(mime_type);
+        let Some(scope) = self.write_scope_mut() else {
+            return Err(Error::NoWritableScope);
         };
 
-        let new_desktop_entry_id = desktop_entry.id().clone();
+        let mut new_desktop_entry_ids: Vec<DesktopEntryId> = vec![];
+        for id in desktop_entry_ids {
+            if !new_desktop_entry_ids.contains(id) {
+                new_desktop_entry_ids.push(id.clone());
+            }
+        }
+
         let previous = scope
             .default_applications
-            .insert(mime_type.clone(), new_desktop_entry_id.clone());
+            .insert(mime_type.clone(), new_desktop_entry_ids.clone());
 
-        if previous != Some(new_desktop_entry_id) {
+        if previous != Some(new_desktop_entry_ids) {
             scope.is_dirty = true;
         }
 
@@ -555,13 +1585,540 @@ impl MimeTypeAssociationStore {
     pub fn make_desktop_entry_default_handler_of_its_supported_mime_types(
         &mut self,
         desktop_entry: &DesktopEntry,
-    ) -> anyhow::Result<()> {
+    ) -> Result<(), Error> {
         for mime_type in desktop_entry.mime_types() {
             self.set_default_handler_for_mime_type(mime_type, desktop_entry)?;
         }
         Ok(())
     }
 
+    /// Make the provided DesktopEntry the default handler for every mime type of `role` it supports, e.g., assigning
+    /// a browser to `x-scheme-handler/http`, `x-scheme-handler/https`, `text/html` and `application/xhtml+xml` at once.
+    /// Returns the mime types assigned. Will return an error if the desktop entry isn't a valid application, if it
+    /// supports none of the role's mime types, or if there are no user customizable scopes in the chain.
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    pub fn set_default_handler_for_role(
+        &mut self,
+        role: &Role,
+        desktop_entry: &DesktopEntry,
+    ) -> Result<Vec<MimeType>, Error> {
+        let mime_types = role
+            .mime_types()
+            .iter()
+            .filter(|mime_type| desktop_entry.can_open_mime_type(mime_type))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if mime_types.is_empty() {
+            return Err(Error::UnsupportedRole {
+                desktop_entry: desktop_entry.id().clone(),
+                role: role.id().to_string(),
+            });
+        }
+
+        // check every assignment can be made before making any, so a failure leaves the role as it was
+        let has_user_scope = self.user_scopes_iter().next().is_some();
+        for mime_type in mime_types.iter() {
+            self.verify_can_handle(mime_type, desktop_entry)?;
+            if !has_user_scope && !self.is_default_without_user_entry(mime_type, desktop_entry.id())
+            {
+                return Err(Error::NoWritableScope);
+            }
+        }
+
+        for mime_type in mime_types.iter() {
+            self.set_default_handler_for_mime_type(mime_type, desktop_entry)?;
+        }
+
+        Ok(mime_types)
+    }
+
+    /// Returns the installed application handling each mime type of `role`, and which application owns the role.
+    /// See `RoleHandlers`.
+    pub fn role_handlers(
+        &self,
+        role: &Role,
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> RoleHandlers {
+        let handlers = role
+            .mime_types()
+            .iter()
+            .map(|mime_type| {
+                let handler = self
+                    .installed_default_application_for(mime_type, desktop_entry_store)
+                    .map(|desktop_entry| desktop_entry.id().clone());
+                (mime_type.clone(), handler)
+            })
+            .collect();
+
+        RoleHandlers::new(handlers, desktop_entry_store)
+    }
+
+    /// Returns the roles of `role_store` whose owner handles only some of the role's mime types it supports,
+    /// e.g., a browser assigned to `x-scheme-handler/https` while another application handles `text/html`.
+    pub fn partially_owned_roles<'a>(
+        &self,
+        role_store: &'a RoleStore,
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Vec<&'a Role> {
+        role_store
+            .roles()
+            .iter()
+            .filter(|role| {
+                self.role_handlers(role, desktop_entry_store)
+                    .is_partially_owned()
+            })
+            .collect()
+    }
+
+    /// Export the user's default applications and added associations as a portable profile. Where several user
+    /// scopes assign a mime type, the highest priority one wins, as when resolving. With `ProfileIdentifiers::Names`,
+    /// installed applications are identified by name, falling back to their id; see `Profile`.
+    pub fn export_profile(
+        &self,
+        desktop_entry_store: &DesktopEntryStore,
+        identifiers: ProfileIdentifiers,
+    ) -> Profile {
+        let identify = |ids: &[DesktopEntryId]| {
+            let mut result: Vec<String> = vec![];
+            for id in ids {
+                // the exact id comes first, since names needn't be unique
+                if !result.iter().any(|identifier| identifier == id.id()) {
+                    result.push(id.to_string());
+                }
+                if identifiers == ProfileIdentifiers::Names {
+                    if let Some(name) = desktop_entry_store
+                        .find_desktop_entry_with_id(id)
+                        .and_then(|desktop_entry| desktop_entry.untranslated_name())
+                    {
+                        if !result.contains(&name) {
+                            result.push(name);
+                        }
+                    }
+                }
+            }
+            result
+        };
+
+        let mut profile = Profile::default();
+        for scope in self.user_scopes_iter() {
+            for (mime_type, ids) in scope.default_applications.iter() {
+                if !ids.is_empty() {
+                    profile
+                        .default_applications
+                        .entry(mime_type.to_string())
+                        .or_insert_with(|| identify(ids));
+                }
+            }
+            // the empty lists added for every supported mime type when loading aren't the user's
+            for (mime_type, ids) in scope.added_associations.iter() {
+                if !ids.is_empty() {
+                    profile
+                        .added_associations
+                        .entry(mime_type.to_string())
+                        .or_insert_with(|| identify(ids));
+                }
+            }
+        }
+        profile
+    }
+
+    /// Import a profile into the user's mimeapps.list, resolving its application identifiers with `lookup_desktop_entry`.
+    /// Each default application entry assigns its applications in order, as a preference list: those installed which
+    /// support the mime type, and, as fallbacks, the ids of those not installed. It's skipped if none of its applications
+    /// is installed and supports the mime type. Each added association entry associates every such application. Entries which can't be applied on this machine, e.g.,
+    /// because the applications aren't installed, are skipped and reported. Assignments of mime types the profile
+    /// doesn't mention are left alone.
+    /// Will return an error if there are no user customizable scopes in the chain.
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    pub fn import_profile(
+        &mut self,
+        profile: &Profile,
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Result<ProfileImport, Error> {
+        if self.user_scopes_iter().next().is_none() {
+            return Err(Error::NoWritableScope);
+        }
+
+        let mut result = ProfileImport::default();
+        let skip = |section, mime_type: &str, identifiers: &[String], reason| SkippedProfileEntry {
+            section,
+            mime_type: mime_type.to_string(),
+            identifiers: identifiers.to_vec(),
+            reason,
+        };
+
+        for (id, identifiers) in profile.default_applications.iter() {
+            let section = ProfileSection::DefaultApplications;
+            let Ok(mime_type) = MimeType::parse(id) else {
+                result
+                    .skipped
+                    .push(skip(section, id, identifiers, SkipReason::InvalidMimeType));
+                continue;
+            };
+
+            match self.resolve_profile_default_application(
+                &mime_type,
+                identifiers,
+                desktop_entry_store,
+            ) {
+                Ok(desktop_entry) => {
+                    let desktop_entry_ids = self.resolve_profile_default_applications(
+                        &mime_type,
+                        identifiers,
+                        desktop_entry_store,
+                    );
+                    self.set_default_handlers_for_mime_type(
+                        &mime_type,
+                        &desktop_entry_ids,
+                        desktop_entry_store,
+                    )?;
+                    result
+                        .default_applications
+                        .push((mime_type, desktop_entry.id().clone()));
+                }
+                Err(reason) => result.skipped.push(skip(section, id, identifiers, reason)),
+            }
+        }
+
+        for (id, identifiers) in profile.added_associations.iter() {
+            let section = ProfileSection::AddedAssociations;
+            let Ok(mime_type) = MimeType::parse(id) else {
+                result
+                    .skipped
+                    .push(skip(section, id, identifiers, SkipReason::InvalidMimeType));
+                continue;
+            };
+
+            for identifier in identifiers {
+                match self.resolve_profile_association(&mime_type, identifier, desktop_entry_store)
+                {
+                    Ok(desktop_entry) => {
+                        self.add_added_associations(
+                            &mime_type,
+                            std::slice::from_ref(desktop_entry),
+                        )?;
+                        result
+                            .added_associations
+                            .push((mime_type.clone(), desktop_entry.id().clone()));
+                    }
+                    Err(reason) => result.skipped.push(skip(
+                        section,
+                        id,
+                        std::slice::from_ref(identifier),
+                        reason,
+                    )),
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Resolve the application a profile assigns to `mime_type`: the first of `identifiers` which names a launchable
+    /// application supporting the mime type. Otherwise returns why none could be assigned.
+    fn resolve_profile_default_application<'a>(
+        &self,
+        mime_type: &MimeType,
+        identifiers: &[String],
+        desktop_entry_store: &'a DesktopEntryStore,
+    ) -> Result<&'a DesktopEntry, SkipReason> {
+        let desktop_entries = identifiers
+            .iter()
+            .filter_map(|identifier| lookup_desktop_entry(desktop_entry_store, identifier))
+            .collect::<Vec<_>>();
+
+        if let Some(desktop_entry) = desktop_entries
+            .iter()
+            .find(|desktop_entry| self.verify_can_handle(mime_type, desktop_entry).is_ok())
+        {
+            Ok(desktop_entry)
+        } else if desktop_entries.is_empty() {
+            Err(SkipReason::NotInstalled)
+        } else if desktop_entries
+            .iter()
+            .any(|desktop_entry| desktop_entry.can_open_mime_type(mime_type))
+        {
+            Err(SkipReason::NotLaunchable)
+        } else {
+            Err(SkipReason::Unsupported)
+        }
+    }
+
+    /// Resolve the preference list a profile assigns to `mime_type`, in the order of `identifiers`: the installed
+    /// applications which support the mime type, and the ids of those which aren't installed, as fallbacks for
+    /// machines which have them. Names of applications which aren't installed can't be kept, so they're dropped.
+    fn resolve_profile_default_applications(
+        &self,
+        mime_type: &MimeType,
+        identifiers: &[String],
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Vec<DesktopEntryId> {
+        let mut desktop_entry_ids = vec![];
+        for identifier in identifiers {
+            let id = match lookup_desktop_entry(desktop_entry_store, identifier) {
+                Some(desktop_entry) if self.verify_can_handle(mime_type, desktop_entry).is_ok() => {
+                    desktop_entry.id().clone()
+                }
+                Some(_) => continue,
+                None => match DesktopEntryId::parse(identifier) {
+                    Ok(id) => id,
+                    Err(_) => continue,
+                },
+            };
+            if !desktop_entry_ids.contains(&id) {
+                desktop_entry_ids.push(id);
+            }
+        }
+        desktop_entry_ids
+    }
+
+    /// Resolve an application a profile associates with `mime_type`, or return why it can't be associated
+    fn resolve_profile_association<'a>(
+        &self,
+        mime_type: &MimeType,
+        identifier: &str,
+        desktop_entry_store: &'a DesktopEntryStore,
+    ) -> Result<&'a DesktopEntry, SkipReason> {
+        let Some(desktop_entry) = lookup_desktop_entry(desktop_entry_store, identifier) else {
+            return Err(SkipReason::NotInstalled);
+        };
+        match self.verify_can_handle(mime_type, desktop_entry) {
+            Ok(()) => Ok(desktop_entry),
+            Err(Error::InvalidApplication(_)) => Err(SkipReason::NotLaunchable),
+            Err(_) => Err(SkipReason::Unsupported),
+        }
+    }
+
+    /// Compare the user's assignments against `desired_state`, returning the changes which would make them match,
+    /// along with the entries which can't be satisfied with the applications in `desktop_entry_store`. A mime type
+    /// is converged when its default application, as given by `MimeTypeAssociationStore::default_application_for`,
+    /// is the one the desired state resolves to, so applying the plan and planning again yields no changes.
+    pub fn plan(
+        &self,
+        desired_state: &DesiredState,
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Plan {
+        let mut plan = Plan::default();
+        let unresolved =
+            |section, mime_type: &str, identifiers: &[String], reason| SkippedProfileEntry {
+                section,
+                mime_type: mime_type.to_string(),
+                identifiers: identifiers.to_vec(),
+                reason,
+            };
+
+        // mime types the desired state assigns are never reset, even if their applications can't be resolved
+        let mut assigned_mime_types = vec![];
+        for (id, identifiers) in desired_state.default_applications.iter() {
+            let section = ProfileSection::DefaultApplications;
+            let Ok(mime_type) = MimeType::parse(id) else {
+                plan.unresolved.push(unresolved(
+                    section,
+                    id,
+                    identifiers,
+                    SkipReason::InvalidMimeType,
+                ));
+                continue;
+            };
+
+            match self.resolve_profile_default_application(
+                &mime_type,
+                identifiers,
+                desktop_entry_store,
+            ) {
+                Ok(desktop_entry) => {
+                    let current = self
+                        .installed_default_application_for(&mime_type, desktop_entry_store)
+                        .map(DesktopEntry::id);
+                    if current != Some(desktop_entry.id()) {
+                        plan.changes.push(PlannedChange::SetDefaultApplication {
+                            mime_type: mime_type.clone(),
+                            from: current.cloned(),
+                            to: desktop_entry.id().clone(),
+                        });
+                    }
+                }
+                Err(reason) => plan
+                    .unresolved
+                    .push(unresolved(section, id, identifiers, reason)),
+            }
+            assigned_mime_types.push(mime_type);
+        }
+
+        let mut reset_mime_types = vec![];
+        for id in desired_state.reset.iter() {
+            match MimeType::parse(id) {
+                Ok(mime_type) => reset_mime_types.push(mime_type),
+                Err(_) => plan.unresolved.push(unresolved(
+                    ProfileSection::Reset,
+                    id,
+                    &[],
+                    SkipReason::InvalidMimeType,
+                )),
+            }
+        }
+
+        let mut user_mime_types = self
+            .user_scopes_iter()
+            .flat_map(|scope| scope.default_applications.iter())
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(mime_type, _)| mime_type)
+            .collect::<Vec<_>>();
+        user_mime_types.sort();
+        user_mime_types.dedup();
+
+        for mime_type in user_mime_types {
+            if assigned_mime_types.contains(mime_type) {
+                continue;
+            }
+            let is_reset = desired_state.exclusive
+                || reset_mime_types
+                    .iter()
+                    .any(|reset| reset == mime_type || reset.wildcard_match(mime_type));
+            if is_reset {
+                plan.changes.push(PlannedChange::ResetDefaultApplication {
+                    mime_type: mime_type.clone(),
+                    from: self.user_default_application_for(mime_type).cloned(),
+                });
+            }
+        }
+
+        for (id, identifiers) in desired_state.added_associations.iter() {
+            let section = ProfileSection::AddedAssociations;
+            let Ok(mime_type) = MimeType::parse(id) else {
+                plan.unresolved.push(unresolved(
+                    section,
+                    id,
+                    identifiers,
+                    SkipReason::InvalidMimeType,
+                ));
+                continue;
+            };
+
+            let mut associated = self.added_associations_for(&mime_type);
+            for identifier in identifiers {
+                match self.resolve_profile_association(&mime_type, identifier, desktop_entry_store)
+                {
+                    Ok(desktop_entry) => {
+                        if !associated.contains(desktop_entry.id()) {
+                            associated.push(desktop_entry.id().clone());
+                            plan.changes.push(PlannedChange::AddAssociation {
+                                mime_type: mime_type.clone(),
+                                desktop_entry: desktop_entry.id().clone(),
+                            });
+                        }
+                    }
+                    Err(reason) => plan.unresolved.push(unresolved(
+                        section,
+                        id,
+                        std::slice::from_ref(identifier),
+                        reason,
+                    )),
+                }
+            }
+        }
+
+        plan
+    }
+
+    /// Make the changes of a plan produced by `MimeTypeAssociationStore::plan`. Entries the plan couldn't resolve
+    /// are left alone. Will return an error if a planned application is no longer in `desktop_entry_store`, or if
+    /// there are no user customizable scopes in the chain.
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    pub fn apply_plan(
+        &mut self,
+        plan: &Plan,
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Result<(), Error> {
+        let find_desktop_entry = |id: &DesktopEntryId| {
+            desktop_entry_store
+                .find_desktop_entry_with_id(id)
+                .ok_or_else(|| Error::InvalidApplication(id.clone()))
+        };
+
+        for change in plan.changes.iter() {
+            match change {
+                PlannedChange::SetDefaultApplication { mime_type, to, .. } => {
+                    self.set_default_handler_for_mime_type(mime_type, find_desktop_entry(to)?)?;
+                }
+                PlannedChange::ResetDefaultApplication { mime_type, .. } => {
+                    self.remove_assigned_applications_for(mime_type);
+                }
+                PlannedChange::AddAssociation {
+                    mime_type,
+                    desktop_entry,
+                } => {
+                    self.add_added_associations(
+                        mime_type,
+                        std::slice::from_ref(find_desktop_entry(desktop_entry)?),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check each mimeapps.list in the chain for problems: lines which can't be parsed and are ignored, entries
+    /// overridden by a later duplicate, keys which are aliases of another mime type, and applications which
+    /// aren't installed or don't declare support for the mime type they're assigned. Diagnostics are in chain
+    /// order, then line order.
+    pub fn lint(
+        &self,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> Vec<LintDiagnostic> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.lint(desktop_entry_store, mime_info_store))
+            .collect()
+    }
+
+    /// Apply the suggested fixes of `diagnostics` found by `lint` in user customizable scopes, returning those
+    /// applied. Diagnostics of other files are skipped, since they aren't the user's to edit.
+    /// Note: Changes won't be commited until `MimeAssociationsStore::save` is called.
+    pub fn fix_lint<'a>(&mut self, diagnostics: &'a [LintDiagnostic]) -> Vec<&'a LintDiagnostic> {
+        let mut fixed = vec![];
+        for scope in self.user_scopes_iter_mut() {
+            let scope_diagnostics = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.path == scope.file_path)
+                .collect::<Vec<_>>();
+            fixed.extend(scope.fix_lint(&scope_diagnostics));
+        }
+        fixed
+    }
+
+    /// Copy the user customizable scopes, to be restored with `MimeTypeAssociationStore::restore_user_scopes`,
+    /// e.g., to undo a change spanning many mime types, such as fixing lint problems
+    pub fn snapshot_user_scopes(&self) -> Vec<MimeTypeAssociationScope> {
+        self.user_scopes_iter().cloned().collect()
+    }
+
+    /// Restore the associations of the user customizable scopes to those of a `snapshot` taken with
+    /// `MimeTypeAssociationStore::snapshot_user_scopes`. Restored scopes are dirty, even if the snapshot was
+    /// taken before a save; saving them still merges changes other programs made since.
+    pub fn restore_user_scopes(&mut self, snapshot: &[MimeTypeAssociationScope]) {
+        for scope in self.user_scopes_iter_mut() {
+            let Some(previous) = snapshot.iter().find(|s| s.file_path == scope.file_path) else {
+                continue;
+            };
+            if scope.added_associations == previous.added_associations
+                && scope.removed_associations == previous.removed_associations
+                && scope.default_applications == previous.default_applications
+                && scope.document == previous.document
+            {
+                continue;
+            }
+
+            scope.added_associations = previous.added_associations.clone();
+            scope.removed_associations = previous.removed_associations.clone();
+            scope.default_applications = previous.default_applications.clone();
+            scope.document = previous.document.clone();
+            scope.is_dirty = true;
+        }
+    }
+
     /// Returns true if any user customizable scope is dirty
     pub fn is_dirty(&self) -> bool {
         for scope in self.scopes.iter() {
@@ -572,17 +2129,38 @@ impl MimeTypeAssociationStore {
         false
     }
 
+    /// Returns the paths of the user customizable scopes which are dirty, i.e., the files `save` will write
+    pub fn dirty_sources(&self) -> Vec<&Path> {
+        self.user_scopes_iter()
+            .filter(|s| s.is_dirty)
+            .map(|s| s.file_path.deref())
+            .collect()
+    }
+
     /// Commit changes to user customizable scopes. This will write to the user's `mimeapps.list` file.
-    pub fn save(&mut self) -> anyhow::Result<()> {
+    pub fn save(&mut self) -> Result<(), Error> {
+        // snapshot each file before overwriting it, so that saving can be undone
+        let backups = self.backups();
+        let mut saved = false;
         for scope in self.scopes.iter_mut() {
             if scope.is_user_customizable && scope.is_dirty {
+                backups.snapshot(&scope.file_path)?;
                 scope.save()?;
+                saved = true;
             }
         }
 
+        if saved {
+            backups.prune()?;
+        }
         Ok(())
     }
 
+    /// The store of the snapshots taken of the user's mimeapps.list files before each save
+    pub fn backups(&self) -> BackupStore {
+        BackupStore::for_environment(&self.environment)
+    }
+
     /// Find matching mimetypes for a wildcard. If the passed-in mime-type is
     /// not a wildcard, find the first match in storage.
     pub fn find_matching_mimetypes(&self, mime_type: &MimeType) -> Vec<&MimeType> {
@@ -610,6 +2188,7 @@ impl MimeTypeAssociationStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ProfileFormat;
 
     fn test_sys_mimeapps_list() -> PathBuf {
         path("test-data/usr/share/applications/mimeapps.list")
@@ -637,6 +2216,15 @@ mod tests {
         path("test-data/usr/share/applications")
     }
 
+    /// An environment whose config home holds the test user mimeapps lists
+    fn test_environment() -> Environment {
+        Environment::for_home(path("test-data/home"))
+            .with_config_home(path("test-data/config"))
+            .with_config_dirs(vec![])
+            .with_data_home(path("test-data/local/share"))
+            .with_data_dirs(vec![path("test-data/usr/share")])
+    }
+
     fn path(p: &str) -> PathBuf {
         let cwd = std::env::current_dir().unwrap();
         cwd.join(p)
@@ -648,18 +2236,21 @@ mod tests {
     {
         let path = path.as_ref();
         if path.exists() {
-            let _ = std::fs::remove_file(&path);
+            let _ = std::fs::remove_file(path);
         }
     }
 
     /// Creates a MimeAssociationStore with the first scope user editable, the others not
     fn create_test_associations() -> anyhow::Result<MimeTypeAssociationStore> {
-        let mut associations = MimeTypeAssociationStore::load(&[
-            test_user_mimeapps_list(),
-            test_user_defaults_list(),
-            test_gnome_mimeapps_list(),
-            test_sys_mimeapps_list(),
-        ])?;
+        let mut associations = MimeTypeAssociationStore::load_for_environment(
+            &[
+                test_user_mimeapps_list(),
+                test_user_defaults_list(),
+                test_gnome_mimeapps_list(),
+                test_sys_mimeapps_list(),
+            ],
+            &test_environment(),
+        )?;
 
         // we need to make first 2 scopes user writable for testing
         associations.scopes[0].is_user_customizable = true;
@@ -686,14 +2277,21 @@ mod tests {
 
     #[test]
     fn mime_associations_load() {
-        assert!(MimeTypeAssociationScope::load(test_sys_mimeapps_list()).is_ok());
-        assert!(MimeTypeAssociationScope::load(test_gnome_mimeapps_list()).is_ok());
-        assert!(MimeTypeAssociationScope::load(test_user_mimeapps_list()).is_ok());
+        assert!(
+            MimeTypeAssociationScope::load(test_sys_mimeapps_list(), &test_environment()).is_ok()
+        );
+        assert!(
+            MimeTypeAssociationScope::load(test_gnome_mimeapps_list(), &test_environment()).is_ok()
+        );
+        assert!(
+            MimeTypeAssociationScope::load(test_user_mimeapps_list(), &test_environment()).is_ok()
+        );
     }
 
     #[test]
     fn mime_associations_load_expected_data() -> anyhow::Result<()> {
-        let associations = MimeTypeAssociationScope::load(test_user_mimeapps_list())?;
+        let associations =
+            MimeTypeAssociationScope::load(test_user_mimeapps_list(), &test_environment())?;
 
         let png = MimeType::parse("image/png")?;
         let gimp = DesktopEntryId::parse("org.gimp.GIMP.desktop")?;
@@ -751,14 +2349,137 @@ mod tests {
     }
 
     #[test]
-    fn default_application_skips_user_associations() -> anyhow::Result<()> {
+    fn inherited_default_application_falls_back_to_ancestors() -> anyhow::Result<()> {
         let associations = create_test_associations()?;
+        let mime_info_store = MimeTypeInfoStore::load(&[path(
+            "test-data/usr/share/mime/packages/freedesktop.org.xml",
+        )])?;
 
-        let image_bmp = MimeType::parse("image/bmp")?;
-        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        let text_editor_id = DesktopEntryId::parse("org.gnome.TextEditor.desktop")?;
+        let firefox_id = DesktopEntryId::parse("org.mozilla.firefox.desktop")?;
+
+        // text/x-csrc has no assignment, but its parent text/plain does
+        let text_x_csrc = MimeType::parse("text/x-csrc")?;
+        assert!(associations.default_application_for(&text_x_csrc).is_none());
         assert_eq!(
-            associations.system_default_application_for(&image_bmp),
-            Some(&eog_id)
+            associations.inherited_default_application_for(&text_x_csrc, &mime_info_store),
+            Some(&text_editor_id)
+        );
+
+        // text/x-c is an alias of text/x-csrc
+        let text_x_c = MimeType::parse("text/x-c")?;
+        assert_eq!(
+            associations.inherited_default_application_for(&text_x_c, &mime_info_store),
+            Some(&text_editor_id)
+        );
+
+        // shell scripts are executables and text; only the latter has a handler
+        let shellscript = MimeType::parse("application/x-shellscript")?;
+        assert_eq!(
+            associations.inherited_default_application_for(&shellscript, &mime_info_store),
+            Some(&text_editor_id)
+        );
+
+        // a type's own assignment wins over its ancestors'
+        let html = MimeType::parse("text/html")?;
+        assert_eq!(
+            associations.inherited_default_application_for(&html, &mime_info_store),
+            Some(&firefox_id)
+        );
+
+        // nothing to inherit from
+        let unknown = MimeType::parse("application/x-not-a-real-type")?;
+        assert!(associations
+            .inherited_default_application_for(&unknown, &mime_info_store)
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_application_falls_back_to_first_installed_entry() -> anyhow::Result<()> {
+        let (entries, associations) = create_test_entries_and_associations()?;
+
+        // image/gif=org.gimp.GIMP.desktop;photopea.desktop; and GIMP isn't installed
+        let image_gif = MimeType::parse("image/gif")?;
+        let gimp_id = DesktopEntryId::parse("org.gimp.GIMP.desktop")?;
+        let photopea_id = DesktopEntryId::parse("photopea.desktop")?;
+        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+
+        assert_eq!(
+            associations.default_application_for(&image_gif),
+            Some(&gimp_id)
+        );
+        assert_eq!(
+            associations.default_applications_for(&image_gif),
+            vec![&gimp_id, &photopea_id, &eog_id]
+        );
+
+        // photopea, the fallback in the user's list, beats eog from the system scopes
+        assert_eq!(
+            associations
+                .installed_default_application_for(&image_gif, &entries)
+                .map(|e| e.id()),
+            Some(&photopea_id)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_default_handlers_assigns_preference_list() -> anyhow::Result<()> {
+        let (entries, mut associations) = create_test_entries_and_associations()?;
+
+        let image_tiff = MimeType::parse("image/tiff")?;
+        let gimp_id = DesktopEntryId::parse("org.gimp.GIMP.desktop")?;
+        let photopea_id = DesktopEntryId::parse("photopea.desktop")?;
+
+        // GIMP isn't installed, but may be on other machines sharing this config
+        associations.set_default_handlers_for_mime_type(
+            &image_tiff,
+            &[gimp_id.clone(), photopea_id.clone()],
+            &entries,
+        )?;
+        assert!(associations.is_dirty());
+        assert_eq!(
+            associations.scopes[0].default_applications[&image_tiff],
+            vec![gimp_id.clone(), photopea_id.clone()]
+        );
+        assert_eq!(
+            associations
+                .installed_default_application_for(&image_tiff, &entries)
+                .map(|e| e.id()),
+            Some(&photopea_id)
+        );
+
+        // installed apps must support the mime type
+        let inode_directory = MimeType::parse("inode/directory")?;
+        assert!(matches!(
+            associations.set_default_handlers_for_mime_type(
+                &inode_directory,
+                &[photopea_id],
+                &entries
+            ),
+            Err(Error::UnsupportedMimeType { .. })
+        ));
+
+        assert!(matches!(
+            associations.set_default_handlers_for_mime_type(&image_tiff, &[], &entries),
+            Err(Error::NoDesktopEntries(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_application_skips_user_associations() -> anyhow::Result<()> {
+        let associations = create_test_associations()?;
+
+        let image_bmp = MimeType::parse("image/bmp")?;
+        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        assert_eq!(
+            associations.system_default_application_for(&image_bmp),
+            Some(&eog_id)
         );
 
         Ok(())
@@ -786,7 +2507,7 @@ mod tests {
         );
 
         // assign photopea
-        associations.set_default_handler_for_mime_type(&image_tiff, &photopea)?;
+        associations.set_default_handler_for_mime_type(&image_tiff, photopea)?;
         assert_eq!(
             associations.default_application_for(&image_tiff),
             Some(&photopea_id)
@@ -859,7 +2580,7 @@ mod tests {
         let photopea = desktop_entry_store
             .find_desktop_entry_with_id(&photopea_id)
             .unwrap();
-        associations.set_default_handler_for_mime_type(&image_bmp, &photopea)?;
+        associations.set_default_handler_for_mime_type(&image_bmp, photopea)?;
         assert!(!associations.is_dirty());
 
         Ok(())
@@ -886,7 +2607,7 @@ mod tests {
         let eog = desktop_entry_store
             .find_desktop_entry_with_id(&eog_id)
             .unwrap();
-        associations.set_default_handler_for_mime_type(&image_bmp, &eog)?;
+        associations.set_default_handler_for_mime_type(&image_bmp, eog)?;
         assert!(associations.is_dirty());
 
         Ok(())
@@ -912,11 +2633,11 @@ mod tests {
 
         associations.scopes[0]
             .default_applications
-            .insert(fake_pdf_assignment.0, fake_pdf_assignment.1.clone());
+            .insert(fake_pdf_assignment.0, vec![fake_pdf_assignment.1.clone()]);
 
         associations.scopes[0]
             .default_applications
-            .insert(fake_psd_assignment.0, fake_psd_assignment.1.clone());
+            .insert(fake_psd_assignment.0, vec![fake_psd_assignment.1.clone()]);
 
         let result = associations.prune_orphaned_application_assignments(&desktop_entry_store);
 
@@ -1004,7 +2725,7 @@ mod tests {
             associations.scopes[1]
                 .default_applications
                 .get(&x_scheme_handler_jetbrains)
-                .unwrap()
+                .unwrap()[0]
                 .id(),
             "jetbrains-toolbox.desktop"
         );
@@ -1013,7 +2734,7 @@ mod tests {
             associations.scopes[1]
                 .default_applications
                 .get(&x_scheme_handler_fleet)
-                .unwrap()
+                .unwrap()[0]
                 .id(),
             "jetbrains-fleet.desktop"
         );
@@ -1050,7 +2771,7 @@ mod tests {
         let image_png = MimeType::parse("image/png")?;
 
         let results = associations.find_matching_mimetypes(&image_star);
-        assert!(results.len() > 0);
+        assert!(!results.is_empty());
         assert!(results.contains(&&image_bmp));
         assert!(results.contains(&&image_png));
 
@@ -1079,7 +2800,7 @@ mod tests {
             Some(&photopea_id)
         );
 
-        associations.set_default_handler_for_mime_type(&image_bmp, &eog)?;
+        associations.set_default_handler_for_mime_type(&image_bmp, eog)?;
 
         assert_eq!(
             associations.default_application_for(&image_bmp),
@@ -1106,12 +2827,93 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn removed_associations_hide_lower_priority_handlers() -> anyhow::Result<()> {
+        let (entries, associations) = create_test_entries_and_associations()?;
+
+        // defaults.list removes chrome from text/html, but the higher priority mimeapps.list adds it
+        let html = MimeType::parse("text/html")?;
+        let chrome = DesktopEntryId::parse("google-chrome.desktop")?;
+        assert!(associations
+            .removed_associations_for(&html)
+            .contains(&chrome));
+        assert!(associations.added_associations_for(&html).contains(&chrome));
+
+        // photopea declares image/tiff support, but defaults.list removes it
+        let image_tiff = MimeType::parse("image/tiff")?;
+        let photopea_id = DesktopEntryId::parse("photopea.desktop")?;
+        let evince_id = DesktopEntryId::parse("org.gnome.Evince.desktop")?;
+        let handlers = associations
+            .desktop_entries_for(&image_tiff, &entries)
+            .iter()
+            .map(|e| e.id().clone())
+            .collect::<Vec<_>>();
+        assert!(!handlers.contains(&photopea_id));
+        assert!(handlers.contains(&evince_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_and_restore_association_works() -> anyhow::Result<()> {
+        let (entries, mut associations) = create_test_entries_and_associations()?;
+
+        let html = MimeType::parse("text/html")?;
+        let firefox = DesktopEntryId::parse("org.mozilla.firefox.desktop")?;
+        let image_bmp = MimeType::parse("image/bmp")?;
+        let photopea_id = DesktopEntryId::parse("photopea.desktop")?;
+
+        // removing drops the added association from the user scope, and hides it from below
+        assert!(associations.remove_association(&html, &firefox)?);
+        assert!(associations.is_dirty());
+        assert!(!associations
+            .added_associations_for(&html)
+            .contains(&firefox));
+        assert!(associations.scopes[0].removed_associations[&html].contains(&firefox));
+
+        // removing an association declared by the desktop entry hides it from the handlers
+        assert!(associations.remove_association(&image_bmp, &photopea_id)?);
+        assert!(!associations
+            .desktop_entries_for(&image_bmp, &entries)
+            .iter()
+            .any(|e| e.id() == &photopea_id));
+
+        // removing twice is a no-op
+        assert!(!associations.remove_association(&image_bmp, &photopea_id)?);
+
+        // restoring brings it back
+        assert!(associations.restore_association(&image_bmp, &photopea_id));
+        assert!(associations
+            .desktop_entries_for(&image_bmp, &entries)
+            .iter()
+            .any(|e| e.id() == &photopea_id));
+        assert!(!associations.restore_association(&image_bmp, &photopea_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_association_errors_without_writeable_scope() -> anyhow::Result<()> {
+        let mut associations = create_test_associations()?;
+        associations.scopes[0].is_user_customizable = false;
+        associations.scopes[1].is_user_customizable = false;
+
+        let html = MimeType::parse("text/html")?;
+        let firefox = DesktopEntryId::parse("org.mozilla.firefox.desktop")?;
+        assert!(matches!(
+            associations.remove_association(&html, &firefox),
+            Err(Error::NoWritableScope)
+        ));
+
+        Ok(())
+    }
+
     // serialization test
 
     #[test]
     fn added_associations_line_roundtrip_works() -> anyhow::Result<()> {
         let input = "image/png=org.gimp.GIMP.desktop";
-        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(&input)?;
+        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(input)?;
         let output = MimeTypeAssociationScope::generate_added_associations_line(
             &mime_type,
             &desktop_entries,
@@ -1119,7 +2921,7 @@ mod tests {
         assert_eq!(input, &output);
 
         let input = "x-scheme-handler/https=org.mozilla.firefox.desktop;google-chrome.desktop";
-        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(&input)?;
+        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(input)?;
         let output = MimeTypeAssociationScope::generate_added_associations_line(
             &mime_type,
             &desktop_entries,
@@ -1131,10 +2933,18 @@ mod tests {
     #[test]
     fn default_applications_line_roundtrip_works() -> anyhow::Result<()> {
         let input = "text/html=org.mozilla.firefox.desktop";
-        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(&input)?;
+        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(input)?;
+        let output = MimeTypeAssociationScope::generate_default_application_line(
+            &mime_type,
+            &desktop_entries,
+        );
+        assert_eq!(input, &output);
+
+        let input = "image/png=org.gimp.GIMP.desktop;org.gnome.eog.desktop";
+        let (mime_type, desktop_entries) = MimeTypeAssociationScope::parse_line(input)?;
         let output = MimeTypeAssociationScope::generate_default_application_line(
             &mime_type,
-            &desktop_entries[0],
+            &desktop_entries,
         );
         assert_eq!(input, &output);
         Ok(())
@@ -1146,10 +2956,12 @@ mod tests {
         let output_path = path("test-data/config/mimeapps.list.copy");
         delete_file(&output_path);
 
-        let input_mimeassociations = MimeTypeAssociationScope::load(&input_path)?;
+        let input_mimeassociations =
+            MimeTypeAssociationScope::load(&input_path, &test_environment())?;
         input_mimeassociations.write_to_path(&output_path)?;
 
-        let copy_mimeassociations = MimeTypeAssociationScope::load(&output_path)?;
+        let copy_mimeassociations =
+            MimeTypeAssociationScope::load(&output_path, &test_environment())?;
 
         assert_eq!(
             input_mimeassociations.added_associations,
@@ -1162,6 +2974,284 @@ mod tests {
 
         delete_file(&output_path);
 
+        // defaults.list has a [Removed Associations] section
+        let input_path = path("test-data/config/defaults.list");
+        let output_path = path("test-data/config/defaults.list.copy");
+        delete_file(&output_path);
+
+        let input_mimeassociations =
+            MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+        assert!(!input_mimeassociations.removed_associations.is_empty());
+        input_mimeassociations.write_to_path(&output_path)?;
+
+        let copy_mimeassociations =
+            MimeTypeAssociationScope::load(&output_path, &test_environment())?;
+        assert_eq!(
+            input_mimeassociations.removed_associations,
+            copy_mimeassociations.removed_associations
+        );
+
+        delete_file(&output_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimeassociationscope_write_is_lossless() -> anyhow::Result<()> {
+        let input_path = path("test-data/config/mimeapps.list");
+        let output_path = path("test-data/config/mimeapps.list.lossless");
+        delete_file(&output_path);
+
+        let input = std::fs::read_to_string(&input_path)?;
+        let mut scope = MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+
+        // an unmodified scope writes back byte-for-byte, trailing semicolons and all
+        scope.write_to_path(&output_path)?;
+        assert_eq!(std::fs::read_to_string(&output_path)?, input);
+
+        // a single change rewrites a single line
+        let image_bmp = MimeType::parse("image/bmp")?;
+        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        scope.default_applications.insert(image_bmp, vec![eog_id]);
+        scope.write_to_path(&output_path)?;
+        let output = std::fs::read_to_string(&output_path)?;
+        assert_eq!(
+            output,
+            input.replace(
+                "image/bmp=photopea.desktop\n",
+                "image/bmp=org.gnome.eog.desktop\n"
+            )
+        );
+
+        delete_file(&output_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimeassociationscope_write_preserves_comments_and_unknown_sections() -> anyhow::Result<()> {
+        let input_path = path("test-data/config/mimeapps.list.commented");
+        let output_path = path("test-data/config/mimeapps.list.commented.copy");
+        delete_file(&output_path);
+
+        std::fs::write(
+            &input_path,
+            "# my defaults\n\
+            [Default Applications]\n\
+            text/html=org.mozilla.firefox.desktop\n\
+            image/png=org.gimp.GIMP.desktop;\n\
+            \n\
+            [X-Custom]\n\
+            foo/bar=baz.desktop\n",
+        )?;
+
+        let mut scope = MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+        let html = MimeType::parse("text/html")?;
+        let image_bmp = MimeType::parse("image/bmp")?;
+        let photopea_id = DesktopEntryId::parse("photopea.desktop")?;
+        scope.default_applications.remove(&html);
+        scope
+            .default_applications
+            .insert(image_bmp, vec![photopea_id]);
+        scope.write_to_path(&output_path)?;
+
+        assert_eq!(
+            std::fs::read_to_string(&output_path)?,
+            "# my defaults\n\
+            [Default Applications]\n\
+            image/png=org.gimp.GIMP.desktop;\n\
+            image/bmp=photopea.desktop\n\
+            \n\
+            [X-Custom]\n\
+            foo/bar=baz.desktop\n"
+        );
+
+        delete_file(&input_path);
+        delete_file(&output_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_to_user_mimeapps_list_ahead_of_desktop_specific_list() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-desktop-specific-write-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config_home = dir.join("config");
+        std::fs::create_dir_all(&config_home)?;
+        let gnome_mimeapps_list = config_home.join("gnome-mimeapps.list");
+        std::fs::write(
+            &gnome_mimeapps_list,
+            "[Default Applications]\nimage/png=org.gimp.GIMP.desktop\ntext/html=firefox.desktop\n",
+        )?;
+
+        let environment = Environment::for_home(&dir)
+            .with_config_home(&config_home)
+            .with_config_dirs(vec![])
+            .with_data_dirs(vec![])
+            .with_current_desktops(vec!["GNOME".to_string()]);
+        let paths = environment.mimeapps_lists_paths()?;
+        assert_eq!(
+            paths,
+            [
+                gnome_mimeapps_list.clone(),
+                config_home.join("mimeapps.list")
+            ]
+        );
+        let mut associations =
+            MimeTypeAssociationStore::load_for_environment(&paths, &environment)?;
+
+        // the assignment goes to mimeapps.list, read by every desktop, and the desktop's own entry no longer overrides it
+        let png = MimeType::parse("image/png")?;
+        let eog = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        let no_applications = DesktopEntryStore::load::<PathBuf>(&[])?;
+        associations.set_default_handlers_for_mime_type(
+            &png,
+            std::slice::from_ref(&eog),
+            &no_applications,
+        )?;
+        assert_eq!(associations.default_application_for(&png), Some(&eog));
+        associations.save()?;
+
+        assert_eq!(
+            std::fs::read_to_string(config_home.join("mimeapps.list"))?,
+            "[Default Applications]\nimage/png=org.gnome.eog.desktop\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&gnome_mimeapps_list)?,
+            "[Default Applications]\ntext/html=firefox.desktop\n"
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn save_merges_concurrent_external_edits() -> anyhow::Result<()> {
+        let input_path = path("test-data/config/mimeapps.list.concurrent");
+        std::fs::write(
+            &input_path,
+            "[Default Applications]\n\
+            text/html=firefox.desktop\n\
+            image/png=org.gimp.GIMP.desktop\n",
+        )?;
+
+        let mut scope = MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+        scope.default_applications.insert(
+            MimeType::parse("image/png")?,
+            vec![DesktopEntryId::parse("org.gnome.eog.desktop")?],
+        );
+        scope.is_dirty = true;
+
+        // another program changes an entry we didn't, and adds one
+        std::fs::write(
+            &input_path,
+            "# edited elsewhere\n\
+            [Default Applications]\n\
+            text/html=chromium.desktop\n\
+            image/png=org.gimp.GIMP.desktop\n\
+            text/plain=org.gnome.TextEditor.desktop\n",
+        )?;
+
+        scope.save()?;
+        assert_eq!(
+            std::fs::read_to_string(&input_path)?,
+            "# edited elsewhere\n\
+            [Default Applications]\n\
+            text/html=chromium.desktop\n\
+            image/png=org.gnome.eog.desktop\n\
+            text/plain=org.gnome.TextEditor.desktop\n"
+        );
+
+        // no temp files are left behind
+        let temp_files = std::fs::read_dir(path("test-data/config"))?
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(temp_files, 0);
+
+        delete_file(&input_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_merges_external_edits_against_the_saved_file() -> anyhow::Result<()> {
+        let input_path = path("test-data/config/mimeapps.list.restored");
+        std::fs::write(
+            &input_path,
+            "[Default Applications]\nimage/png=org.gimp.GIMP.desktop\n",
+        )?;
+
+        let image_png = MimeType::parse("image/png")?;
+        let mut scope = MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+        let snapshot = scope.clone();
+        scope.default_applications.insert(
+            image_png.clone(),
+            vec![DesktopEntryId::parse("org.gnome.eog.desktop")?],
+        );
+        scope.is_dirty = true;
+        scope.save()?;
+
+        // undo the saved change, as `MimeTypeAssociationStore::restore_user_scopes` does
+        scope.default_applications = snapshot.default_applications.clone();
+        scope.document = snapshot.document.clone();
+        scope.is_dirty = true;
+
+        // another program adds an entry; the undone one is merged against the file as saved, so isn't lost
+        std::fs::write(
+            &input_path,
+            "[Default Applications]\n\
+            image/png=org.gnome.eog.desktop\n\
+            text/html=chromium.desktop\n",
+        )?;
+
+        scope.save()?;
+        let saved = MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+        assert_eq!(
+            saved.default_applications[&image_png],
+            [DesktopEntryId::parse("org.gimp.GIMP.desktop")?]
+        );
+        assert_eq!(
+            saved.default_applications[&MimeType::parse("text/html")?],
+            [DesktopEntryId::parse("chromium.desktop")?]
+        );
+
+        delete_file(&input_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_fails_on_conflicting_external_edits() -> anyhow::Result<()> {
+        let input_path = path("test-data/config/mimeapps.list.conflicting");
+        std::fs::write(
+            &input_path,
+            "[Default Applications]\nimage/png=org.gimp.GIMP.desktop\n",
+        )?;
+
+        let image_png = MimeType::parse("image/png")?;
+        let mut scope = MimeTypeAssociationScope::load(&input_path, &test_environment())?;
+        scope.default_applications.insert(
+            image_png.clone(),
+            vec![DesktopEntryId::parse("org.gnome.eog.desktop")?],
+        );
+        scope.is_dirty = true;
+
+        let external = "[Default Applications]\nimage/png=org.kde.krita.desktop\n";
+        std::fs::write(&input_path, external)?;
+
+        match scope.save() {
+            Err(Error::SaveConflict { mime_types, .. }) => assert_eq!(mime_types, [image_png]),
+            other => panic!("expected a save conflict, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(&input_path)?, external);
+        assert!(scope.is_dirty);
+
+        delete_file(&input_path);
+
         Ok(())
     }
 
@@ -1184,7 +3274,7 @@ mod tests {
         );
 
         // assign photopea
-        associations.set_default_handler_for_mime_type(&image_tiff, &photopea)?;
+        associations.set_default_handler_for_mime_type(&image_tiff, photopea)?;
         assert_eq!(
             associations.default_application_for(&image_tiff),
             Some(&photopea_id)
@@ -1206,9 +3296,10 @@ mod tests {
         // photopea doesn't support inode/directory
         let inode_directory = MimeType::parse("inode/directory")?;
 
-        assert!(associations
-            .set_default_handler_for_mime_type(&inode_directory, &photopea)
-            .is_err());
+        assert!(matches!(
+            associations.set_default_handler_for_mime_type(&inode_directory, photopea),
+            Err(Error::UnsupportedMimeType { mime_type, .. }) if mime_type == inode_directory
+        ));
 
         Ok(())
     }
@@ -1224,9 +3315,712 @@ mod tests {
         let image_tiff = MimeType::parse("image/tiff")?;
 
         // assignment should fail since no writable scope is set
+        assert!(matches!(
+            associations.set_default_handler_for_mime_type(&image_tiff, photopea),
+            Err(Error::NoWritableScope)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn schemes_include_those_declared_by_applications() -> anyhow::Result<()> {
+        let associations = MimeTypeAssociationStore::load_for_environment(
+            &[path("test-data/open/mimeapps.list")],
+            &test_environment(),
+        )?;
+        let entries =
+            DesktopEntryStore::load(&[test_user_applications(), test_sys_applications()])?;
+
+        // https is assigned in the list; rtsp is only declared by an installed application (Totem)
+        let schemes = associations.schemes(&entries);
+        assert!(schemes.iter().any(|scheme| scheme == "https"));
+        assert!(schemes.iter().any(|scheme| scheme == "rtsp"));
+        assert!(schemes.windows(2).all(|pair| pair[0] < pair[1]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn assigns_and_reports_roles() -> anyhow::Result<()> {
+        let mut associations = MimeTypeAssociationStore::load_for_environment(
+            &[path("test-data/open/mimeapps.list")],
+            &test_environment(),
+        )?;
+        associations.scopes[0].is_user_customizable = true;
+        associations.verify_app_is_valid = false;
+        let desktop_entry_store = DesktopEntryStore::load(&[path("test-data/open/applications")])?;
+        let role_store = RoleStore::builtin();
+
+        let browser_id = DesktopEntryId::parse("stub-browser.desktop")?;
+        let browser = desktop_entry_store
+            .find_desktop_entry_with_id(&browser_id)
+            .unwrap();
+        let browser_role = role_store.find_role("browser").unwrap();
+        let text_html = MimeType::parse("text/html")?;
+        let https = MimeType::for_scheme("https")?;
+
+        // stub-browser handles https, but not text/html, which it supports
+        let handlers = associations.role_handlers(browser_role, &desktop_entry_store);
+        assert_eq!(handlers.owner(), Some(&browser_id));
+        assert_eq!(
+            handlers.unowned_mime_types(),
+            std::slice::from_ref(&text_html)
+        );
+        assert!(handlers.is_partially_owned());
+        assert!(!handlers.is_fully_owned());
+        assert_eq!(
+            associations.partially_owned_roles(&role_store, &desktop_entry_store),
+            [browser_role]
+        );
+
+        // assigning the role assigns only the types stub-browser supports
+        let assigned = associations.set_default_handler_for_role(browser_role, browser)?;
+        assert_eq!(assigned, [https, text_html.clone()]);
+        assert_eq!(
+            associations.default_application_for(&text_html),
+            Some(&browser_id)
+        );
+
+        let handlers = associations.role_handlers(browser_role, &desktop_entry_store);
+        assert!(handlers.is_fully_owned());
         assert!(associations
-            .set_default_handler_for_mime_type(&image_tiff, &photopea)
+            .partially_owned_roles(&role_store, &desktop_entry_store)
+            .is_empty());
+
+        // stub-browser can't fill the photos role at all
+        let photos_role = role_store.find_role("photos").unwrap();
+        assert!(matches!(
+            associations.set_default_handler_for_role(photos_role, browser),
+            Err(Error::UnsupportedRole { .. })
+        ));
+        assert_eq!(
+            associations
+                .role_handlers(photos_role, &desktop_entry_store)
+                .owner()
+                .map(DesktopEntryId::id),
+            Some("stub-viewer.desktop")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_and_launches_applications_for_paths_and_uris() -> anyhow::Result<()> {
+        let associations = MimeTypeAssociationStore::load_for_environment(
+            &[path("test-data/open/mimeapps.list")],
+            &test_environment(),
+        )?;
+        let desktop_entry_store = DesktopEntryStore::load(&[path("test-data/open/applications")])?;
+        let mime_info_store = MimeTypeInfoStore::load(&[path(
+            "test-data/usr/share/mime/packages/freedesktop.org.xml",
+        )])?;
+
+        let resolve = |target: &str| {
+            let mime_type = mime_info_store.mime_type_for_path_or_uri(target);
+            associations
+                .resolve_application_for(&mime_type, &desktop_entry_store, &mime_info_store)
+                .map(|desktop_entry| desktop_entry.id().id().to_string())
+        };
+
+        // missing.desktop is skipped in favor of the next installed default
+        assert_eq!(resolve("photo.png").as_deref(), Some("stub-viewer.desktop"));
+        assert_eq!(
+            resolve("https://example.com/").as_deref(),
+            Some("stub-browser.desktop")
+        );
+        // no default for text/html, but stub-browser supports it
+        assert_eq!(
+            resolve("page.html").as_deref(),
+            Some("stub-browser.desktop")
+        );
+        // no default or supporting application for text/x-csrc, so it falls back to its ancestor text/plain
+        assert_eq!(resolve("main.c").as_deref(), Some("stub-viewer.desktop"));
+        assert_eq!(resolve("mailto:someone@example.com"), None);
+
+        // launch the resolved handler, which records its argv
+        let output =
+            std::env::temp_dir().join(format!("mimeassoc-stub-output-{}", std::process::id()));
+        let viewer = desktop_entry_store
+            .find_desktop_entry_with_id(&DesktopEntryId::parse("stub-viewer.desktop")?)
+            .unwrap();
+        let command_lines = viewer.command_line(&["photo.png", "file:///tmp/other%20photo.png"])?;
+        assert_eq!(command_lines.len(), 1);
+        let status = command_lines[0]
+            .command()
+            .env("MIMEASSOC_STUB_OUTPUT", &output)
+            .status()?;
+        let recorded = std::fs::read_to_string(&output);
+        delete_file(&output);
+
+        assert!(status.success());
+        assert_eq!(recorded?, "--view\nphoto.png\n/tmp/other photo.png\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn traces_resolution_through_scopes_and_fallbacks() -> anyhow::Result<()> {
+        let associations = MimeTypeAssociationStore::load_for_environment(
+            &[
+                path("test-data/open/mimeapps.list"),
+                test_sys_mimeapps_list(),
+            ],
+            &test_environment(),
+        )?;
+        let desktop_entry_store = DesktopEntryStore::load(&[path("test-data/open/applications")])?;
+        let mime_info_store = MimeTypeInfoStore::load(&[path(
+            "test-data/usr/share/mime/packages/freedesktop.org.xml",
+        )])?;
+
+        let traced = |id: &str, outcome| -> anyhow::Result<TracedApplication> {
+            Ok(TracedApplication {
+                desktop_entry: DesktopEntryId::parse(id)?,
+                outcome,
+            })
+        };
+
+        // missing.desktop isn't installed, so stub-viewer is chosen, overriding the system scope's eog
+        let png = MimeType::parse("image/png")?;
+        let trace = associations.trace_resolution(&png, &desktop_entry_store, &mime_info_store);
+        assert_eq!(
+            trace.result,
+            Some(DesktopEntryId::parse("stub-viewer.desktop")?)
+        );
+        let [TraceStep::Scope(user_step), TraceStep::Scope(system_step)] = trace.steps.as_slice()
+        else {
+            panic!("expected one step per scope, got {:?}", trace.steps);
+        };
+        assert_eq!(user_step.line, Some(3));
+        assert_eq!(user_step.origin, CandidateOrigin::Requested);
+        assert_eq!(
+            user_step.applications,
+            [
+                traced("missing.desktop", TracedOutcome::NotInstalled)?,
+                traced("stub-viewer.desktop", TracedOutcome::Chosen)?,
+            ]
+        );
+        assert_eq!(system_step.path, test_sys_mimeapps_list());
+        assert_eq!(system_step.line, Some(292));
+        assert_eq!(
+            system_step.applications,
+            [traced("org.gnome.eog.desktop", TracedOutcome::Overridden)?]
+        );
+
+        // firefox is assigned text/html by the system, but isn't installed, so the supporting stub-browser is used
+        let html = MimeType::parse("text/html")?;
+        let trace = associations.trace_resolution(&html, &desktop_entry_store, &mime_info_store);
+        assert!(trace.steps.iter().any(|step| matches!(
+            step,
+            TraceStep::Scope(step) if step.line == Some(62)
+                && step.applications == [traced("firefox.desktop", TracedOutcome::NotInstalled).unwrap()]
+        )));
+        let Some(TraceStep::Fallback(fallback)) = trace.steps.last() else {
+            panic!("expected to fall back, got {:?}", trace.steps);
+        };
+        assert_eq!(fallback.mime_type, html);
+        assert_eq!(
+            fallback.applications,
+            [traced("stub-browser.desktop", TracedOutcome::Chosen)?]
+        );
+
+        // the trace always agrees with resolution
+        for target in [
+            "photo.png",
+            "https://example.com/",
+            "page.html",
+            "main.c",
+            "mailto:a@b.c",
+        ] {
+            let mime_type = mime_info_store.mime_type_for_path_or_uri(target);
+            assert_eq!(
+                associations
+                    .trace_resolution(&mime_type, &desktop_entry_store, &mime_info_store)
+                    .result
+                    .as_ref(),
+                associations
+                    .resolve_application_for(&mime_type, &desktop_entry_store, &mime_info_store)
+                    .map(DesktopEntry::id),
+                "{}",
+                target
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn wildcard_entries_are_a_lower_precedence_match() -> anyhow::Result<()> {
+        let (entries, mut associations) = create_test_entries_and_associations()?;
+        let mime_info_store = MimeTypeInfoStore::load(&[path(
+            "test-data/usr/share/mime/packages/freedesktop.org.xml",
+        )])?;
+
+        let images = MimeType::parse("image/*")?;
+        let png = MimeType::parse("image/png")?;
+        let jpeg = MimeType::parse("image/jpeg")?;
+        let photopea_id = DesktopEntryId::parse("photopea.desktop")?;
+        let photopea = entries.find_desktop_entry_with_id(&photopea_id).unwrap();
+        let gimp_id = DesktopEntryId::parse("org.gimp.GIMP.desktop")?;
+        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        let eog = entries.find_desktop_entry_with_id(&eog_id).unwrap();
+
+        // assigning a wildcard writes a single entry, which needs an application handling at least one image type
+        associations.set_default_handler_for_mime_type(&images, photopea)?;
+        assert_eq!(
+            associations.scopes[0].default_applications[&images].first(),
+            Some(&photopea_id)
+        );
+        assert!(associations
+            .set_default_handler_for_mime_type(&MimeType::parse("audio/*")?, photopea)
             .is_err());
+        assert!(!associations.mime_types().contains(&&images));
+
+        // the scope's own image/png entry takes precedence, but the user's wildcard beats the system's image/jpeg
+        assert_eq!(associations.default_application_for(&png), Some(&gimp_id));
+        assert_eq!(
+            associations.default_application_for(&jpeg),
+            Some(&photopea_id)
+        );
+        assert_eq!(
+            associations.default_applications_for(&jpeg),
+            [&photopea_id, &eog_id]
+        );
+
+        // tracing shows the wildcard entry which decided image/jpeg
+        let trace = associations.trace_resolution(&jpeg, &entries, &mime_info_store);
+        assert!(trace.steps.iter().any(|step| matches!(
+            step,
+            TraceStep::Scope(step) if step.origin == CandidateOrigin::Wildcard
+                && step.mime_type == images
+                && step.applications.first().map(|a| a.outcome) == Some(TracedOutcome::Chosen)
+        )));
+
+        // resetting image/png leaves the wildcard in place, which now handles it
+        associations.remove_assigned_applications_for(&png);
+        assert_eq!(
+            associations.default_application_for(&png),
+            Some(&photopea_id)
+        );
+        assert!(associations.scopes[0]
+            .default_applications
+            .contains_key(&images));
+
+        // the system default needs an explicit entry to override the user's wildcard
+        associations.set_default_handler_for_mime_type(&jpeg, eog)?;
+        assert_eq!(
+            associations.user_default_application_for(&jpeg),
+            Some(&eog_id)
+        );
+        assert_eq!(associations.default_application_for(&jpeg), Some(&eog_id));
+
+        // resetting the wildcard removes it along with the entries it matches
+        associations.remove_assigned_applications_for(&images);
+        assert_eq!(associations.default_application_for(&png), Some(&eog_id));
+        assert_eq!(associations.user_default_application_for(&jpeg), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lints_and_fixes_user_mimeapps_list() -> anyhow::Result<()> {
+        let input_path = path("test-data/config/mimeapps.list.lint");
+        std::fs::write(
+            &input_path,
+            "# lint fixture\n\
+            [Default Applications]\n\
+            text/html=org.gnome.eog.desktop\n\
+            image/png=org.gimp.GIMP.desktop\n\
+            this line is junk\n\
+            text/plain=\n\
+            image/pdf=org.gnome.eog.desktop\n\
+            \n\
+            [Default Applications]\n\
+            image/png=missing.desktop;org.gnome.eog.desktop;\n\
+            video/mp4=missing.desktop;\n",
+        )?;
+
+        // malformed lines no longer prevent loading
+        let mut associations = MimeTypeAssociationStore::load_for_environment(
+            &[input_path.clone(), test_sys_mimeapps_list()],
+            &test_environment(),
+        )?;
+        let entries =
+            DesktopEntryStore::load(&[test_user_applications(), test_sys_applications()])?;
+        let mime_info_store = MimeTypeInfoStore::load(&[path(
+            "test-data/usr/share/mime/packages/freedesktop.org.xml",
+        )])?;
+        let eog_id = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        assert_eq!(
+            associations.default_application_for(&MimeType::parse("image/png")?),
+            Some(&DesktopEntryId::parse("missing.desktop")?)
+        );
+
+        let diagnostics = associations.lint(&entries, &mime_info_store);
+        let user_diagnostics = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.path == input_path)
+            .map(|diagnostic| (diagnostic.line, diagnostic.severity, &diagnostic.problem))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            user_diagnostics,
+            [
+                (
+                    3,
+                    LintSeverity::Warning,
+                    &LintProblem::UndeclaredMimeType {
+                        mime_type: MimeType::parse("text/html")?,
+                        desktop_entry: eog_id.clone(),
+                    }
+                ),
+                (
+                    4,
+                    LintSeverity::Warning,
+                    &LintProblem::DuplicateKey {
+                        mime_type: MimeType::parse("image/png")?,
+                        overridden_by: 10,
+                    }
+                ),
+                (
+                    5,
+                    LintSeverity::Error,
+                    &LintProblem::MalformedLine {
+                        text: "this line is junk".to_string()
+                    }
+                ),
+                (
+                    6,
+                    LintSeverity::Error,
+                    &LintProblem::MalformedLine {
+                        text: "text/plain=".to_string()
+                    }
+                ),
+                (
+                    7,
+                    LintSeverity::Warning,
+                    &LintProblem::AliasKey {
+                        mime_type: MimeType::parse("image/pdf")?,
+                        canonical: MimeType::parse("application/pdf")?,
+                    }
+                ),
+                // a fallback for an installed application is portable, so it's only informational
+                (
+                    10,
+                    LintSeverity::Info,
+                    &LintProblem::NotInstalled {
+                        mime_type: MimeType::parse("image/png")?,
+                        desktop_entry: DesktopEntryId::parse("missing.desktop")?,
+                    }
+                ),
+                (
+                    11,
+                    LintSeverity::Warning,
+                    &LintProblem::NotInstalled {
+                        mime_type: MimeType::parse("video/mp4")?,
+                        desktop_entry: DesktopEntryId::parse("missing.desktop")?,
+                    }
+                ),
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.path == input_path && diagnostic.line == 10)
+            .all(|diagnostic| diagnostic.fix.is_none() && !diagnostic.is_fixable()));
+
+        // the system list's uninstalled alternatives are only informational, and aren't the user's to fix
+        assert!(diagnostics.iter().any(|diagnostic| !diagnostic.is_fixable()
+            && diagnostic.severity == LintSeverity::Info
+            && matches!(diagnostic.problem, LintProblem::NotInstalled { .. })));
+        let original = std::fs::read_to_string(&input_path)?;
+        let snapshot = associations.snapshot_user_scopes();
+        let fixed = associations.fix_lint(&diagnostics);
+        // all but the fallback's diagnostic are fixed
+        assert_eq!(fixed.len(), user_diagnostics.len() - 1);
+        assert!(fixed
+            .iter()
+            .all(|diagnostic| diagnostic.path == input_path && diagnostic.is_fixable()));
+        assert!(associations.scopes[0].is_dirty);
+
+        associations.scopes[0].save()?;
+        assert_eq!(
+            std::fs::read_to_string(&input_path)?,
+            "# lint fixture\n\
+            [Default Applications]\n\
+            \n\
+            [Default Applications]\n\
+            image/png=missing.desktop;org.gnome.eog.desktop;\n\
+            application/pdf=org.gnome.eog.desktop\n"
+        );
+        let remaining = associations.lint(&entries, &mime_info_store);
+        assert!(remaining
+            .iter()
+            .all(|diagnostic| diagnostic.path != input_path || !diagnostic.is_fixable()));
+
+        // fixing what remains applies nothing, and leaves the scope clean
+        assert!(associations.fix_lint(&remaining).is_empty());
+        assert!(!associations.scopes[0].is_dirty);
+
+        // fixes can be undone by restoring a snapshot taken beforehand, even once saved
+        associations.restore_user_scopes(&snapshot);
+        assert!(associations.is_dirty());
+        associations.scopes[0].save()?;
+        // but for the empty entry, which assigns nothing, and so isn't written
+        assert_eq!(
+            std::fs::read_to_string(&input_path)?,
+            original.replace("text/plain=\n", "")
+        );
+
+        delete_file(&input_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_user_assignments_as_profile() -> anyhow::Result<()> {
+        let (entries, associations) = create_test_entries_and_associations()?;
+
+        let profile = associations.export_profile(&entries, ProfileIdentifiers::Ids);
+        assert_eq!(
+            profile.default_applications["image/gif"],
+            ["org.gimp.GIMP.desktop", "photopea.desktop"]
+        );
+        // the second user scope contributes too
+        assert_eq!(
+            profile.default_applications["x-scheme-handler/jetbrains"],
+            ["jetbrains-toolbox.desktop"]
+        );
+        assert_eq!(
+            profile.added_associations["image/png"],
+            ["org.gimp.GIMP.desktop"]
+        );
+        // system assignments aren't the user's
+        assert!(!profile.default_applications.contains_key("image/tiff"));
+
+        // installed applications are named as a fallback for their id
+        let profile = associations.export_profile(&entries, ProfileIdentifiers::Names);
+        assert_eq!(
+            profile.default_applications["image/bmp"],
+            ["photopea.desktop", "Photopea"]
+        );
+        assert_eq!(
+            profile.default_applications["image/png"],
+            ["org.gimp.GIMP.desktop"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn exported_names_roundtrip_to_the_same_application() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-shared-name-applications-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        for id in ["a-editor.desktop", "b-editor.desktop"] {
+            std::fs::write(
+                dir.join(id),
+                "[Desktop Entry]\nType=Application\nName=Text Editor\nExec=sh %f\nMimeType=text/plain;\n",
+            )?;
+        }
+        let exported_path = path("test-data/config/mimeapps.list.shared-name-export");
+        let imported_path = path("test-data/config/mimeapps.list.shared-name-import");
+        std::fs::write(
+            &exported_path,
+            "[Default Applications]\ntext/plain=b-editor.desktop\n",
+        )?;
+        std::fs::write(&imported_path, "[Default Applications]\n")?;
+
+        let entries = DesktopEntryStore::load(&[&dir]);
+        let exported =
+            MimeTypeAssociationStore::load_for_environment(&[&exported_path], &test_environment());
+        let imported =
+            MimeTypeAssociationStore::load_for_environment(&[&imported_path], &test_environment());
+        let _ = std::fs::remove_dir_all(&dir);
+        delete_file(&exported_path);
+        delete_file(&imported_path);
+        let (entries, exported, mut imported) = (entries?, exported?, imported?);
+
+        // both applications are named "Text Editor", so only the id identifies the assigned one
+        let profile = exported.export_profile(&entries, ProfileIdentifiers::Names);
+        assert_eq!(
+            profile.default_applications["text/plain"],
+            ["b-editor.desktop", "Text Editor"]
+        );
+
+        imported.import_profile(&profile, &entries)?;
+        assert_eq!(
+            imported.default_application_for(&MimeType::parse("text/plain")?),
+            Some(&DesktopEntryId::parse("b-editor.desktop")?)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn imports_profile_skipping_unavailable_applications() -> anyhow::Result<()> {
+        let (entries, mut associations) = create_test_entries_and_associations()?;
+
+        let profile = Profile::parse(
+            r#"{
+                "default_applications": {
+                    "image/png": ["missing.desktop", "Not Installed", "photopea"],
+                    "text/plain": ["Photopea"],
+                    "application/x-missing": ["missing-app"],
+                    "not-a-mime-type": ["photopea"]
+                },
+                "added_associations": {
+                    "image/jpeg": ["Eye of Gnome", "nothing"]
+                }
+            }"#,
+            ProfileFormat::Json,
+        )?;
+
+        let png = MimeType::parse("image/png")?;
+        let jpeg = MimeType::parse("image/jpeg")?;
+        let photopea = DesktopEntryId::parse("photopea.desktop")?;
+        let eog = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+
+        let result = associations.import_profile(&profile, &entries)?;
+        assert_eq!(
+            result.default_applications,
+            [(png.clone(), photopea.clone())]
+        );
+        // the uninstalled application is kept ahead of the installed one, as a fallback for machines which have it
+        let missing = DesktopEntryId::parse("missing.desktop")?;
+        assert_eq!(
+            associations.scopes[0].default_applications[&png],
+            [missing, photopea.clone()]
+        );
+        assert_eq!(
+            associations
+                .installed_default_application_for(&png, &entries)
+                .map(DesktopEntry::id),
+            Some(&photopea)
+        );
+        assert_eq!(result.added_associations, [(jpeg.clone(), eog.clone())]);
+        assert!(associations.added_associations_for(&jpeg).contains(&eog));
+        assert!(associations.is_dirty());
+
+        let skipped = result
+            .skipped
+            .iter()
+            .map(|entry| (entry.section, entry.mime_type.as_str(), entry.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            skipped,
+            [
+                (
+                    ProfileSection::DefaultApplications,
+                    "application/x-missing",
+                    SkipReason::NotInstalled
+                ),
+                (
+                    ProfileSection::DefaultApplications,
+                    "not-a-mime-type",
+                    SkipReason::InvalidMimeType
+                ),
+                (
+                    ProfileSection::DefaultApplications,
+                    "text/plain",
+                    SkipReason::Unsupported
+                ),
+                (
+                    ProfileSection::AddedAssociations,
+                    "image/jpeg",
+                    SkipReason::NotInstalled
+                ),
+            ]
+        );
+        assert_eq!(result.skipped[3].identifiers, ["nothing"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn plans_and_applies_desired_state() -> anyhow::Result<()> {
+        let (entries, mut associations) = create_test_entries_and_associations()?;
+
+        let desired_state = DesiredState::parse(
+            r#"
+            reset = ["image/*"]
+
+            [default_applications]
+            "image/bmp" = ["photopea"]
+            "image/png" = ["Photopea"]
+            "text/plain" = ["missing-editor"]
+
+            [added_associations]
+            "image/jpeg" = ["org.gnome.eog.desktop"]
+            "#,
+            ProfileFormat::Toml,
+        )?;
+
+        let png = MimeType::parse("image/png")?;
+        let gif = MimeType::parse("image/gif")?;
+        let pdf = MimeType::parse("image/pdf")?;
+        let jpeg = MimeType::parse("image/jpeg")?;
+        let gimp = DesktopEntryId::parse("org.gimp.GIMP.desktop")?;
+        let eog = DesktopEntryId::parse("org.gnome.eog.desktop")?;
+        let photopea = DesktopEntryId::parse("photopea.desktop")?;
+        let evince = DesktopEntryId::parse("org.gnome.Evince.desktop")?;
+
+        // image/bmp is already converged; image/png is only kept from the reset by its assignment, and changes from
+        // Eye of Gnome, which opens it since the user's GIMP isn't installed
+        let plan = associations.plan(&desired_state, &entries);
+        assert_eq!(
+            plan.changes,
+            [
+                PlannedChange::SetDefaultApplication {
+                    mime_type: png.clone(),
+                    from: Some(eog.clone()),
+                    to: photopea.clone(),
+                },
+                PlannedChange::ResetDefaultApplication {
+                    mime_type: gif.clone(),
+                    from: Some(gimp.clone()),
+                },
+                PlannedChange::ResetDefaultApplication {
+                    mime_type: pdf.clone(),
+                    from: Some(evince),
+                },
+                PlannedChange::AddAssociation {
+                    mime_type: jpeg.clone(),
+                    desktop_entry: eog.clone(),
+                },
+            ]
+        );
+        assert_eq!(plan.unresolved.len(), 1);
+        assert_eq!(plan.unresolved[0].mime_type, "text/plain");
+        assert_eq!(plan.unresolved[0].reason, SkipReason::NotInstalled);
+        assert!(!plan.is_converged());
+
+        associations.apply_plan(&plan, &entries)?;
+        assert_eq!(associations.default_application_for(&png), Some(&photopea));
+        assert_eq!(associations.user_default_application_for(&gif), None);
+        assert!(associations.added_associations_for(&jpeg).contains(&eog));
+
+        // applying is idempotent; only the unresolvable entry remains
+        let plan = associations.plan(&desired_state, &entries);
+        assert!(plan.changes.is_empty());
+        assert_eq!(plan.unresolved.len(), 1);
+
+        // an exclusive desired state resets every other user assignment, in every user scope
+        let desired_state = DesiredState {
+            exclusive: true,
+            default_applications: [("image/bmp".to_string(), vec!["photopea".to_string()])].into(),
+            ..Default::default()
+        };
+        let plan = associations.plan(&desired_state, &entries);
+        assert!(plan
+            .changes
+            .contains(&PlannedChange::ResetDefaultApplication {
+                mime_type: MimeType::parse("x-scheme-handler/jetbrains")?,
+                from: Some(DesktopEntryId::parse("jetbrains-toolbox.desktop")?),
+            }));
+        associations.apply_plan(&plan, &entries)?;
+        assert!(associations.plan(&desired_state, &entries).is_converged());
 
         Ok(())
     }
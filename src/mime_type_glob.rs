@@ -0,0 +1,210 @@
+use serde::Serialize;
+
+/// Represents a `<glob>` element of a shared-mime-info mime type, e.g., `<glob pattern="*.c" case-sensitive="true"/>`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MimeTypeGlob {
+    pattern: String,
+    weight: u32,
+    case_sensitive: bool,
+}
+
+impl MimeTypeGlob {
+    pub const DEFAULT_WEIGHT: u32 = 50;
+
+    /// Special pattern which, when loaded from a later source, discards globs specified by earlier sources
+    pub const NO_GLOBS: &'static str = "__NOGLOBS__";
+
+    pub fn new(pattern: &str, weight: u32, case_sensitive: bool) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            weight,
+            case_sensitive,
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    /// True if the pattern contains no wildcards, and must match the entire file name, e.g., "Makefile"
+    pub fn is_literal(&self) -> bool {
+        !self.pattern.contains(['*', '?', '['])
+    }
+
+    /// If this pattern is a simple `*` followed by a literal suffix, e.g. `*.tar.gz`, returns that suffix
+    pub fn literal_suffix(&self) -> Option<&str> {
+        let suffix = self.pattern.strip_prefix('*')?;
+        if suffix.is_empty() || suffix.contains(['*', '?', '[']) {
+            None
+        } else {
+            Some(suffix)
+        }
+    }
+
+    /// Returns true if the file name (not a full path) matches this glob
+    pub fn matches(&self, file_name: &str) -> bool {
+        if self.case_sensitive {
+            fnmatch(&self.pattern, file_name)
+        } else {
+            fnmatch(&self.pattern.to_lowercase(), &file_name.to_lowercase())
+        }
+    }
+}
+
+/// Minimal fnmatch(3) implementation supporting `*`, `?` and `[...]` bracket expressions
+/// (with `!` or `^` negation and `a-z` ranges), without any special handling of `/` or leading dots.
+fn fnmatch(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let mut p = 0;
+    let mut t = 0;
+    // position in pattern after the last `*`, and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                '*' => {
+                    backtrack = Some((p + 1, t));
+                    p += 1;
+                    continue;
+                }
+                '?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                '[' => {
+                    if let Some((is_match, next_p)) = match_bracket(&pattern, p, text[t]) {
+                        if is_match {
+                            p = next_p;
+                            t += 1;
+                            continue;
+                        }
+                    } else if text[t] == '[' {
+                        // unterminated bracket is treated as a literal `[`
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == text[t] {
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // mismatch; let the most recent `*` consume one more character, if there is one
+        if let Some((star_p, star_t)) = backtrack {
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Evaluate the bracket expression starting at `pattern[start]` (which must be `[`) against `c`.
+/// Returns whether it matched and the pattern index following the closing `]`, or None if
+/// the expression is unterminated.
+fn match_bracket(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut is_match = false;
+    let mut first = true;
+    while i < pattern.len() {
+        let current = pattern[i];
+        if current == ']' && !first {
+            return Some((is_match != negated, i + 1));
+        }
+        first = false;
+
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|e| *e != ']') {
+            let end = pattern[i + 2];
+            if current <= c && c <= end {
+                is_match = true;
+            }
+            i += 3;
+        } else {
+            if current == c {
+                is_match = true;
+            }
+            i += 1;
+        }
+    }
+
+    None
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnmatch_works() {
+        assert!(fnmatch("*.png", "foo.png"));
+        assert!(!fnmatch("*.png", "foo.png.bak"));
+        assert!(fnmatch("*.tar.gz", "archive.tar.gz"));
+        assert!(fnmatch("README*", "README.md"));
+        assert!(fnmatch("*.[ch]", "main.c"));
+        assert!(fnmatch("*.[ch]", "main.h"));
+        assert!(!fnmatch("*.[!ch]", "main.c"));
+        assert!(fnmatch("*.[0-9]", "ls.1"));
+        assert!(fnmatch("?akefile", "Makefile"));
+        assert!(!fnmatch("?akefile", "akefile"));
+        assert!(fnmatch("*", ""));
+        assert!(fnmatch("a*b*c", "aXXbYYc"));
+        assert!(!fnmatch("a*b*c", "aXXbYY"));
+    }
+
+    #[test]
+    fn glob_classification_works() {
+        assert!(MimeTypeGlob::new("Makefile", 50, false).is_literal());
+        assert!(!MimeTypeGlob::new("*.c", 50, false).is_literal());
+
+        assert_eq!(
+            MimeTypeGlob::new("*.tar.gz", 50, false).literal_suffix(),
+            Some(".tar.gz")
+        );
+        assert_eq!(
+            MimeTypeGlob::new("*.[ch]", 50, false).literal_suffix(),
+            None
+        );
+        assert_eq!(
+            MimeTypeGlob::new("README*", 50, false).literal_suffix(),
+            None
+        );
+    }
+
+    #[test]
+    fn glob_case_sensitivity_works() {
+        let insensitive = MimeTypeGlob::new("*.png", 50, false);
+        assert!(insensitive.matches("FOO.PNG"));
+
+        let sensitive = MimeTypeGlob::new("*.C", 50, true);
+        assert!(sensitive.matches("foo.C"));
+        assert!(!sensitive.matches("foo.c"));
+    }
+}
@@ -1,8 +1,18 @@
-use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
 
 use serde::Serialize;
+use xml::common::Position;
 
+use crate::desktop_entry_exec::local_path;
+use crate::error::Error;
 use crate::mime_type::MimeType;
+use crate::mime_type_glob::MimeTypeGlob;
+use crate::mime_type_magic::{MagicMatch, MimeTypeMagic};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct MimeTypeInfo {
@@ -13,8 +23,12 @@ pub struct MimeTypeInfo {
     // comments for other languages, "en_GB", "de", etc.
     comments: HashMap<String, String>,
     generic_icon: Option<String>,
-    glob_patterns: Vec<String>,
+    globs: Vec<MimeTypeGlob>,
     aliases: Vec<MimeType>,
+    sub_class_of: Vec<MimeType>,
+    // magic rules are bulky and only meaningful for content sniffing, so they're left out of serialization
+    #[serde(skip)]
+    magic: Vec<MimeTypeMagic>,
 }
 impl MimeTypeInfo {
     fn new(mime_type: &MimeType) -> Self {
@@ -23,8 +37,10 @@ impl MimeTypeInfo {
             comment: None,
             comments: HashMap::new(),
             generic_icon: None,
-            glob_patterns: Vec::new(),
+            globs: Vec::new(),
             aliases: Vec::new(),
+            sub_class_of: Vec::new(),
+            magic: Vec::new(),
         }
     }
 
@@ -50,22 +66,35 @@ impl MimeTypeInfo {
     }
 
     pub fn glob_patterns(&self) -> Vec<&str> {
-        self.glob_patterns.iter().map(|g| g.as_str()).collect()
+        self.globs.iter().map(|g| g.pattern()).collect()
     }
 
+    /// The `<glob>` elements used to identify this mime type by file name
+    pub fn globs(&self) -> &[MimeTypeGlob] {
+        &self.globs
+    }
+
+    /// File extensions for globs of the form `*.ext`, e.g., `*.tar.gz` yields "tar.gz"
     pub fn extensions(&self) -> Vec<&str> {
-        self.glob_patterns
+        self.globs
             .iter()
-            .filter_map(|glob| {
-                let components = glob.split('.').collect::<Vec<_>>();
-                components.get(1).copied()
-            })
+            .filter_map(|glob| glob.literal_suffix()?.strip_prefix('.'))
             .collect()
     }
 
     pub fn aliases(&self) -> Vec<&MimeType> {
         self.aliases.iter().collect()
     }
+
+    /// The mime types this mime type is declared a `<sub-class-of>`, e.g., text/x-csrc is a subclass of text/plain
+    pub fn parents(&self) -> Vec<&MimeType> {
+        self.sub_class_of.iter().collect()
+    }
+
+    /// The `<magic>` blocks used to identify this mime type by file content
+    pub fn magic(&self) -> &[MimeTypeMagic] {
+        &self.magic
+    }
 }
 
 pub struct MimeTypeInfoStore {
@@ -75,28 +104,51 @@ pub struct MimeTypeInfoStore {
     /// "application/vnd.amazon.mobi8-ebook" has an alias "application/x-mobi8-ebook".
     /// That means, looking up aliases["application/x-mobi8-ebook"] gives us "application/vnd.amazon.mobi8-ebook"
     aliases: HashMap<MimeType, MimeType>,
+
+    /// The number of leading bytes of a file needed to evaluate every magic rule in the store
+    magic_extent: usize,
+
+    /// text/plain, the implicit parent of text/* types
+    text_plain: MimeType,
+
+    /// application/octet-stream, the type of content which can't be identified
+    octet_stream: MimeType,
+
+    /// The xml files loaded, in load order
+    sources: Vec<PathBuf>,
 }
 
 impl MimeTypeInfoStore {
-    pub fn load<P: AsRef<Path>>(mime_info_xml_paths: &[P]) -> anyhow::Result<Self> {
+    pub fn load<P: AsRef<Path>>(mime_info_xml_paths: &[P]) -> Result<Self, Error> {
         let mut store = Self {
             mime_types: HashMap::new(),
             aliases: HashMap::new(),
+            magic_extent: 0,
+            text_plain: MimeType::parse("text/plain")?,
+            octet_stream: MimeType::parse("application/octet-stream")?,
+            sources: vec![],
         };
 
         for path in mime_info_xml_paths.iter() {
             Self::load_mime_info(path, &mut store)?;
+            store.sources.push(path.as_ref().to_path_buf());
         }
 
         store.resolve_aliases();
+        store.resolve_magic_extent();
         Ok(store)
     }
 
-    fn load_mime_info<P: AsRef<Path>>(mime_info_path: P, store: &mut Self) -> anyhow::Result<()> {
+    /// The mime info xml files this store was loaded from, in load order
+    pub fn sources(&self) -> Vec<&Path> {
+        self.sources.iter().map(PathBuf::as_path).collect()
+    }
+
+    fn load_mime_info<P: AsRef<Path>>(mime_info_path: P, store: &mut Self) -> Result<(), Error> {
         let path = mime_info_path.as_ref();
         log::info!("MimeTypeInfoStore::load_mime_info {:?}", path);
 
-        let file = File::open(path)?;
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
         let reader = BufReader::new(file);
         let parser = xml::EventReader::new(reader);
 
@@ -104,6 +156,12 @@ impl MimeTypeInfoStore {
         let mut current_mime_type_info: Option<MimeTypeInfo> = None;
         let mut is_handling_comment = false;
         let mut current_comment_language: Option<String> = None;
+        // set when the current <mime-type> contains __NOGLOBS__, discarding globs from earlier sources
+        let mut discards_existing_globs = false;
+        let mut current_magic: Option<MimeTypeMagic> = None;
+        // nested <match> elements; entries are None for matches which failed to parse,
+        // so that the stack stays balanced with the xml
+        let mut match_stack: Vec<Option<MagicMatch>> = Vec::new();
 
         for e in parser {
             match e {
@@ -116,6 +174,7 @@ impl MimeTypeInfoStore {
 
                         if let Some(mime_type) = mime_type {
                             current_mime_type_info.replace(MimeTypeInfo::new(&mime_type));
+                            discards_existing_globs = false;
                         }
                     }
 
@@ -144,8 +203,24 @@ impl MimeTypeInfoStore {
                             Self::get_attribute_named(&attributes, "pattern")
                         {
                             if let Some(current_mime_type_info) = current_mime_type_info.as_mut() {
-                                let glob_pattern = glob_pattern.value.to_string();
-                                current_mime_type_info.glob_patterns.push(glob_pattern);
+                                let glob_pattern = glob_pattern.value.as_str();
+                                if glob_pattern == MimeTypeGlob::NO_GLOBS {
+                                    discards_existing_globs = true;
+                                    current_mime_type_info.globs.clear();
+                                } else {
+                                    let weight = Self::get_attribute_named(&attributes, "weight")
+                                        .and_then(|attr| attr.value.trim().parse().ok())
+                                        .unwrap_or(MimeTypeGlob::DEFAULT_WEIGHT);
+                                    let case_sensitive =
+                                        Self::get_attribute_named(&attributes, "case-sensitive")
+                                            .map(|attr| attr.value.trim() == "true")
+                                            .unwrap_or(false);
+                                    current_mime_type_info.globs.push(MimeTypeGlob::new(
+                                        glob_pattern,
+                                        weight,
+                                        case_sensitive,
+                                    ));
+                                }
                             }
                         }
                     }
@@ -162,34 +237,118 @@ impl MimeTypeInfoStore {
                         }
                     }
 
+                    "sub-class-of" => {
+                        if let Some(parent_attr) = Self::get_attribute_named(&attributes, "type") {
+                            if let Some(current_mime_type_info) = current_mime_type_info.as_mut() {
+                                if let Ok(parent_mime_type) =
+                                    MimeType::parse(parent_attr.value.trim())
+                                {
+                                    if !current_mime_type_info
+                                        .sub_class_of
+                                        .contains(&parent_mime_type)
+                                    {
+                                        current_mime_type_info.sub_class_of.push(parent_mime_type);
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    "magic" => {
+                        let priority = Self::get_attribute_named(&attributes, "priority")
+                            .and_then(|attr| attr.value.trim().parse().ok())
+                            .unwrap_or(MimeTypeMagic::DEFAULT_PRIORITY);
+                        current_magic = Some(MimeTypeMagic::new(priority));
+                    }
+
+                    "match" if current_magic.is_some() => {
+                        let attribute = |name| {
+                            Self::get_attribute_named(&attributes, name).map(|a| a.value.as_str())
+                        };
+                        let magic_match = MagicMatch::parse(
+                            attribute("type").unwrap_or_default(),
+                            attribute("value").unwrap_or_default(),
+                            attribute("offset").unwrap_or_default(),
+                            attribute("mask"),
+                        );
+                        match magic_match {
+                            Ok(magic_match) => match_stack.push(Some(magic_match)),
+                            Err(e) => {
+                                log::warn!(
+                                    "MimeTypeInfoStore::load_mime_info - skipping malformed magic match in {:?}: {}",
+                                    path,
+                                    e
+                                );
+                                match_stack.push(None);
+                            }
+                        }
+                    }
+
                     _ => {}
                 },
-                Ok(xml::reader::XmlEvent::Characters(characters)) => {
-                    if is_handling_comment {
-                        let comment = characters.trim().to_string();
-                        if let Some(current_mime_type_info) = current_mime_type_info.as_mut() {
-                            if let Some(language) = current_comment_language.take() {
-                                current_mime_type_info.comments.insert(language, comment);
-                            } else {
-                                current_mime_type_info.comment = Some(comment);
-                            }
+                Ok(xml::reader::XmlEvent::Characters(characters)) if is_handling_comment => {
+                    let comment = characters.trim().to_string();
+                    if let Some(current_mime_type_info) = current_mime_type_info.as_mut() {
+                        if let Some(language) = current_comment_language.take() {
+                            current_mime_type_info.comments.insert(language, comment);
+                        } else {
+                            current_mime_type_info.comment = Some(comment);
                         }
                     }
                 }
                 Ok(xml::reader::XmlEvent::EndElement { name }) => match name.local_name.as_str() {
                     "mime-type" => {
-                        if let Some(current_mime_type_info) = current_mime_type_info.take() {
+                        if let Some(mut current_mime_type_info) = current_mime_type_info.take() {
                             let key = current_mime_type_info.mime_type.clone();
+
+                            // globs accumulate across sources unless a later source says __NOGLOBS__
+                            if !discards_existing_globs {
+                                if let Some(existing) = store.mime_types.get(&key) {
+                                    let mut globs = existing.globs.clone();
+                                    for glob in current_mime_type_info.globs.drain(..) {
+                                        if !globs.contains(&glob) {
+                                            globs.push(glob);
+                                        }
+                                    }
+                                    current_mime_type_info.globs = globs;
+                                }
+                            }
+
                             store.mime_types.insert(key, current_mime_type_info);
                         }
                     }
                     "comment" => {
                         is_handling_comment = false;
                     }
+                    "magic" => {
+                        if let (Some(magic), Some(current_mime_type_info)) =
+                            (current_magic.take(), current_mime_type_info.as_mut())
+                        {
+                            current_mime_type_info.magic.push(magic);
+                        }
+                    }
+                    "match" if current_magic.is_some() => {
+                        // a match which failed to parse takes its children with it
+                        if let Some(Some(magic_match)) = match_stack.pop() {
+                            match match_stack.last_mut() {
+                                Some(Some(parent)) => parent.add_child(magic_match),
+                                Some(None) => {}
+                                None => {
+                                    if let Some(magic) = current_magic.as_mut() {
+                                        magic.add_match(magic_match);
+                                    }
+                                }
+                            }
+                        }
+                    }
                     _ => {}
                 },
                 Err(e) => {
-                    return Err(e.into());
+                    return Err(Error::parse(
+                        path,
+                        e.position().row as usize + 1,
+                        e.msg().to_string(),
+                    ));
                 }
                 _ => {}
             }
@@ -208,6 +367,227 @@ impl MimeTypeInfoStore {
         }
     }
 
+    /// Resolve an alias to the mime type it stands for, e.g., "text/x-c" resolves to "text/x-csrc".
+    /// Mime types which aren't aliases are returned as-is.
+    pub fn unalias<'a>(&'a self, mime_type: &'a MimeType) -> &'a MimeType {
+        self.aliases.get(mime_type).unwrap_or(mime_type)
+    }
+
+    /// Returns the mime types the given mime type directly subclasses, as declared by `<sub-class-of>`.
+    /// Per the shared-mime-info spec, text/* types which declare no parents implicitly subclass text/plain.
+    pub fn parents(&self, mime_type: &MimeType) -> Vec<&MimeType> {
+        let mut parents = self
+            .get_info_for_mime_type(mime_type)
+            .map(|info| info.parents())
+            .unwrap_or_default();
+
+        if parents.is_empty()
+            && mime_type.major_type() == "text"
+            && self.unalias(mime_type) != &self.text_plain
+        {
+            parents.push(&self.text_plain);
+        }
+
+        parents
+    }
+
+    /// Returns all the mime types the given mime type inherits from, nearest first, without duplicates.
+    /// E.g., for application/x-shellscript this yields application/x-executable and text/plain.
+    /// The implicit application/octet-stream supertype is omitted, since it says nothing useful
+    /// about how a file should be opened; `MimeTypeInfoStore::is_subclass_of` still honors it.
+    pub fn ancestors(&self, mime_type: &MimeType) -> Vec<&MimeType> {
+        let mime_type = self.unalias(mime_type);
+        let mut ancestors: Vec<&MimeType> = Vec::new();
+        let mut queue = std::collections::VecDeque::from([mime_type]);
+
+        // breadth-first, so nearer ancestors come before more distant ones
+        while let Some(current) = queue.pop_front() {
+            for parent in self.parents(current) {
+                let parent = self.unalias(parent);
+                if parent != mime_type && !ancestors.contains(&parent) {
+                    ancestors.push(parent);
+                    queue.push_back(parent);
+                }
+            }
+        }
+
+        ancestors
+    }
+
+    /// Returns true if `mime_type` is `ancestor`, an alias of it, or inherits from it. Wildcards such as
+    /// `image/*` are treated as supertypes of every type with that major type, and every type other
+    /// than inode/* is a subclass of application/octet-stream.
+    pub fn is_subclass_of(&self, mime_type: &MimeType, ancestor: &MimeType) -> bool {
+        let mime_type = self.unalias(mime_type);
+        let ancestor = self.unalias(ancestor);
+
+        if mime_type == ancestor || ancestor.wildcard_match(mime_type) {
+            return true;
+        }
+
+        if ancestor.id() == "application/octet-stream" && mime_type.major_type() != "inode" {
+            return true;
+        }
+
+        self.ancestors(mime_type)
+            .into_iter()
+            .any(|a| a == ancestor || ancestor.wildcard_match(a))
+    }
+
+    /// Identify the mime types of file content using the shared-mime-info magic rules.
+    /// Returns candidates in descending order of magic priority; the first entry is the best guess.
+    /// Returns an empty vector if no magic rule matched.
+    pub fn sniff(&self, data: &[u8]) -> Vec<MimeType> {
+        let mut candidates: Vec<(u32, &MimeType)> = Vec::new();
+        for (mime_type, info) in self.mime_types.iter() {
+            let priority = info
+                .magic
+                .iter()
+                .filter(|magic| magic.test(data))
+                .map(|magic| magic.priority())
+                .max();
+
+            if let Some(priority) = priority {
+                candidates.push((priority, mime_type));
+            }
+        }
+
+        // highest priority first, then by name so results are stable
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        candidates.into_iter().map(|(_, m)| m.clone()).collect()
+    }
+
+    /// Identify the mime types of the file at `path` by reading its leading bytes and
+    /// sniffing them against the magic rules. See `MimeTypeInfoStore::sniff`.
+    pub fn detect_path<P: AsRef<Path>>(&self, path: P) -> Result<Vec<MimeType>, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
+        let mut data = Vec::with_capacity(self.magic_extent);
+        file.take(self.magic_extent as u64)
+            .read_to_end(&mut data)
+            .map_err(|e| Error::io(path, e))?;
+        Ok(self.sniff(&data))
+    }
+
+    /// Identify the mime type to open `target` with, where `target` is a path, a `file://` URI, or any other URI.
+    /// Other URIs map to `x-scheme-handler/<scheme>`, e.g., `x-scheme-handler/https`, and directories map to
+    /// `inode/directory`. For files, the glob rules are preferred, using the content to choose between several
+    /// equally good glob matches; without a glob match, the content decides, falling back to text/plain for
+    /// textual content and application/octet-stream otherwise.
+    pub fn mime_type_for_path_or_uri(&self, target: &str) -> MimeType {
+        let Some(path) = local_path(target) else {
+            let scheme = target.split(':').next().unwrap_or_default();
+            return MimeType::for_scheme(scheme).unwrap_or_else(|_| self.octet_stream.clone());
+        };
+
+        let path = Path::new(&path);
+        if path.is_dir() {
+            return MimeType::parse("inode/directory")
+                .unwrap_or_else(|_| self.octet_stream.clone());
+        }
+
+        let glob_matches = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .map(|file_name| self.mime_types_for_file_name(file_name))
+            .unwrap_or_default();
+        if glob_matches.len() == 1 {
+            return glob_matches[0].clone();
+        }
+
+        let mut data = Vec::with_capacity(self.magic_extent);
+        let data = File::open(path)
+            .and_then(|file| file.take(self.magic_extent as u64).read_to_end(&mut data))
+            .map(|_| data)
+            .ok();
+
+        let sniffed = data
+            .as_deref()
+            .map(|data| self.sniff(data))
+            .unwrap_or_default();
+        if let Some(glob_match) = glob_matches.first() {
+            return sniffed
+                .into_iter()
+                .find(|mime_type| glob_matches.contains(mime_type))
+                .unwrap_or_else(|| glob_match.clone());
+        }
+
+        if let Some(mime_type) = sniffed.into_iter().next() {
+            return mime_type;
+        }
+
+        let looks_textual = data.as_deref().is_some_and(|data| {
+            std::str::from_utf8(data).is_ok_and(|text| {
+                !text
+                    .chars()
+                    .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+            })
+        });
+        if looks_textual {
+            self.text_plain.clone()
+        } else {
+            self.octet_stream.clone()
+        }
+    }
+
+    /// Identify the mime types of a file by its name using the shared-mime-info glob rules.
+    /// Literal names (e.g., "Makefile") are tried first, then simple `*.ext` suffixes, then
+    /// any other globs; only matches from the first of those stages to succeed are returned.
+    /// Case-sensitive matches take precedence over case-insensitive ones, and candidates are
+    /// ranked by glob weight, then by pattern length, so `*.tar.gz` outranks `*.gz`.
+    /// Returns an empty vector if no glob matched. If `file_name` is a path, only its final
+    /// component is considered.
+    pub fn mime_types_for_file_name(&self, file_name: &str) -> Vec<MimeType> {
+        let file_name = file_name.rsplit('/').next().unwrap_or(file_name);
+        if file_name.is_empty() {
+            return Vec::new();
+        }
+
+        // (stage, case_sensitive, weight, pattern length, mime type); lower stages are more specific
+        let mut candidates: Vec<(u8, bool, u32, usize, &MimeType)> = Vec::new();
+        for (mime_type, info) in self.mime_types.iter() {
+            for glob in info.globs.iter().filter(|glob| glob.matches(file_name)) {
+                let stage = if glob.is_literal() {
+                    0
+                } else if glob.literal_suffix().is_some() {
+                    1
+                } else {
+                    2
+                };
+                candidates.push((
+                    stage,
+                    glob.is_case_sensitive(),
+                    glob.weight(),
+                    glob.pattern().len(),
+                    mime_type,
+                ));
+            }
+        }
+
+        let Some(best_stage) = candidates.iter().map(|c| c.0).min() else {
+            return Vec::new();
+        };
+        candidates.retain(|c| c.0 == best_stage);
+        if candidates.iter().any(|c| c.1) {
+            candidates.retain(|c| c.1);
+        }
+
+        // highest weight first, then longest pattern, then by name so results are stable
+        candidates.sort_by(|a, b| {
+            b.2.cmp(&a.2)
+                .then_with(|| b.3.cmp(&a.3))
+                .then_with(|| a.4.cmp(b.4))
+        });
+
+        let mut mime_types: Vec<MimeType> = Vec::new();
+        for (_, _, _, _, mime_type) in candidates {
+            if !mime_types.contains(mime_type) {
+                mime_types.push(mime_type.clone());
+            }
+        }
+        mime_types
+    }
+
     /// Find the first attribute with matching name, if any
     fn get_attribute_named<'a>(
         attributes: &'a [xml::attribute::OwnedAttribute],
@@ -227,6 +607,16 @@ impl MimeTypeInfoStore {
             }
         }
     }
+
+    fn resolve_magic_extent(&mut self) {
+        self.magic_extent = self
+            .mime_types
+            .values()
+            .flat_map(|info| info.magic.iter())
+            .map(|magic| magic.extent())
+            .max()
+            .unwrap_or(0);
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -317,6 +707,209 @@ mod tests {
         loads_and_contains_expected_data(full_freedesktop_org_xml_path())
     }
 
+    #[test]
+    fn loads_magic_rules() -> anyhow::Result<()> {
+        let store = MimeTypeInfoStore::load(&[tiny_freedesktop_org_xml_path()])?;
+
+        let atari_7800_mime_type = MimeType::parse("application/x-atari-7800-rom")?;
+        let atari_7800_info = store.get_info_for_mime_type(&atari_7800_mime_type).unwrap();
+
+        let magic = atari_7800_info.magic();
+        assert_eq!(magic.len(), 1);
+        assert_eq!(magic[0].priority(), MimeTypeMagic::DEFAULT_PRIORITY);
+        assert_eq!(magic[0].matches().len(), 1);
+        assert_eq!(magic[0].matches()[0].offset(), (1, 1));
+        assert_eq!(magic[0].matches()[0].value(), b"ATARI7800");
+
+        let mut rom = b"\0ATARI7800".to_vec();
+        rom.resize(128, 0);
+        assert_eq!(store.sniff(&rom), vec![atari_7800_mime_type]);
+        assert!(store.sniff(b"ATARI7800").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sniffs_content_with_full_freedesktop_org_xml() -> anyhow::Result<()> {
+        let store = MimeTypeInfoStore::load(&[full_freedesktop_org_xml_path()])?;
+
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(
+            store.sniff(png).first(),
+            Some(&MimeType::parse("image/png")?)
+        );
+
+        let pdf = b"%PDF-1.7\n";
+        assert_eq!(
+            store.sniff(pdf).first(),
+            Some(&MimeType::parse("application/pdf")?)
+        );
+
+        assert!(store.sniff(b"").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_path_by_content() -> anyhow::Result<()> {
+        let store = MimeTypeInfoStore::load(&[full_freedesktop_org_xml_path()])?;
+
+        // the photopea entry has a shebang line before [Desktop Entry], which the offset range covers
+        let candidates =
+            store.detect_path(path("test-data/local/share/applications/photopea.desktop"))?;
+        assert!(candidates.contains(&MimeType::parse("application/x-desktop")?));
+
+        assert!(store.detect_path(path("test-data/does-not-exist")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn identifies_paths_and_uris() -> anyhow::Result<()> {
+        let store = MimeTypeInfoStore::load(&[full_freedesktop_org_xml_path()])?;
+        let identify = |target: &str| store.mime_type_for_path_or_uri(target).to_string();
+
+        assert_eq!(
+            identify("https://example.com/a.png"),
+            "x-scheme-handler/https"
+        );
+        assert_eq!(
+            identify("MAILTO:someone@example.com"),
+            "x-scheme-handler/mailto"
+        );
+        assert_eq!(identify("photo.png"), "image/png");
+        assert_eq!(identify("file:///tmp/photo%20copy.png"), "image/png");
+        assert_eq!(identify("test-data"), "inode/directory");
+        assert_eq!(
+            identify("test-data/open/bin/stub-handler"),
+            "application/x-shellscript"
+        );
+        assert_eq!(identify("test-data/open/mimeapps.list"), "text/plain");
+        assert_eq!(
+            identify("test-data/does-not-exist"),
+            "application/octet-stream"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_file_names_with_full_freedesktop_org_xml() -> anyhow::Result<()> {
+        let store = MimeTypeInfoStore::load(&[full_freedesktop_org_xml_path()])?;
+
+        // longest suffix outranks shorter suffixes of equal weight
+        let candidates = store.mime_types_for_file_name("archive.tar.gz");
+        assert_eq!(
+            candidates.first(),
+            Some(&MimeType::parse("application/x-compressed-tar")?)
+        );
+        assert!(candidates.contains(&MimeType::parse("application/gzip")?));
+
+        // case-sensitive globs win over case-insensitive ones
+        assert_eq!(
+            store.mime_types_for_file_name("main.c"),
+            vec![MimeType::parse("text/x-csrc")?]
+        );
+        assert_eq!(
+            store.mime_types_for_file_name("main.C"),
+            vec![MimeType::parse("text/x-c++src")?]
+        );
+
+        // case-insensitive globs match regardless of case, and paths are reduced to file names
+        assert_eq!(
+            store.mime_types_for_file_name("/tmp/IMAGE.PNG").first(),
+            Some(&MimeType::parse("image/png")?)
+        );
+
+        // literal names are tried before suffixes
+        assert_eq!(
+            store.mime_types_for_file_name("Makefile").first(),
+            Some(&MimeType::parse("text/x-makefile")?)
+        );
+
+        // weights order candidates which match the same stage
+        assert_eq!(
+            store.mime_types_for_file_name("index.html").first(),
+            Some(&MimeType::parse("text/html")?)
+        );
+
+        assert!(store
+            .mime_types_for_file_name("no-such-extension.zzzzz")
+            .is_empty());
+        assert!(store.mime_types_for_file_name("").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn later_sources_merge_or_discard_globs() -> anyhow::Result<()> {
+        let sources = vec![
+            tiny_freedesktop_org_xml_path(),
+            path("test-data/usr/share/mime/packages/noglobs.xml"),
+        ];
+        let store = MimeTypeInfoStore::load(&sources)?;
+
+        // __NOGLOBS__ discards the *.a78 glob from the earlier source
+        let atari_7800_mime_type = MimeType::parse("application/x-atari-7800-rom")?;
+        let atari_7800_info = store.get_info_for_mime_type(&atari_7800_mime_type).unwrap();
+        assert_eq!(atari_7800_info.glob_patterns(), ["*.atari7800"]);
+        assert!(store.mime_types_for_file_name("game.a78").is_empty());
+        assert_eq!(
+            store.mime_types_for_file_name("game.atari7800"),
+            vec![atari_7800_mime_type]
+        );
+
+        // without __NOGLOBS__, globs accumulate
+        let mobi_mime_type = MimeType::parse("application/vnd.amazon.mobi8-ebook")?;
+        let mobi_info = store.get_info_for_mime_type(&mobi_mime_type).unwrap();
+        assert_eq!(mobi_info.glob_patterns(), ["*.azw3", "*.kfx", "*.mobi8"]);
+        assert_eq!(mobi_info.globs()[2].weight(), 60);
+        assert_eq!(
+            store.mime_types_for_file_name("book.kfx"),
+            vec![mobi_mime_type]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_mime_type_inheritance() -> anyhow::Result<()> {
+        let store = MimeTypeInfoStore::load(&[full_freedesktop_org_xml_path()])?;
+
+        let text_plain = MimeType::parse("text/plain")?;
+        let text_x_csrc = MimeType::parse("text/x-csrc")?;
+        let text_x_c = MimeType::parse("text/x-c")?;
+        let shellscript = MimeType::parse("application/x-shellscript")?;
+        let executable = MimeType::parse("application/x-executable")?;
+        let octet_stream = MimeType::parse("application/octet-stream")?;
+        let inode_directory = MimeType::parse("inode/directory")?;
+
+        let csrc_info = store.get_info_for_mime_type(&text_x_csrc).unwrap();
+        assert_eq!(csrc_info.parents(), vec![&text_plain]);
+
+        assert_eq!(store.parents(&shellscript), vec![&executable, &text_plain]);
+        assert!(store.ancestors(&shellscript).contains(&&text_plain));
+        assert!(store.ancestors(&text_plain).is_empty());
+
+        // aliases resolve before walking the tree
+        assert_eq!(store.unalias(&text_x_c), &text_x_csrc);
+        assert_eq!(store.ancestors(&text_x_c), vec![&text_plain]);
+
+        assert!(store.is_subclass_of(&text_x_csrc, &text_plain));
+        assert!(store.is_subclass_of(&text_x_c, &text_x_csrc));
+        assert!(store.is_subclass_of(&shellscript, &text_plain));
+        assert!(store.is_subclass_of(&shellscript, &MimeType::parse("text/*")?));
+        assert!(!store.is_subclass_of(&text_plain, &text_x_csrc));
+
+        // implicit supertypes
+        assert!(store.is_subclass_of(&MimeType::parse("image/png")?, &octet_stream));
+        assert!(!store.is_subclass_of(&inode_directory, &octet_stream));
+        let unknown_text = MimeType::parse("text/x-not-a-real-type")?;
+        assert_eq!(store.parents(&unknown_text), vec![&text_plain]);
+
+        Ok(())
+    }
+
     #[test]
     fn merges_multiple_sources() -> anyhow::Result<()> {
         let sources = vec![tiny_freedesktop_org_xml_path(), code_workspace_xml_path()];
@@ -0,0 +1,436 @@
+use serde::Serialize;
+
+use crate::Error;
+
+/// The data type a `<match>` element of a shared-mime-info `<magic>` block compares against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum MagicMatchType {
+    String,
+    Byte,
+    Host16,
+    Host32,
+    Big16,
+    Big32,
+    Little16,
+    Little32,
+}
+
+impl MagicMatchType {
+    fn parse(text: &str) -> Result<Self, Error> {
+        Ok(match text.trim() {
+            "string" => Self::String,
+            "byte" => Self::Byte,
+            "host16" => Self::Host16,
+            "host32" => Self::Host32,
+            "big16" => Self::Big16,
+            "big32" => Self::Big32,
+            "little16" => Self::Little16,
+            "little32" => Self::Little32,
+            _ => {
+                return Err(Error::InvalidMagic(format!(
+                    "Unrecognized magic match type \"{}\"",
+                    text
+                )))
+            }
+        })
+    }
+
+    /// Width in bytes of numeric match types, or None for strings
+    fn word_size(&self) -> Option<usize> {
+        match self {
+            Self::String => None,
+            Self::Byte => Some(1),
+            Self::Host16 | Self::Big16 | Self::Little16 => Some(2),
+            Self::Host32 | Self::Big32 | Self::Little32 => Some(4),
+        }
+    }
+
+    fn is_big_endian(&self) -> bool {
+        match self {
+            Self::Big16 | Self::Big32 => true,
+            Self::Host16 | Self::Host32 => cfg!(target_endian = "big"),
+            _ => false,
+        }
+    }
+
+    /// Convert a numeric value to the byte sequence it will appear as in a file
+    fn number_to_bytes(&self, value: u64) -> Vec<u8> {
+        let size = self.word_size().unwrap_or(0);
+        let bytes = (0..size).map(|i| ((value >> (8 * i)) & 0xff) as u8);
+        if self.is_big_endian() {
+            bytes.rev().collect()
+        } else {
+            bytes.collect()
+        }
+    }
+}
+
+/// A single `<match>` rule. A match succeeds if its value is found at its offset (or
+/// within its offset range), and, if it has children, at least one child matches too.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MagicMatch {
+    match_type: MagicMatchType,
+    offset_start: usize,
+    offset_end: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    children: Vec<MagicMatch>,
+}
+
+impl MagicMatch {
+    /// Create a MagicMatch from the raw attribute values of a `<match>` element
+    pub fn parse(
+        match_type: &str,
+        value: &str,
+        offset: &str,
+        mask: Option<&str>,
+    ) -> Result<Self, Error> {
+        let match_type = MagicMatchType::parse(match_type)?;
+        let (offset_start, offset_end) = Self::parse_offset(offset)?;
+
+        let value = if match_type == MagicMatchType::String {
+            Self::parse_string_value(value)
+        } else {
+            match_type.number_to_bytes(Self::parse_number(value)?)
+        };
+
+        let mask = if let Some(mask) = mask {
+            let mask = if match_type == MagicMatchType::String {
+                Self::parse_hex_bytes(mask)?
+            } else {
+                match_type.number_to_bytes(Self::parse_number(mask)?)
+            };
+            if mask.len() != value.len() {
+                return Err(Error::InvalidMagic(format!(
+                    "Magic match mask is {} bytes long, but value is {} bytes",
+                    mask.len(),
+                    value.len()
+                )));
+            }
+            Some(mask)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            match_type,
+            offset_start,
+            offset_end,
+            value,
+            mask,
+            children: Vec::new(),
+        })
+    }
+
+    pub fn match_type(&self) -> MagicMatchType {
+        self.match_type
+    }
+
+    /// The inclusive range of offsets at which the value may begin
+    pub fn offset(&self) -> (usize, usize) {
+        (self.offset_start, self.offset_end)
+    }
+
+    /// The bytes being searched for, in file order
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    pub fn mask(&self) -> Option<&[u8]> {
+        self.mask.as_deref()
+    }
+
+    pub fn children(&self) -> &[MagicMatch] {
+        &self.children
+    }
+
+    pub(crate) fn add_child(&mut self, child: MagicMatch) {
+        self.children.push(child);
+    }
+
+    /// The number of leading bytes of a file needed to evaluate this match and its children
+    pub fn extent(&self) -> usize {
+        let own_extent = self.offset_end + self.value.len();
+        self.children
+            .iter()
+            .map(|c| c.extent())
+            .fold(own_extent, usize::max)
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        if !self.matches_self(data) {
+            return false;
+        }
+        self.children.is_empty() || self.children.iter().any(|c| c.matches(data))
+    }
+
+    fn matches_self(&self, data: &[u8]) -> bool {
+        let len = self.value.len();
+        for start in self.offset_start..=self.offset_end {
+            let Some(window) = data.get(start..start + len) else {
+                return false;
+            };
+
+            let is_match = if let Some(mask) = &self.mask {
+                window
+                    .iter()
+                    .zip(self.value.iter())
+                    .zip(mask.iter())
+                    .all(|((d, v), m)| d & m == v & m)
+            } else {
+                window == self.value.as_slice()
+            };
+
+            if is_match {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Parse offsets in the form "4" or "0:256"
+    fn parse_offset(offset: &str) -> Result<(usize, usize), Error> {
+        let offset = offset.trim();
+        let parse = |value: &str| {
+            value.trim().parse::<usize>().map_err(|e| {
+                Error::InvalidMagic(format!(
+                    "Unable to parse magic match offset \"{}\": {}",
+                    offset, e
+                ))
+            })
+        };
+        if let Some((start, end)) = offset.split_once(':') {
+            let start = parse(start)?;
+            let end = parse(end)?;
+            if end < start {
+                return Err(Error::InvalidMagic(format!(
+                    "Magic match offset range \"{}\" is inverted",
+                    offset
+                )));
+            }
+            Ok((start, end))
+        } else {
+            let start = parse(offset)?;
+            Ok((start, start))
+        }
+    }
+
+    /// Parse numbers as C's strtol with base 0 would: "0x" prefix is hex, a leading "0" is octal
+    fn parse_number(value: &str) -> Result<u64, Error> {
+        let value = value.trim();
+        let result = if let Some(hex) = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            u64::from_str_radix(hex, 16)
+        } else if value.len() > 1 && value.starts_with('0') {
+            u64::from_str_radix(&value[1..], 8)
+        } else {
+            value.parse::<u64>()
+        };
+
+        result.map_err(|e| {
+            Error::InvalidMagic(format!("Unable to parse magic number \"{}\": {}", value, e))
+        })
+    }
+
+    /// Parse a mask for a string match, which is always written as a hex number, e.g., "0xffff00ff"
+    fn parse_hex_bytes(value: &str) -> Result<Vec<u8>, Error> {
+        let value = value.trim();
+        let Some(hex) = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        else {
+            return Err(Error::InvalidMagic(format!(
+                "String magic mask \"{}\" should be hexadecimal",
+                value
+            )));
+        };
+        if hex.len() % 2 != 0 {
+            return Err(Error::InvalidMagic(format!(
+                "String magic mask \"{}\" has an odd number of digits",
+                value
+            )));
+        }
+
+        let mut bytes = Vec::with_capacity(hex.len() / 2);
+        for i in (0..hex.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| {
+                Error::InvalidMagic(format!(
+                    "Unable to parse string magic mask \"{}\": {}",
+                    value, e
+                ))
+            })?);
+        }
+        Ok(bytes)
+    }
+
+    /// Decode the escapes used in string match values: `\xHH`, octal `\NNN`, `\n`, `\r`, `\t`,
+    /// with any other escaped character standing for itself.
+    fn parse_string_value(value: &str) -> Vec<u8> {
+        let bytes = value.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if bytes[i] != b'\\' || i + 1 == bytes.len() {
+                result.push(bytes[i]);
+                i += 1;
+                continue;
+            }
+
+            i += 1;
+            match bytes[i] {
+                b'x' => {
+                    let digits = bytes[i + 1..]
+                        .iter()
+                        .take(2)
+                        .take_while(|b| b.is_ascii_hexdigit())
+                        .count();
+                    if digits == 0 {
+                        result.push(b'x');
+                    } else {
+                        let hex = std::str::from_utf8(&bytes[i + 1..i + 1 + digits]).unwrap();
+                        result.push(u8::from_str_radix(hex, 16).unwrap());
+                    }
+                    i += 1 + digits;
+                }
+                b'0'..=b'7' => {
+                    let digits = bytes[i..]
+                        .iter()
+                        .take(3)
+                        .take_while(|b| (b'0'..=b'7').contains(*b))
+                        .count();
+                    let octal = std::str::from_utf8(&bytes[i..i + digits]).unwrap();
+                    result.push((u32::from_str_radix(octal, 8).unwrap() & 0xff) as u8);
+                    i += digits;
+                }
+                b'n' => {
+                    result.push(b'\n');
+                    i += 1;
+                }
+                b'r' => {
+                    result.push(b'\r');
+                    i += 1;
+                }
+                b't' => {
+                    result.push(b'\t');
+                    i += 1;
+                }
+                other => {
+                    result.push(other);
+                    i += 1;
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Represents a `<magic>` block of a mime type; the block matches if any of its top level matches do.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MimeTypeMagic {
+    priority: u32,
+    matches: Vec<MagicMatch>,
+}
+
+impl MimeTypeMagic {
+    pub const DEFAULT_PRIORITY: u32 = 50;
+
+    pub fn new(priority: u32) -> Self {
+        Self {
+            priority,
+            matches: Vec::new(),
+        }
+    }
+
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    pub fn matches(&self) -> &[MagicMatch] {
+        &self.matches
+    }
+
+    pub(crate) fn add_match(&mut self, magic_match: MagicMatch) {
+        self.matches.push(magic_match);
+    }
+
+    /// The number of leading bytes of a file needed to evaluate this magic block
+    pub fn extent(&self) -> usize {
+        self.matches.iter().map(|m| m.extent()).max().unwrap_or(0)
+    }
+
+    /// Returns true if the provided file content is matched by this magic block
+    pub fn test(&self, data: &[u8]) -> bool {
+        self.matches.iter().any(|m| m.matches(data))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_string_escapes() {
+        assert_eq!(MagicMatch::parse_string_value("\\x89PNG"), b"\x89PNG");
+        assert_eq!(
+            MagicMatch::parse_string_value("\\177ELF\\0\\004"),
+            b"\x7fELF\x00\x04"
+        );
+        assert_eq!(MagicMatch::parse_string_value("{\\\\rtf"), b"{\\rtf");
+        assert_eq!(MagicMatch::parse_string_value("a\\nb"), b"a\nb");
+    }
+
+    #[test]
+    fn parses_numbers_in_file_byte_order() -> anyhow::Result<()> {
+        let big = MagicMatch::parse("big16", "0x9501", "0", None)?;
+        assert_eq!(big.value(), &[0x95, 0x01]);
+
+        let little = MagicMatch::parse("little32", "0x10000419", "8", None)?;
+        assert_eq!(little.value(), &[0x19, 0x04, 0x00, 0x10]);
+
+        let byte = MagicMatch::parse("byte", "0x41", "8", None)?;
+        assert_eq!(byte.value(), &[0x41]);
+
+        let octal = MagicMatch::parse("byte", "010", "0", None)?;
+        assert_eq!(octal.value(), &[8]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn matches_offset_ranges_and_masks() -> anyhow::Result<()> {
+        let ranged = MagicMatch::parse("string", "[Desktop Entry]", "0:32", None)?;
+        assert!(ranged.matches(b"#!/usr/bin/env xdg-open\n[Desktop Entry]\n"));
+        assert!(!ranged.matches(b"[Desktop"));
+
+        let masked = MagicMatch::parse(
+            "string",
+            "BMxxxx\\000\\000",
+            "0",
+            Some("0xffff00000000ffff"),
+        )?;
+        assert!(masked.matches(b"BM\x01\x02\x03\x04\x00\x00"));
+        assert!(!masked.matches(b"BM\x01\x02\x03\x04\x00\x01"));
+
+        assert!(MagicMatch::parse("string", "abc", "0", Some("0xffff")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested_matches_require_a_matching_child() -> anyhow::Result<()> {
+        let mut parent = MagicMatch::parse("string", "RIFF", "0", None)?;
+        parent.add_child(MagicMatch::parse("string", "WAVE", "8", None)?);
+
+        assert!(parent.matches(b"RIFF\0\0\0\0WAVE"));
+        assert!(!parent.matches(b"RIFF\0\0\0\0AVI "));
+        assert_eq!(parent.extent(), 12);
+
+        Ok(())
+    }
+}
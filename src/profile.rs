@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{DesktopEntryId, Error, MimeType};
+
+/// The file formats a profile can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Json,
+    Toml,
+}
+
+impl ProfileFormat {
+    /// The format implied by the extension of `path`, e.g., `team.toml`; None if it's neither `.json` nor `.toml`
+    pub fn from_path<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Parse `contents` as a `T` written in `format`, e.g., a `Profile` or a `DesiredState`
+pub(crate) fn parse_in<T>(contents: &str, format: ProfileFormat) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    match format {
+        ProfileFormat::Json => {
+            serde_json::from_str(contents).map_err(|e| Error::InvalidProfile(e.to_string()))
+        }
+        ProfileFormat::Toml => {
+            toml::from_str(contents).map_err(|e| Error::InvalidProfile(e.to_string()))
+        }
+    }
+}
+
+/// Read the `T` at `path`, in the format implied by its extension, or JSON if the extension is unknown
+pub(crate) fn load_in<T, P>(path: P) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    parse_in(
+        &contents,
+        ProfileFormat::from_path(path).unwrap_or(ProfileFormat::Json),
+    )
+}
+
+/// How applications are identified when exporting a profile
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProfileIdentifiers {
+    /// The exact desktop entry id, e.g., "org.mozilla.firefox.desktop"
+    #[default]
+    Ids,
+    /// The desktop entry id, followed by the application's untranslated name, e.g., "Firefox", as a fallback,
+    /// so a profile can be shared between machines which package an application under different ids
+    Names,
+}
+
+/// A portable description of a user's default applications and added associations, for sharing a reviewed
+/// set of default applications between machines without copying a `mimeapps.list`.
+///
+/// Each mime type maps to a list of application identifiers, each of which may be a desktop entry id, an
+/// application name or the last component of an id, as accepted by `lookup_desktop_entry`. For default
+/// applications the list is in preference order, and the first installed application which supports the mime
+/// type is assigned; for added associations every installed application is associated. E.g., in TOML:
+///
+/// ```toml
+/// [default_applications]
+/// "text/html" = ["org.mozilla.firefox.desktop", "Firefox", "chromium"]
+///
+/// [added_associations]
+/// "image/png" = ["org.gimp.GIMP.desktop"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default_applications: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub added_associations: BTreeMap<String, Vec<String>>,
+}
+
+impl Profile {
+    pub fn parse(contents: &str, format: ProfileFormat) -> Result<Self, Error> {
+        parse_in(contents, format)
+    }
+
+    /// Read the profile at `path`, in the format implied by its extension, or JSON if the extension is unknown
+    pub fn load<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        load_in(path)
+    }
+
+    pub fn to_string(&self, format: ProfileFormat) -> Result<String, Error> {
+        match format {
+            ProfileFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| Error::InvalidProfile(e.to_string()))
+            }
+            ProfileFormat::Toml => {
+                toml::to_string(self).map_err(|e| Error::InvalidProfile(e.to_string()))
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default_applications.is_empty() && self.added_associations.is_empty()
+    }
+}
+
+/// The sections of a profile, or of a `DesiredState`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ProfileSection {
+    DefaultApplications,
+    AddedAssociations,
+    Reset,
+}
+
+/// Why a profile entry wasn't imported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum SkipReason {
+    /// The key isn't a valid mime type
+    InvalidMimeType,
+    /// None of the identifiers match an installed application
+    NotInstalled,
+    /// The matching applications don't support the mime type
+    Unsupported,
+    /// The matching applications which support the mime type don't appear to be launchable
+    NotLaunchable,
+}
+
+/// A profile entry which wasn't imported
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SkippedProfileEntry {
+    pub section: ProfileSection,
+    pub mime_type: String,
+    pub identifiers: Vec<String>,
+    pub reason: SkipReason,
+}
+
+/// The result of `MimeTypeAssociationStore::import_profile`: the assignments and associations made,
+/// and the entries which were skipped
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProfileImport {
+    pub default_applications: Vec<(MimeType, DesktopEntryId)>,
+    pub added_associations: Vec<(MimeType, DesktopEntryId)>,
+    pub skipped: Vec<SkippedProfileEntry>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_roundtrip_through_json_and_toml() -> anyhow::Result<()> {
+        let toml = "[default_applications]\n\"text/html\" = [\"Firefox\", \"org.mozilla.firefox.desktop\"]\n\n[added_associations]\n\"image/png\" = [\"org.gimp.GIMP.desktop\"]\n";
+        let profile = Profile::parse(toml, ProfileFormat::Toml)?;
+        assert_eq!(
+            profile.default_applications["text/html"],
+            ["Firefox", "org.mozilla.firefox.desktop"]
+        );
+        assert_eq!(
+            profile.added_associations["image/png"],
+            ["org.gimp.GIMP.desktop"]
+        );
+
+        for format in [ProfileFormat::Json, ProfileFormat::Toml] {
+            assert_eq!(
+                Profile::parse(&profile.to_string(format)?, format)?,
+                profile
+            );
+        }
+
+        // either section may be omitted
+        let profile = Profile::parse(
+            r#"{ "default_applications": { "text/plain": ["gedit"] } }"#,
+            ProfileFormat::Json,
+        )?;
+        assert!(profile.added_associations.is_empty());
+        assert!(!profile.is_empty());
+
+        assert!(matches!(
+            Profile::parse("default_applications = 1", ProfileFormat::Toml),
+            Err(Error::InvalidProfile(_))
+        ));
+        assert_eq!(
+            ProfileFormat::from_path("team.toml"),
+            Some(ProfileFormat::Toml)
+        );
+        assert_eq!(ProfileFormat::from_path("mimeapps.list"), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{DesktopEntryId, MimeType};
+
+/// How a mime type consulted while resolving relates to the requested one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum CandidateOrigin {
+    /// The requested mime type itself
+    Requested,
+    /// The canonical mime type the requested one is an alias of
+    Alias,
+    /// A mime type the requested one is a subclass of, e.g., text/plain for text/x-csrc
+    Ancestor,
+    /// A wildcard entry matching the mime type consulted before it, e.g., `image/*` for image/png
+    Wildcard,
+}
+
+/// What became of an application considered while resolving
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TracedOutcome {
+    /// The application was chosen
+    Chosen,
+    /// The application is listed, but isn't installed
+    NotInstalled,
+    /// The application is installed, but isn't shown in the current desktop, or its `TryExec` fails
+    NotLaunchable,
+    /// The application is hidden from the mime type by a `[Removed Associations]` entry
+    Removed,
+    /// An application listed before it, or in a higher priority scope, was chosen
+    Overridden,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TracedApplication {
+    pub desktop_entry: DesktopEntryId,
+    pub outcome: TracedOutcome,
+}
+
+/// A lookup of a mime type in the `[Default Applications]` of one scope
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ScopeTraceStep {
+    pub path: PathBuf,
+    pub is_user_customizable: bool,
+    pub mime_type: MimeType,
+    pub origin: CandidateOrigin,
+    /// The 1-based line of the entry, if the scope has one for the mime type and it has been saved
+    pub line: Option<usize>,
+    /// The applications the entry lists, in preference order; empty if the scope has no entry
+    pub applications: Vec<TracedApplication>,
+}
+
+/// A search of the applications able to open a mime type, made when no scope assigns an installed application
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FallbackTraceStep {
+    pub mime_type: MimeType,
+    pub origin: CandidateOrigin,
+    /// The added associations, then the applications declaring support for the mime type, and those removed
+    pub applications: Vec<TracedApplication>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum TraceStep {
+    Scope(ScopeTraceStep),
+    Fallback(FallbackTraceStep),
+}
+
+/// A record of how `MimeTypeAssociationStore::resolve_application_for` chose the application for a mime type:
+/// each scope consulted for each candidate mime type, in order, and what became of each application listed.
+/// Produced by `MimeTypeAssociationStore::trace_resolution`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ResolutionTrace {
+    pub mime_type: MimeType,
+    pub steps: Vec<TraceStep>,
+    pub result: Option<DesktopEntryId>,
+}
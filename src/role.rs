@@ -0,0 +1,434 @@
+use std::path::{Path, PathBuf};
+
+use crate::{DesktopEntry, DesktopEntryId, DesktopEntryStore, Error, KeyFileValue, MimeType};
+
+/// The roles every RoleStore starts with, as (id, name, mime types)
+const BUILTIN_ROLES: &[(&str, &str, &[&str])] = &[
+    (
+        "browser",
+        "Web Browser",
+        &[
+            "x-scheme-handler/http",
+            "x-scheme-handler/https",
+            "text/html",
+            "application/xhtml+xml",
+        ],
+    ),
+    (
+        "mail",
+        "Mail",
+        &["x-scheme-handler/mailto", "message/rfc822"],
+    ),
+    (
+        "music",
+        "Music",
+        &[
+            "audio/mpeg",
+            "audio/flac",
+            "audio/ogg",
+            "audio/x-vorbis+ogg",
+            "audio/mp4",
+            "audio/aac",
+            "audio/x-wav",
+        ],
+    ),
+    (
+        "video",
+        "Video",
+        &[
+            "video/mp4",
+            "video/x-matroska",
+            "video/webm",
+            "video/mpeg",
+            "video/quicktime",
+            "video/x-msvideo",
+            "video/ogg",
+        ],
+    ),
+    (
+        "photos",
+        "Photos",
+        &[
+            "image/jpeg",
+            "image/png",
+            "image/gif",
+            "image/webp",
+            "image/bmp",
+            "image/tiff",
+        ],
+    ),
+    ("text-editor", "Text Editor", &["text/plain"]),
+    ("file-manager", "File Manager", &["inode/directory"]),
+    // the MIME Apps spec has no type for terminals, so the role owns a pseudo scheme: a terminal takes the role by
+    // listing `x-scheme-handler/terminal` in its MimeType, and programs find the user's terminal by looking up the
+    // scheme's default application, e.g., with `xdg-mime query default x-scheme-handler/terminal`
+    ("terminal", "Terminal", &["x-scheme-handler/terminal"]),
+];
+
+/// A group of related mime types which are usually handled by one application, e.g., a web browser
+/// handles `x-scheme-handler/http`, `x-scheme-handler/https`, `text/html` and `application/xhtml+xml`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Role {
+    id: String,
+    name: String,
+    mime_types: Vec<MimeType>,
+    is_builtin: bool,
+}
+
+impl Role {
+    pub fn new(id: &str, name: &str, mime_types: Vec<MimeType>) -> Self {
+        Self {
+            id: id.to_lowercase(),
+            name: name.to_string(),
+            mime_types,
+            is_builtin: false,
+        }
+    }
+
+    /// The role's identifier, e.g., "browser"; always lowercase
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The human readable name of the role, e.g., "Web Browser"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mime_types(&self) -> &[MimeType] {
+        &self.mime_types
+    }
+
+    /// True if the role is one of mimeassoc's built-in roles, rather than user-defined
+    pub fn is_builtin(&self) -> bool {
+        self.is_builtin
+    }
+
+    /// True if `desktop_entry` supports at least one of the role's mime types
+    pub fn can_be_filled_by(&self, desktop_entry: &DesktopEntry) -> bool {
+        self.mime_types
+            .iter()
+            .any(|mime_type| desktop_entry.can_open_mime_type(mime_type))
+    }
+}
+
+/// The registry of roles: the built-in roles, plus any defined by the user in a roles file, e.g.:
+/// ```text
+/// [Role ebook-reader]
+/// Name=E-Book Reader
+/// MimeType=application/epub+zip;application/x-mobipocket-ebook;
+/// ```
+/// A user-defined role with the id of a built-in role replaces it.
+pub struct RoleStore {
+    roles: Vec<Role>,
+    source: Option<PathBuf>,
+}
+
+impl RoleStore {
+    /// Create a store with just the built-in roles
+    pub fn builtin() -> Self {
+        let roles = BUILTIN_ROLES
+            .iter()
+            .map(|(id, name, mime_types)| Role {
+                id: id.to_string(),
+                name: name.to_string(),
+                mime_types: mime_types
+                    .iter()
+                    .map(|mime_type| MimeType::parse(mime_type).expect("Invalid built-in role"))
+                    .collect(),
+                is_builtin: true,
+            })
+            .collect();
+
+        Self {
+            roles,
+            source: None,
+        }
+    }
+
+    /// Create a store with the built-in roles and those defined in the roles file at `path`.
+    /// A missing roles file is not an error, since defining roles is optional.
+    pub fn load<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut store = Self::builtin();
+        if !path.exists() {
+            return Ok(store);
+        }
+
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        for role in parse_roles(path, &text)? {
+            store.insert(role);
+        }
+        store.source = Some(path.to_path_buf());
+
+        Ok(store)
+    }
+
+    /// Add a role, replacing any existing role with the same id
+    pub fn insert(&mut self, role: Role) {
+        if let Some(existing) = self.roles.iter_mut().find(|r| r.id == role.id) {
+            *existing = role;
+        } else {
+            self.roles.push(role);
+        }
+    }
+
+    /// All roles, built-in roles first, in the order they were defined
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    /// Find a role by id, ignoring case
+    pub fn find_role(&self, id: &str) -> Option<&Role> {
+        let id = id.to_lowercase();
+        self.roles.iter().find(|role| role.id == id)
+    }
+
+    /// Returns the roles which `desktop_entry` supports at least one mime type of
+    pub fn roles_for(&self, desktop_entry: &DesktopEntry) -> Vec<&Role> {
+        self.roles
+            .iter()
+            .filter(|role| role.can_be_filled_by(desktop_entry))
+            .collect()
+    }
+
+    /// The roles file loaded, if one exists
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+}
+
+/// Parse the `[Role <id>]` groups of the roles file at `path`. Other groups, comments and unknown keys are ignored.
+fn parse_roles(path: &Path, text: &str) -> Result<Vec<Role>, Error> {
+    // each role is paired with the line number of its group header, for reporting roles without mime types
+    let mut roles: Vec<(Role, usize)> = vec![];
+    let mut current: Option<(Role, usize)> = None;
+
+    for (line_index, line) in text.lines().map(str::trim).enumerate() {
+        let line_number = line_index + 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            roles.extend(current.take());
+            if let Some(id) = line[1..line.len() - 1].strip_prefix("Role ") {
+                let id = id.trim();
+                if id.is_empty() {
+                    return Err(Error::parse(
+                        path,
+                        line_number,
+                        format!("Role group \"{}\" has no id", line),
+                    ));
+                }
+                current = Some((Role::new(id, id, vec![]), line_number));
+            }
+            continue;
+        }
+
+        let (Some((role, _)), Some((key, value))) = (current.as_mut(), line.split_once('=')) else {
+            continue;
+        };
+        let value = KeyFileValue::new(value.trim());
+        match key.trim() {
+            "Name" => role.name = value.as_string(),
+            "MimeType" => {
+                for mime_type in value.as_string_list() {
+                    let mime_type = MimeType::parse(&mime_type)
+                        .map_err(|e| Error::parse(path, line_number, e.to_string()))?;
+                    role.mime_types.push(mime_type);
+                }
+            }
+            _ => {}
+        }
+    }
+    roles.extend(current);
+
+    for (role, line_number) in roles.iter() {
+        if role.mime_types.is_empty() {
+            return Err(Error::parse(
+                path,
+                *line_number,
+                format!("Role \"{}\" has no mime types", role.id),
+            ));
+        }
+    }
+
+    Ok(roles.into_iter().map(|(role, _)| role).collect())
+}
+
+/// The applications handling each mime type of a role, from `MimeTypeAssociationStore::role_handlers`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoleHandlers {
+    handlers: Vec<(MimeType, Option<DesktopEntryId>)>,
+    owner: Option<DesktopEntryId>,
+    unowned: Vec<MimeType>,
+}
+
+impl RoleHandlers {
+    pub(crate) fn new(
+        handlers: Vec<(MimeType, Option<DesktopEntryId>)>,
+        desktop_entry_store: &DesktopEntryStore,
+    ) -> Self {
+        // the owner is the application handling the most of the role's types; ties go to the earliest listed
+        let mut counts: Vec<(&DesktopEntryId, usize)> = vec![];
+        for id in handlers.iter().filter_map(|(_, id)| id.as_ref()) {
+            match counts.iter_mut().find(|(counted, _)| *counted == id) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((id, 1)),
+            }
+        }
+        let mut owner: Option<(&DesktopEntryId, usize)> = None;
+        for (id, count) in counts {
+            if owner.is_none_or(|(_, max)| count > max) {
+                owner = Some((id, count));
+            }
+        }
+        let owner = owner.map(|(id, _)| id.clone());
+
+        // types the owner can open, but which are handled by another application, or none
+        let unowned = match owner
+            .as_ref()
+            .and_then(|id| desktop_entry_store.find_desktop_entry_with_id(id))
+        {
+            Some(owner_entry) => handlers
+                .iter()
+                .filter(|(mime_type, id)| {
+                    id.as_ref() != owner.as_ref() && owner_entry.can_open_mime_type(mime_type)
+                })
+                .map(|(mime_type, _)| mime_type.clone())
+                .collect(),
+            None => vec![],
+        };
+
+        Self {
+            handlers,
+            owner,
+            unowned,
+        }
+    }
+
+    /// Each mime type of the role, with the installed application handling it, if any
+    pub fn handlers(&self) -> &[(MimeType, Option<DesktopEntryId>)] {
+        &self.handlers
+    }
+
+    /// The application handling the most of the role's mime types
+    pub fn owner(&self) -> Option<&DesktopEntryId> {
+        self.owner.as_ref()
+    }
+
+    /// The mime types the owner supports, but which another application, or none, handles
+    pub fn unowned_mime_types(&self) -> &[MimeType] {
+        &self.unowned
+    }
+
+    /// True if the owner handles every mime type of the role it supports
+    pub fn is_fully_owned(&self) -> bool {
+        self.owner.is_some() && self.unowned.is_empty()
+    }
+
+    /// True if the owner handles only some of the mime types of the role it supports, e.g., a browser
+    /// assigned to `x-scheme-handler/https` but not to `text/html`
+    pub fn is_partially_owned(&self) -> bool {
+        self.owner.is_some() && !self.unowned.is_empty()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_roles_are_valid() {
+        let store = RoleStore::builtin();
+        for id in [
+            "browser",
+            "mail",
+            "music",
+            "video",
+            "photos",
+            "text-editor",
+            "file-manager",
+            "terminal",
+        ] {
+            let role = store.find_role(id).unwrap();
+            assert!(role.is_builtin());
+            assert!(!role.mime_types().is_empty());
+        }
+
+        let browser = store.find_role("Browser").unwrap();
+        assert_eq!(browser.name(), "Web Browser");
+        assert!(browser
+            .mime_types()
+            .contains(&MimeType::parse("x-scheme-handler/https").unwrap()));
+    }
+
+    #[test]
+    fn parses_user_defined_roles() -> anyhow::Result<()> {
+        let path = Path::new("roles.conf");
+        let roles = parse_roles(
+            path,
+            "# my roles\n[Role Ebook-Reader]\nName=E-Book\\sReader\n\
+            MimeType=application/epub+zip;application/x-mobipocket-ebook;\n\
+            [Other Group]\nMimeType=text/plain\n\
+            [Role browser]\nMimeType=text/html;\n",
+        )?;
+
+        assert_eq!(roles.len(), 2);
+        assert_eq!(roles[0].id(), "ebook-reader");
+        assert_eq!(roles[0].name(), "E-Book Reader");
+        assert_eq!(
+            roles[0].mime_types(),
+            [
+                MimeType::parse("application/epub+zip")?,
+                MimeType::parse("application/x-mobipocket-ebook")?
+            ]
+        );
+        assert!(!roles[0].is_builtin());
+        assert_eq!(roles[1].name(), "browser");
+
+        assert!(matches!(
+            parse_roles(path, "[Role empty]\nName=Empty\n"),
+            Err(Error::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_roles(path, "# bad\n[Role bad]\nMimeType=not a mime type\n"),
+            Err(Error::Parse { line: 3, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn user_defined_roles_replace_builtin_roles() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("mimeassoc-role-tests-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("roles.conf");
+        std::fs::write(
+            &path,
+            "[Role browser]\nName=Browser\nMimeType=text/html;\n[Role ebook-reader]\nMimeType=application/epub+zip\n",
+        )?;
+
+        let store = RoleStore::load(&path)?;
+        assert_eq!(store.source(), Some(path.as_path()));
+        assert_eq!(store.roles().len(), RoleStore::builtin().roles().len() + 1);
+
+        let browser = store.find_role("browser").unwrap();
+        assert!(!browser.is_builtin());
+        assert_eq!(browser.mime_types(), [MimeType::parse("text/html")?]);
+        assert!(store.find_role("ebook-reader").is_some());
+
+        let missing = RoleStore::load(dir.join("missing.conf"))?;
+        assert_eq!(missing.source(), None);
+        assert_eq!(missing.roles(), RoleStore::builtin().roles());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,583 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+use crate::{
+    has_extension, DesktopEntryStore, Environment, Error, MimeTypeAssociationStore,
+    MimeTypeInfoStore,
+};
+
+/// The kinds of files a watched directory is of interest for. A directory may hold several, e.g.,
+/// `~/.local/share/applications` holds both desktop entries and a mimeapps.list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct WatchedKinds {
+    mime_associations: bool,
+    desktop_entries: bool,
+    mime_info: bool,
+}
+
+impl WatchedKinds {
+    fn any(&self) -> bool {
+        self.mime_associations || self.desktop_entries || self.mime_info
+    }
+}
+
+struct WatchedDirectory {
+    directory: PathBuf,
+    kinds: WatchedKinds,
+}
+
+/// The stores affected by a burst of file system changes, along with the paths which changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreChanges {
+    // each changed path, with the stores it affects
+    changes: Vec<(PathBuf, WatchedKinds)>,
+}
+
+impl StoreChanges {
+    /// True if a mimeapps.list or defaults.list was written, created or removed
+    pub fn mime_associations(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|(_, kinds)| kinds.mime_associations)
+    }
+
+    /// True if a desktop entry, or a directory of desktop entries, was written, created or removed
+    pub fn desktop_entries(&self) -> bool {
+        self.changes.iter().any(|(_, kinds)| kinds.desktop_entries)
+    }
+
+    /// True if a shared-mime-info package was written, created or removed
+    pub fn mime_info(&self) -> bool {
+        self.changes.iter().any(|(_, kinds)| kinds.mime_info)
+    }
+
+    /// The changed paths, in the order they were first reported
+    pub fn paths(&self) -> Vec<&Path> {
+        self.changes
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn record(&mut self, kinds: WatchedKinds, path: PathBuf) {
+        if !kinds.any() {
+            return;
+        }
+        match self.changes.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, recorded_kinds)) => {
+                recorded_kinds.mime_associations |= kinds.mime_associations;
+                recorded_kinds.desktop_entries |= kinds.desktop_entries;
+                recorded_kinds.mime_info |= kinds.mime_info;
+            }
+            None => self.changes.push((path, kinds)),
+        }
+    }
+
+    fn remove<P>(&mut self, paths: &[P])
+    where
+        P: AsRef<Path>,
+    {
+        self.changes
+            .retain(|(path, _)| !paths.iter().any(|p| p.as_ref() == path));
+    }
+}
+
+/// Watches the files the stores were loaded from with inotify: the directories holding the
+/// mimeapps.list scopes, the desktop entry directories (recursively), and the directories holding the
+/// mime info packages, along with every directory of the environment which may come to hold them, so that,
+/// e.g., a first `~/.local/share/applications` is noticed. A directory which doesn't exist yet is watched through
+/// its nearest existing ancestor until it's created. Changes are coalesced, so that a package install which touches many files, or an
+/// editor which writes a file in several steps, is reported once the file system has been quiet for a while.
+///
+/// The watcher never blocks; call `poll` periodically, e.g., from a UI timer.
+pub struct StoreWatcher {
+    inotify: Inotify,
+    watches: HashMap<WatchDescriptor, WatchedDirectory>,
+    pending: StoreChanges,
+    last_event: Option<Instant>,
+    quiet_period: Duration,
+    buffer: Vec<u8>,
+    // directories of the environment which don't exist yet, and the kinds of files they'd hold
+    missing: Vec<(PathBuf, WatchedKinds)>,
+}
+
+impl StoreWatcher {
+    /// The default time the file system must be quiet for before pending changes are reported
+    pub const DEFAULT_QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+    const MASK: WatchMask = WatchMask::CLOSE_WRITE
+        .union(WatchMask::MOVED_TO)
+        .union(WatchMask::MOVED_FROM)
+        .union(WatchMask::CREATE)
+        .union(WatchMask::DELETE);
+
+    /// Create a watcher for the sources of the provided stores, and the directories of `environment` they'd
+    /// be reloaded from
+    pub fn new(
+        environment: &Environment,
+        mime_associations_store: &MimeTypeAssociationStore,
+        desktop_entry_store: &DesktopEntryStore,
+        mime_info_store: &MimeTypeInfoStore,
+    ) -> Result<Self, Error> {
+        let mut watcher = Self {
+            inotify: Inotify::init().map_err(Error::Watch)?,
+            watches: HashMap::new(),
+            pending: StoreChanges::default(),
+            last_event: None,
+            quiet_period: Self::DEFAULT_QUIET_PERIOD,
+            buffer: vec![0; 4096],
+            missing: vec![],
+        };
+
+        let mime_associations = WatchedKinds {
+            mime_associations: true,
+            ..Default::default()
+        };
+        for path in mime_associations_store.sources() {
+            // the user's mimeapps.list, and its dir, may not exist until saved
+            if let Some(directory) = path.parent() {
+                watcher.watch_candidate(directory.to_path_buf(), mime_associations)?;
+            }
+        }
+        for directory in environment.mimeapps_list_dirs() {
+            watcher.watch_candidate(directory, mime_associations)?;
+        }
+
+        for directory in desktop_entry_store.sources() {
+            watcher.watch_desktop_entry_dir(directory)?;
+        }
+        let desktop_entries = WatchedKinds {
+            desktop_entries: true,
+            ..Default::default()
+        };
+        for directory in environment.applications_dirs() {
+            watcher.watch_candidate(directory, desktop_entries)?;
+        }
+
+        let mime_info = WatchedKinds {
+            mime_info: true,
+            ..Default::default()
+        };
+        for path in mime_info_store.sources() {
+            if let Some(directory) = path.parent() {
+                watcher.watch(directory, mime_info)?;
+            }
+        }
+        for directory in environment.mimeinfo_dirs() {
+            watcher.watch_candidate(directory, mime_info)?;
+        }
+
+        Ok(watcher)
+    }
+
+    /// Set the time the file system must be quiet for before pending changes are reported by `poll`
+    pub fn with_quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.quiet_period = quiet_period;
+        self
+    }
+
+    /// The directories being watched
+    pub fn directories(&self) -> Vec<&Path> {
+        let mut directories = self
+            .watches
+            .values()
+            .map(|w| w.directory.as_path())
+            .collect::<Vec<_>>();
+        directories.sort();
+        directories
+    }
+
+    /// Read any pending file system events without blocking. Returns the changes accumulated since the
+    /// last report, once no further changes have arrived for the quiet period; otherwise returns None.
+    pub fn poll(&mut self) -> Result<Option<StoreChanges>, Error> {
+        self.read_events()?;
+
+        match self.last_event {
+            Some(last_event) if last_event.elapsed() >= self.quiet_period => {
+                self.last_event = None;
+                Ok(Some(std::mem::take(&mut self.pending)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Read any pending file system events, dropping the changes to `paths`, e.g., the files we saved ourselves.
+    /// Changes to other paths are still reported.
+    pub fn ignore_changes_to<P>(&mut self, paths: &[P]) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.read_events()?;
+        self.pending.remove(paths);
+        if self.pending.is_empty() {
+            self.last_event = None;
+        }
+        Ok(())
+    }
+
+    fn read_events(&mut self) -> Result<(), Error> {
+        loop {
+            let events = match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => events,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(Error::Watch(e)),
+            };
+
+            let mut new_directories = vec![];
+            let mut created_missing_ancestor = false;
+            for event in events {
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    // events were dropped, so assume everything changed
+                    log::warn!("StoreWatcher::read_events - event queue overflowed");
+                    for watch in self.watches.values() {
+                        self.pending.record(watch.kinds, watch.directory.clone());
+                    }
+                    created_missing_ancestor = !self.missing.is_empty();
+                    self.last_event = Some(Instant::now());
+                    continue;
+                }
+
+                let (Some(watch), Some(name)) = (self.watches.get(&event.wd), event.name) else {
+                    continue;
+                };
+                let path = watch.directory.join(name);
+                let is_dir = event.mask.contains(EventMask::ISDIR);
+                if is_dir
+                    && event
+                        .mask
+                        .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+                    && self
+                        .missing
+                        .iter()
+                        .any(|(directory, _)| directory.starts_with(&path))
+                {
+                    created_missing_ancestor = true;
+                }
+
+                let kinds = WatchedKinds {
+                    mime_associations: watch.kinds.mime_associations
+                        && !is_dir
+                        && is_mimeapps_list(&path),
+                    desktop_entries: watch.kinds.desktop_entries
+                        && (is_dir || has_extension(&path, "desktop")),
+                    mime_info: watch.kinds.mime_info && !is_dir && has_extension(&path, "xml"),
+                };
+                if !kinds.any() {
+                    continue;
+                }
+
+                log::debug!("StoreWatcher::read_events - {:?} {:?}", event.mask, path);
+                if kinds.desktop_entries
+                    && is_dir
+                    && event
+                        .mask
+                        .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+                {
+                    new_directories.push(path.clone());
+                }
+                self.pending.record(kinds, path);
+                self.last_event = Some(Instant::now());
+            }
+
+            // desktop entries may be installed to new subdirectories, which must be watched too
+            for directory in new_directories {
+                if directory.is_dir() {
+                    self.watch_desktop_entry_dir(&directory)?;
+                }
+            }
+
+            if created_missing_ancestor {
+                self.watch_missing()?;
+            }
+        }
+    }
+
+    /// Watch `directory` for changes to `kinds` of files, if it exists, returning true. Otherwise its nearest existing
+    /// ancestor is watched, so that `directory` can be watched once it's created.
+    fn watch_candidate(&mut self, directory: PathBuf, kinds: WatchedKinds) -> Result<bool, Error> {
+        if directory.is_dir() {
+            if kinds.desktop_entries {
+                self.watch_desktop_entry_dir(&directory)?;
+            } else {
+                self.watch(&directory, kinds)?;
+            }
+            return Ok(true);
+        }
+
+        if let Some(ancestor) = directory.ancestors().skip(1).find(|a| a.is_dir()) {
+            // an ancestor, e.g., `/`, may not be watchable; the directory then goes unnoticed until a reload
+            if let Err(e) = self.watch(ancestor, WatchedKinds::default()) {
+                log::warn!(
+                    "StoreWatcher::watch_candidate - unable to watch for the creation of {:?}: {}",
+                    directory,
+                    e
+                );
+            }
+        }
+        self.missing.push((directory, kinds));
+        Ok(false)
+    }
+
+    /// Watch the missing directories which have been created. Each is reported as changed, since files may have been
+    /// written to it before it was watched. The rest are watched through their nearest existing ancestors.
+    fn watch_missing(&mut self) -> Result<(), Error> {
+        for (directory, kinds) in std::mem::take(&mut self.missing) {
+            if self.watch_candidate(directory.clone(), kinds)? {
+                self.pending.record(kinds, directory);
+                self.last_event = Some(Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    /// Watch `directory` and its subdirectories for changes to desktop entries
+    fn watch_desktop_entry_dir(&mut self, directory: &Path) -> Result<(), Error> {
+        let desktop_entries = WatchedKinds {
+            desktop_entries: true,
+            ..Default::default()
+        };
+        if !self.watch(directory, desktop_entries)? {
+            // already watched, e.g., reached again through a symlink, so its subdirectories are too
+            return Ok(());
+        }
+
+        let Ok(contents) = std::fs::read_dir(directory) else {
+            return Ok(());
+        };
+        for entry in contents.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.watch_desktop_entry_dir(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Watch `directory` for changes to `kinds` of files, returning false if it was already watched for them
+    fn watch(&mut self, directory: &Path, kinds: WatchedKinds) -> Result<bool, Error> {
+        let wd = self
+            .inotify
+            .watches()
+            .add(directory, Self::MASK)
+            .map_err(|e| Error::io(directory, e))?;
+
+        // inotify returns the existing descriptor when a directory is watched again
+        let watch = self.watches.entry(wd).or_insert_with(|| WatchedDirectory {
+            directory: directory.to_path_buf(),
+            kinds: WatchedKinds::default(),
+        });
+        let watched = watch.kinds;
+        watch.kinds.mime_associations |= kinds.mime_associations;
+        watch.kinds.desktop_entries |= kinds.desktop_entries;
+        watch.kinds.mime_info |= kinds.mime_info;
+        Ok(watch.kinds != watched)
+    }
+}
+
+/// True if `path` names a mimeapps.list, a `$desktop-mimeapps.list` or a legacy defaults.list
+fn is_mimeapps_list(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with("mimeapps.list") || name == "defaults.list")
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll_until_changed(watcher: &mut StoreWatcher) -> anyhow::Result<StoreChanges> {
+        for _ in 0..100 {
+            if let Some(changes) = watcher.poll()? {
+                return Ok(changes);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        anyhow::bail!("no changes were reported")
+    }
+
+    /// An environment whose homes are within `dir`, and which has no system dirs
+    fn test_environment(dir: &Path) -> Environment {
+        Environment::for_home(dir)
+            .with_config_home(dir.join("config"))
+            .with_config_dirs(vec![])
+            .with_data_home(dir.join("share"))
+            .with_data_dirs(vec![])
+            .with_state_home(dir.join("state"))
+    }
+
+    #[test]
+    fn reports_coalesced_changes_to_sources() -> anyhow::Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("mimeassoc-store-watcher-{}", std::process::id()));
+        let config_dir = dir.join("config");
+        let applications_dir = dir.join("share/applications");
+        std::fs::create_dir_all(&config_dir)?;
+        std::fs::create_dir_all(&applications_dir)?;
+
+        let mimeapps_list_path = config_dir.join("mimeapps.list");
+        std::fs::write(&mimeapps_list_path, "[Default Applications]\n")?;
+
+        let mime_associations_store = MimeTypeAssociationStore::load(&[&mimeapps_list_path])?;
+        let desktop_entry_store = DesktopEntryStore::load(&[&applications_dir])?;
+        let mime_info_store = MimeTypeInfoStore::load::<PathBuf>(&[])?;
+        let mut watcher = StoreWatcher::new(
+            &test_environment(&dir),
+            &mime_associations_store,
+            &desktop_entry_store,
+            &mime_info_store,
+        )?
+        .with_quiet_period(Duration::from_millis(50));
+        // `share` is watched for the creation of `share/mime/packages`
+        assert_eq!(
+            watcher.directories(),
+            [&config_dir, &dir.join("share"), &applications_dir]
+        );
+        assert_eq!(watcher.poll()?, None);
+
+        // several writes to the same file are reported once; unrelated files are ignored
+        std::fs::write(
+            &mimeapps_list_path,
+            "[Default Applications]\ntext/plain=org.gnome.TextEditor.desktop\n",
+        )?;
+        std::fs::write(&mimeapps_list_path, "[Default Applications]\n")?;
+        std::fs::write(config_dir.join("unrelated.conf"), "")?;
+
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.mime_associations());
+        assert!(!changes.desktop_entries());
+        assert!(!changes.mime_info());
+        assert_eq!(changes.paths(), [mimeapps_list_path.as_path()]);
+        assert_eq!(watcher.poll()?, None);
+
+        // new subdirectories of desktop entry dirs are watched as they appear
+        std::fs::create_dir_all(applications_dir.join("kde4"))?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.desktop_entries());
+        assert!(!changes.mime_associations());
+
+        let desktop_entry_path = applications_dir.join("kde4").join("okular.desktop");
+        std::fs::write(&desktop_entry_path, "[Desktop Entry]\n")?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.desktop_entries());
+        assert_eq!(changes.paths(), [desktop_entry_path.as_path()]);
+
+        // ignoring our own writes keeps changes made by others
+        std::fs::write(&mimeapps_list_path, "[Default Applications]\n")?;
+        std::fs::write(&desktop_entry_path, "[Desktop Entry]\nType=Application\n")?;
+        std::thread::sleep(Duration::from_millis(20));
+        watcher.ignore_changes_to(&[&mimeapps_list_path])?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.desktop_entries());
+        assert!(!changes.mime_associations());
+        assert_eq!(changes.paths(), [desktop_entry_path.as_path()]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn watches_directories_created_later() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-store-watcher-created-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let packages_dir = dir.join("usr/share/mime/packages");
+        std::fs::create_dir_all(&packages_dir)?;
+        std::fs::create_dir_all(dir.join("share"))?;
+
+        let environment = test_environment(&dir).with_data_dirs(vec![dir.join("usr/share")]);
+        let mime_associations_store = MimeTypeAssociationStore::load_for_environment(
+            &environment.mimeapps_lists_paths()?,
+            &environment,
+        )?;
+        let desktop_entry_store = DesktopEntryStore::load(&environment.desktop_entry_dirs())?;
+        let mime_info_store = MimeTypeInfoStore::load(&environment.mimeinfo_paths()?)?;
+        let mut watcher = StoreWatcher::new(
+            &environment,
+            &mime_associations_store,
+            &desktop_entry_store,
+            &mime_info_store,
+        )?
+        .with_quiet_period(Duration::from_millis(50));
+
+        // a first package in an empty `mime/packages`
+        let package_path = packages_dir.join("custom.xml");
+        std::fs::write(&package_path, "<mime-info/>")?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.mime_info());
+        assert_eq!(changes.paths(), [package_path.as_path()]);
+
+        // a first `$desktop-mimeapps.list` in an `applications` dir which didn't exist
+        let system_applications_dir = dir.join("usr/share/applications");
+        std::fs::create_dir_all(&system_applications_dir)?;
+        std::fs::write(
+            system_applications_dir.join("gnome-mimeapps.list"),
+            "[Default Applications]\n",
+        )?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.mime_associations());
+
+        // a first `~/.local/share/applications`, whose subdirectories are watched too
+        let applications_dir = dir.join("share/applications");
+        std::fs::create_dir_all(applications_dir.join("kde4"))?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.desktop_entries());
+        assert!(watcher
+            .directories()
+            .contains(&applications_dir.join("kde4").as_path()));
+
+        let desktop_entry_path = applications_dir.join("kde4/okular.desktop");
+        std::fs::write(&desktop_entry_path, "[Desktop Entry]\n")?;
+        let changes = poll_until_changed(&mut watcher)?;
+        assert!(changes.desktop_entries());
+        assert_eq!(changes.paths(), [desktop_entry_path.as_path()]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn watches_symlink_loops_once() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "mimeassoc-store-watcher-loop-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let applications_dir = dir.join("applications");
+        std::fs::create_dir_all(applications_dir.join("kde4"))?;
+        std::fs::write(applications_dir.join("kde4/okular.desktop"), "")?;
+        std::os::unix::fs::symlink("..", applications_dir.join("kde4/parent"))?;
+
+        let mime_associations_store = MimeTypeAssociationStore::load::<PathBuf>(&[])?;
+        let desktop_entry_store = DesktopEntryStore::load(&[&applications_dir])?;
+        let mime_info_store = MimeTypeInfoStore::load::<PathBuf>(&[])?;
+        let environment = test_environment(&dir)
+            .with_config_home(&dir)
+            .with_data_home(&dir);
+        let watcher = StoreWatcher::new(
+            &environment,
+            &mime_associations_store,
+            &desktop_entry_store,
+            &mime_info_store,
+        )?;
+        assert_eq!(
+            watcher.directories(),
+            [&dir, &applications_dir, &applications_dir.join("kde4")]
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}